@@ -0,0 +1,113 @@
+use std::{error::Error, path::Path};
+
+use rusqlite::Connection;
+
+use crate::{server::CatalogServer, settings};
+
+/// Porta padrão da API local; a 7878 fica para `catalogo serve`.
+pub const DEFAULT_PORT: i64 = 7879;
+
+/// API local para integrações (ERP, macros de planilha), ligada pelo
+/// aplicativo e atendida só neste computador.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: i64,
+    pub token: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+pub fn load_config(conn: &Connection) -> ApiConfig {
+    ApiConfig {
+        enabled: settings::get_i64(conn, "api_enabled", 0) != 0,
+        port: settings::get_i64(conn, "api_port", DEFAULT_PORT),
+        token: settings::get(conn, "api_token")
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+    }
+}
+
+pub fn save_config(conn: &Connection, config: &ApiConfig) -> rusqlite::Result<()> {
+    settings::set(conn, "api_enabled", if config.enabled { "1" } else { "0" })?;
+    settings::set(conn, "api_port", &config.port.to_string())?;
+    settings::set(conn, "api_token", &config.token)
+}
+
+/// Token aleatório de 48 caracteres hexadecimais, gerado pelo SQLite.
+pub fn new_token(conn: &Connection) -> rusqlite::Result<String> {
+    conn.query_row("SELECT lower(hex(randomblob(24)))", [], |row| row.get(0))
+}
+
+/// Inicia a API em `127.0.0.1`, com uma conexão própria ao catálogo.
+pub fn start(
+    db_path: &Path,
+    config: &ApiConfig,
+) -> Result<CatalogServer, Box<dyn Error + Send + Sync>> {
+    if config.token.is_empty() {
+        return Err("gere um token antes de ligar a API".into());
+    }
+    let conn = crate::init_db(db_path).map_err(|e| e.to_string())?;
+    CatalogServer::start(
+        conn,
+        &format!("127.0.0.1:{}", config.port),
+        Some(config.token.clone()),
+    )
+}
+
+#[derive(Default)]
+pub struct ApiView {
+    pub open: bool,
+    pub config: ApiConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{ApiConfig, load_config, new_token, save_config, start};
+
+    #[test]
+    fn api_does_not_start_without_a_token() {
+        let path = std::env::temp_dir().join(format!("catalogo-api-{}.db", std::process::id()));
+        let config = ApiConfig {
+            enabled: true,
+            port: 0,
+            ..Default::default()
+        };
+        match start(&path, &config) {
+            Err(e) => assert!(e.to_string().contains("token"), "{}", e),
+            Ok(_) => panic!("a API não deveria iniciar sem token"),
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn config_is_saved_with_a_new_token() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let config = load_config(&conn);
+        assert_eq!(config, ApiConfig::default());
+
+        let token = new_token(&conn).unwrap();
+        assert_eq!(token.len(), 48);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(new_token(&conn).unwrap(), token);
+        let config = ApiConfig {
+            enabled: true,
+            port: 8080,
+            token,
+        };
+        save_config(&conn, &config).unwrap();
+        assert_eq!(load_config(&conn), config);
+    }
+}
@@ -0,0 +1,365 @@
+use std::{fs::File, io::Write, path::Path};
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::{
+    in_savepoint,
+    undo::{ItemState, Snapshot},
+};
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    // Sem chave estrangeira: o registro continua valendo depois que o item é apagado
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY,
+            recorded_at TEXT NOT NULL,
+            user TEXT NOT NULL,
+            machine TEXT NOT NULL,
+            action TEXT NOT NULL,
+            origin TEXT NOT NULL,
+            item_id INTEGER NOT NULL,
+            old_values TEXT,
+            new_values TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Nome do computador, para distinguir quem usa o mesmo usuário em máquinas diferentes.
+pub fn machine_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "desconhecida".to_string())
+}
+
+fn action(before: Option<&ItemState>, after: Option<&ItemState>) -> &'static str {
+    match (before, after) {
+        (None, _) => "inclusão",
+        (Some(_), None) => "exclusão definitiva",
+        (Some(b), Some(a)) if !b.is_deleted() && a.is_deleted() => "exclusão",
+        (Some(b), Some(a)) if b.is_deleted() && !a.is_deleted() => "restauração",
+        _ => "alteração",
+    }
+}
+
+fn values(state: Option<&ItemState>) -> Option<String> {
+    state.map(|state| {
+        let item = state.item();
+        json!({
+            "descricao": item.description,
+            "marca": item.brand,
+            "fornecedor": item.vendor,
+            "preco": item.price,
+            "atualizado_em": item.updated_at,
+            "tags": item.tags,
+            "categoria": item.category,
+            "unidade": item.unit,
+            "na_lixeira": state.is_deleted(),
+        })
+        .to_string()
+    })
+}
+
+/// Registra cada item que mudou entre os dois retratos. `origin` descreve a
+/// operação que causou a mudança ("Adicionar item", "Importar arquivo.csv", ...).
+/// Chamada dentro da transação da alteração, o registro só fica se ela ficar.
+pub fn record_changes(
+    conn: &Connection,
+    origin: &str,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> rusqlite::Result<()> {
+    let user = crate::current_user();
+    record_changes_by(conn, &user, &machine_name(), origin, before, after)
+}
+
+/// Como `record_changes`, mas atribuída a outro usuário e máquina (alterações
+/// recebidas pelo servidor).
+pub fn record_changes_by(
+    conn: &Connection,
+    user: &str,
+    machine: &str,
+    origin: &str,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> rusqlite::Result<()> {
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    in_savepoint(conn, || {
+        for ((id, b), (_, a)) in before.iter().zip(after) {
+            if b == a {
+                continue;
+            }
+            conn.execute(
+                "INSERT INTO audit_log
+                    (recorded_at, user, machine, action, origin, item_id, old_values, new_values)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                (
+                    &now,
+                    user,
+                    machine,
+                    action(b.as_ref(), a.as_ref()),
+                    origin,
+                    id,
+                    values(b.as_ref()),
+                    values(a.as_ref()),
+                ),
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub struct AuditEntry {
+    pub recorded_at: String,
+    pub user: String,
+    pub machine: String,
+    pub action: String,
+    pub origin: String,
+    pub item_id: i32,
+    pub old_values: String,
+    pub new_values: String,
+}
+
+/// Registros mais recentes primeiro. `query` procura em usuário, máquina,
+/// ação, origem e nos valores; `limit` evita carregar o log inteiro na tela.
+pub fn load_entries(
+    conn: &Connection,
+    query: &str,
+    limit: Option<usize>,
+) -> rusqlite::Result<Vec<AuditEntry>> {
+    let limit = limit.map(|l| l as i64).unwrap_or(-1);
+    let mut stmt = conn.prepare(
+        "SELECT recorded_at, user, machine, action, origin, item_id,
+            COALESCE(old_values, ''), COALESCE(new_values, '')
+        FROM audit_log
+        WHERE ?1 = ''
+            OR user LIKE '%' || ?1 || '%'
+            OR machine LIKE '%' || ?1 || '%'
+            OR action LIKE '%' || ?1 || '%'
+            OR origin LIKE '%' || ?1 || '%'
+            OR old_values LIKE '%' || ?1 || '%'
+            OR new_values LIKE '%' || ?1 || '%'
+        ORDER BY id DESC
+        LIMIT ?2",
+    )?;
+    let entries = stmt.query_map((query.trim(), limit), |row| {
+        Ok(AuditEntry {
+            recorded_at: row.get(0)?,
+            user: row.get(1)?,
+            machine: row.get(2)?,
+            action: row.get(3)?,
+            origin: row.get(4)?,
+            item_id: row.get(5)?,
+            old_values: row.get(6)?,
+            new_values: row.get(7)?,
+        })
+    })?;
+    entries.collect()
+}
+
+impl AuditEntry {
+    /// Resumo legível do que mudou: só os campos diferentes entre os valores
+    /// anteriores e os novos.
+    pub fn summary(&self) -> String {
+        let parse = |text: &str| {
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(text)
+                .unwrap_or_default()
+        };
+        let old = parse(&self.old_values);
+        let new = parse(&self.new_values);
+        let show = |value: Option<&serde_json::Value>| match value {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => "-".to_string(),
+        };
+        if old.is_empty() || new.is_empty() {
+            let values = if old.is_empty() { &new } else { &old };
+            return format!(
+                "{} {} - {} (R$ {})",
+                show(values.get("descricao")),
+                show(values.get("marca")),
+                show(values.get("fornecedor")),
+                show(values.get("preco"))
+            );
+        }
+        new.iter()
+            .filter(|(key, value)| old.get(*key) != Some(*value))
+            .map(|(key, value)| format!("{}: {} → {}", key, show(old.get(key)), show(Some(value))))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+pub fn export_csv(path: &Path, entries: &[AuditEntry]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(b"\xEF\xBB\xBF")?;
+    let mut wtr = csv::WriterBuilder::new().delimiter(b';').from_writer(file);
+
+    wtr.write_record([
+        "data",
+        "usuário",
+        "máquina",
+        "ação",
+        "origem",
+        "id do item",
+        "valores anteriores",
+        "valores novos",
+    ])?;
+    for entry in entries {
+        wtr.write_record([
+            &entry.recorded_at,
+            &entry.user,
+            &entry.machine,
+            &entry.action,
+            &entry.origin,
+            &entry.item_id.to_string(),
+            &entry.old_values,
+            &entry.new_values,
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Estado da janela de auditoria.
+#[derive(Default)]
+pub struct AuditView {
+    pub open: bool,
+    pub query: String,
+    pub entries: Vec<AuditEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{export_csv, load_entries, record_changes, record_changes_by};
+    use crate::{items, trash, undo};
+
+    fn fields(description: &str) -> items::ItemFields {
+        items::ItemFields {
+            description: description.to_string(),
+            brand: "Sil".to_string(),
+            vendor: "Eletro".to_string(),
+            price: 3.5,
+            tags: vec![],
+            category: "Cabos".to_string(),
+            unit: "m".to_string(),
+        }
+    }
+
+    /// Catálogo com uma edição de categoria e unidade e uma exclusão
+    /// registradas, nessa ordem.
+    fn audited_catalog() -> (Connection, i32, i32) {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let cable = items::upsert_item(&conn, &fields("Cabo 2,5mm"), "2024-01-01").unwrap();
+        let socket = items::upsert_item(&conn, &fields("Tomada 10A"), "2024-01-01").unwrap();
+
+        let before = undo::capture(&conn, &[cable, socket]).unwrap();
+        conn.execute(
+            "UPDATE infra_item SET category = 'Fios', unit = 'rolo' WHERE id = ?1",
+            [cable],
+        )
+        .unwrap();
+        let after = undo::capture(&conn, &[cable, socket]).unwrap();
+        record_changes(&conn, "Editar 2 itens em lote", &before, &after).unwrap();
+
+        let before = undo::capture(&conn, &[socket]).unwrap();
+        trash::soft_delete(&mut conn, &[socket], "joao").unwrap();
+        let after = undo::capture(&conn, &[socket]).unwrap();
+        record_changes_by(
+            &conn,
+            "joao",
+            "obra-01",
+            "Excluir 1 item(ns)",
+            &before,
+            &after,
+        )
+        .unwrap();
+        (conn, cable, socket)
+    }
+
+    #[test]
+    fn records_category_and_unit_changes() {
+        let (conn, cable, socket) = audited_catalog();
+        let entries = load_entries(&conn, "", None).unwrap();
+        // O item sem diferença na edição em lote não aparece
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].item_id, socket);
+        assert_eq!(entries[0].action, "exclusão");
+        assert_eq!(
+            (entries[0].user.as_str(), entries[0].machine.as_str()),
+            ("joao", "obra-01")
+        );
+        assert_eq!(entries[0].summary(), "na_lixeira: false → true");
+
+        assert_eq!(entries[1].item_id, cable);
+        assert_eq!(entries[1].action, "alteração");
+        assert_eq!(entries[1].user, crate::current_user());
+        assert_eq!(
+            entries[1].summary(),
+            "categoria: Cabos → Fios; unidade: m → rolo"
+        );
+    }
+
+    #[test]
+    fn summary_of_an_insertion_shows_the_item() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let id = items::upsert_item(&conn, &fields("Cabo 4mm"), "2024-01-01").unwrap();
+        let after = undo::capture(&conn, &[id]).unwrap();
+        record_changes(&conn, "Inserir", &vec![(id, None)], &after).unwrap();
+
+        let entries = load_entries(&conn, "", None).unwrap();
+        assert_eq!(entries[0].action, "inclusão");
+        assert_eq!(entries[0].old_values, "");
+        assert_eq!(entries[0].summary(), "Cabo 4mm Sil - Eletro (R$ 3.5)");
+    }
+
+    #[test]
+    fn entries_are_searched_and_limited() {
+        let (conn, cable, _) = audited_catalog();
+        let found = load_entries(&conn, "rolo", None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].item_id, cable);
+        assert_eq!(load_entries(&conn, " obra-01 ", None).unwrap().len(), 1);
+        assert!(load_entries(&conn, "ninguém", None).unwrap().is_empty());
+        assert_eq!(load_entries(&conn, "", Some(1)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn csv_export_keeps_every_column() {
+        let (conn, _, socket) = audited_catalog();
+        let entries = load_entries(&conn, "", None).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("catalogo-auditoria-{}.csv", std::process::id()));
+        export_csv(&path, &entries).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(bytes.starts_with(b"\xEF\xBB\xBF"));
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .from_reader(&bytes[3..]);
+        assert_eq!(reader.headers().unwrap().get(1), Some("usuário"));
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get(1), Some("joao"));
+        assert_eq!(rows[0].get(3), Some("exclusão"));
+        assert_eq!(rows[0].get(5), Some(socket.to_string().as_str()));
+        assert_eq!(rows[0].get(6), Some(entries[0].old_values.as_str()));
+        assert_eq!(rows[0].get(7), Some(entries[0].new_values.as_str()));
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use eframe::egui::{self, Id, Key, Modifiers, Response, TextEdit, Ui, vec2};
+
+use crate::{InfraItem, filters::fold};
+
+/// Quantidade máxima de sugestões mostradas abaixo do campo.
+pub const MAX_SUGGESTIONS: usize = 8;
+
+/// Valores distintos do campo nos itens cadastrados, dos mais usados para os
+/// menos usados; no empate, o de preço mais recente primeiro.
+pub fn ranked_values(items: &[InfraItem], field: impl Fn(&InfraItem) -> &str) -> Vec<String> {
+    let mut stats: HashMap<&str, (usize, &str)> = HashMap::new();
+    for item in items {
+        let value = field(item);
+        if value.trim().is_empty() {
+            continue;
+        }
+        let entry = stats.entry(value).or_insert((0, ""));
+        entry.0 += 1;
+        entry.1 = entry.1.max(item.updated_at.as_str());
+    }
+    let mut ranked: Vec<(&str, (usize, &str))> = stats.into_iter().collect();
+    ranked.sort_by(|(a, (a_count, a_date)), (b, (b_count, b_date))| {
+        b_count.cmp(a_count).then(b_date.cmp(a_date)).then(a.cmp(b))
+    });
+    ranked
+        .into_iter()
+        .map(|(value, _)| value.to_string())
+        .collect()
+}
+
+/// Valores que começam com o texto digitado e, depois, os que o contêm, sem
+/// diferenciar acentos e maiúsculas. Nada é sugerido quando o texto já é
+/// exatamente um dos valores.
+pub fn suggestions(ranked: &[String], typed: &str) -> Vec<String> {
+    let typed_folded = fold(typed.trim());
+    if typed_folded.is_empty() || ranked.iter().any(|value| value == typed) {
+        return vec![];
+    }
+    let (mut prefixed, mut containing) = (vec![], vec![]);
+    for value in ranked {
+        let folded = fold(value);
+        if folded.starts_with(&typed_folded) {
+            prefixed.push(value.clone());
+        } else if folded.contains(&typed_folded) {
+            containing.push(value.clone());
+        }
+    }
+    prefixed.append(&mut containing);
+    prefixed.truncate(MAX_SUGGESTIONS);
+    prefixed
+}
+
+/// Itens já cadastrados com todas as palavras da descrição digitada; os de
+/// descrição igual primeiro, depois do mais barato para o mais caro.
+pub fn similar_items<'a>(
+    items: &'a [InfraItem],
+    description: &str,
+    limit: usize,
+) -> Vec<&'a InfraItem> {
+    let typed = fold(description.trim());
+    let words: Vec<&str> = typed
+        .split_whitespace()
+        .filter(|word| word.chars().count() >= 2)
+        .collect();
+    if words.is_empty() {
+        return vec![];
+    }
+    let mut similar: Vec<(bool, &InfraItem)> = items
+        .iter()
+        .filter_map(|item| {
+            let folded = fold(&item.description);
+            words
+                .iter()
+                .all(|word| folded.contains(word))
+                .then_some((folded != typed, item))
+        })
+        .collect();
+    similar.sort_by(|(a_differs, a), (b_differs, b)| {
+        a_differs.cmp(b_differs).then(a.price.total_cmp(&b.price))
+    });
+    similar.truncate(limit);
+    similar.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Estado das sugestões do campo em foco. Um só para o formulário: só um
+/// campo tem o foco de cada vez.
+#[derive(Default)]
+pub struct Completion {
+    field: Option<Id>,
+    highlighted: Option<usize>,
+    /// Fechadas com Esc ou depois de escolher, até o texto mudar.
+    dismissed: bool,
+    open: bool,
+}
+
+impl Completion {
+    /// As sugestões estavam abertas no último quadro; as teclas sem Ctrl ou
+    /// Alt ficam com elas.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Há uma sugestão destacada: o Enter a escolhe em vez de gravar.
+    pub fn is_choosing(&self) -> bool {
+        self.open && self.highlighted.is_some()
+    }
+}
+
+/// Campo de texto com sugestões abaixo dele. As setas percorrem as
+/// sugestões, Enter escolhe a destacada e Esc fecha a lista.
+pub fn completing_field(
+    ui: &mut Ui,
+    id: Id,
+    text: &mut String,
+    hint: &str,
+    width: f32,
+    suggestions: &[String],
+    completion: &mut Completion,
+) -> Response {
+    let focused = ui.memory(|memory| memory.has_focus(id));
+    if focused && completion.field != Some(id) {
+        *completion = Completion {
+            field: Some(id),
+            ..Default::default()
+        };
+    }
+    let active = focused && completion.field == Some(id);
+    let show = active && !completion.dismissed && !suggestions.is_empty();
+
+    let mut chosen = None;
+    if show {
+        ui.input_mut(|input| {
+            let last = suggestions.len() - 1;
+            if input.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                completion.highlighted =
+                    Some(completion.highlighted.map_or(0, |h| (h + 1).min(last)));
+            }
+            if input.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                completion.highlighted = completion.highlighted.and_then(|h| h.checked_sub(1));
+            }
+            if let Some(highlighted) = completion.highlighted
+                && input.consume_key(Modifiers::NONE, Key::Enter)
+            {
+                chosen = Some(highlighted.min(last));
+            }
+            if input.consume_key(Modifiers::NONE, Key::Escape) {
+                completion.dismissed = true;
+            }
+        });
+    }
+
+    let mut edit = TextEdit::singleline(text).id(id).min_size(vec2(width, 0.0));
+    if !hint.is_empty() {
+        edit = edit.hint_text(hint);
+    }
+    let mut response = ui.add(edit);
+    if response.changed() {
+        completion.dismissed = false;
+        completion.highlighted = None;
+    }
+
+    if show && !completion.dismissed && chosen.is_none() {
+        egui::Area::new(id.with("suggestions"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(response.rect.left_bottom())
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_min_width(response.rect.width());
+                    for (index, suggestion) in suggestions.iter().enumerate() {
+                        let highlighted = completion.highlighted == Some(index);
+                        if ui.selectable_label(highlighted, suggestion).clicked() {
+                            chosen = Some(index);
+                        }
+                    }
+                });
+            });
+    }
+
+    if let Some(index) = chosen {
+        *text = suggestions[index].clone();
+        completion.highlighted = None;
+        completion.dismissed = true;
+        response.request_focus();
+        response.mark_changed();
+    }
+    if completion.field == Some(id) {
+        completion.open = show && chosen.is_none() && !completion.dismissed;
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ranked_values, similar_items, suggestions};
+    use crate::InfraItem;
+
+    fn item(description: &str, vendor: &str, price: f32, updated_at: &str) -> InfraItem {
+        InfraItem {
+            id: 0,
+            description: description.to_string(),
+            brand: String::new(),
+            vendor: vendor.to_string(),
+            price,
+            updated_at: updated_at.to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: String::new(),
+        }
+    }
+
+    #[test]
+    fn vendors_ranked_by_use_then_recency() {
+        let items = [
+            item("a", "Elétrica Sul", 1.0, "2024-01-01"),
+            item("b", "Eletro Norte", 1.0, "2024-03-01"),
+            item("c", "Elétrica Sul", 1.0, "2024-01-02"),
+            item("d", "Casa do Eletricista", 1.0, "2024-05-01"),
+            item("e", "", 1.0, "2024-06-01"),
+        ];
+        let ranked = ranked_values(&items, |item| &item.vendor);
+        assert_eq!(
+            ranked,
+            ["Elétrica Sul", "Casa do Eletricista", "Eletro Norte"]
+        );
+        assert_eq!(
+            suggestions(&ranked, "ELETR"),
+            ["Elétrica Sul", "Eletro Norte", "Casa do Eletricista"]
+        );
+        assert!(suggestions(&ranked, "Eletro Norte").is_empty());
+        assert_eq!(suggestions(&ranked, "eletro norte"), ["Eletro Norte"]);
+    }
+
+    #[test]
+    fn similar_items_put_same_description_first() {
+        let items = [
+            item("Disjuntor bipolar 20A", "A", 30.0, "2024-01-01"),
+            item("Disjuntor 20A", "B", 15.0, "2024-01-01"),
+            item("disjuntor 20a", "C", 18.0, "2024-01-01"),
+            item("Disjuntor 25A", "D", 9.0, "2024-01-01"),
+        ];
+        let similar: Vec<&str> = similar_items(&items, "Disjuntor 20A", 5)
+            .iter()
+            .map(|item| item.vendor.as_str())
+            .collect();
+        assert_eq!(similar, ["B", "C", "A"]);
+        assert!(similar_items(&items, " ", 5).is_empty());
+    }
+}
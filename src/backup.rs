@@ -0,0 +1,354 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use rusqlite::{Connection, MAIN_DB, OpenFlags};
+
+use crate::settings;
+
+pub const BACKUP_DIR: &str = "backups";
+
+/// Pasta de backups de um catálogo: `backups` ao lado do arquivo do banco.
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(BACKUP_DIR)
+}
+const FILE_PREFIX: &str = "infra_items_";
+const DATE_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackupKind {
+    Daily,
+    Weekly,
+    BeforeImport,
+    BeforeMigration,
+    BeforeRestore,
+    BeforeSync,
+    Manual,
+}
+
+impl BackupKind {
+    const ALL: [BackupKind; 7] = [
+        BackupKind::Daily,
+        BackupKind::Weekly,
+        BackupKind::BeforeImport,
+        BackupKind::BeforeMigration,
+        BackupKind::BeforeRestore,
+        BackupKind::BeforeSync,
+        BackupKind::Manual,
+    ];
+
+    /// Parte do nome do arquivo que identifica o tipo.
+    fn tag(self) -> &'static str {
+        match self {
+            BackupKind::Daily => "diario",
+            BackupKind::Weekly => "semanal",
+            BackupKind::BeforeImport => "importacao",
+            BackupKind::BeforeMigration => "migracao",
+            BackupKind::BeforeRestore => "restauracao",
+            BackupKind::BeforeSync => "sincronizacao",
+            BackupKind::Manual => "manual",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BackupKind::Daily => "Diário",
+            BackupKind::Weekly => "Semanal",
+            BackupKind::BeforeImport => "Antes de importação",
+            BackupKind::BeforeMigration => "Antes de atualização do banco",
+            BackupKind::BeforeRestore => "Antes de restauração",
+            BackupKind::BeforeSync => "Antes de sincronização",
+            BackupKind::Manual => "Manual",
+        }
+    }
+}
+
+/// Quantos backups de cada tipo são mantidos.
+#[derive(Clone, PartialEq)]
+pub struct BackupConfig {
+    pub keep_daily: i64,
+    pub keep_weekly: i64,
+    /// Backups feitos antes de importações, atualizações, restaurações,
+    /// sincronizações e os manuais.
+    pub keep_other: i64,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_other: 10,
+        }
+    }
+}
+
+impl BackupConfig {
+    fn keep(&self, kind: BackupKind) -> i64 {
+        match kind {
+            BackupKind::Daily => self.keep_daily,
+            BackupKind::Weekly => self.keep_weekly,
+            _ => self.keep_other,
+        }
+    }
+}
+
+pub fn load_config(conn: &Connection) -> BackupConfig {
+    let default = BackupConfig::default();
+    BackupConfig {
+        keep_daily: settings::get_i64(conn, "backup_keep_daily", default.keep_daily),
+        keep_weekly: settings::get_i64(conn, "backup_keep_weekly", default.keep_weekly),
+        keep_other: settings::get_i64(conn, "backup_keep_other", default.keep_other),
+    }
+}
+
+pub fn save_config(conn: &Connection, config: &BackupConfig) -> rusqlite::Result<()> {
+    settings::set(conn, "backup_keep_daily", &config.keep_daily.to_string())?;
+    settings::set(conn, "backup_keep_weekly", &config.keep_weekly.to_string())?;
+    settings::set(conn, "backup_keep_other", &config.keep_other.to_string())
+}
+
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub kind: BackupKind,
+    pub created_at: NaiveDateTime,
+    pub size: u64,
+    /// Itens fora da lixeira; `None` se o arquivo não pôde ser lido.
+    pub item_count: Option<i64>,
+}
+
+/// Tipo, data e contador do backup. O contador ("-2", "-3"...) diferencia
+/// backups feitos no mesmo segundo; é 1 no primeiro, que não o leva no nome.
+fn parse_file_name(name: &str) -> Option<(BackupKind, NaiveDateTime, u32)> {
+    let rest = name.strip_prefix(FILE_PREFIX)?.strip_suffix(".db")?;
+    let (tag, date) = rest.split_once('_')?;
+    let (date, counter) = match date.rsplit_once('-') {
+        Some((date, counter)) if counter.chars().all(|c| c.is_ascii_digit()) => {
+            (date, counter.parse().ok()?)
+        }
+        _ => (date, 1),
+    };
+    let kind = BackupKind::ALL.into_iter().find(|k| k.tag() == tag)?;
+    let created_at = NaiveDateTime::parse_from_str(date, DATE_FORMAT).ok()?;
+    Some((kind, created_at, counter))
+}
+
+/// Backups da pasta, do mais recente para o mais antigo. Arquivos com nome
+/// fora do padrão são ignorados.
+pub fn list_backups(dir: &Path) -> std::io::Result<Vec<BackupFile>> {
+    let mut backups = vec![];
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some((kind, created_at, counter)) =
+            entry.file_name().to_str().and_then(parse_file_name)
+        else {
+            continue;
+        };
+        backups.push((
+            counter,
+            BackupFile {
+                path: entry.path(),
+                kind,
+                created_at,
+                size: entry.metadata()?.len(),
+                item_count: None,
+            },
+        ));
+    }
+    backups.sort_by_key(|(counter, b)| std::cmp::Reverse((b.created_at, *counter)));
+    Ok(backups.into_iter().map(|(_, b)| b).collect())
+}
+
+fn open_read_only(path: &Path) -> rusqlite::Result<Connection> {
+    Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// Conta os itens do backup. Backups anteriores à lixeira não têm `deleted_at`.
+pub fn count_items(path: &Path) -> rusqlite::Result<i64> {
+    let conn = open_read_only(path)?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM infra_item WHERE deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )
+    .or_else(|_| conn.query_row("SELECT COUNT(*) FROM infra_item", [], |row| row.get(0)))
+}
+
+/// Roda `PRAGMA integrity_check` no arquivo. Devolve os problemas
+/// encontrados; vazio quando o arquivo está íntegro.
+pub fn integrity_check(path: &Path) -> rusqlite::Result<Vec<String>> {
+    let conn = open_read_only(path)?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
+
+/// Copia o banco aberto para um novo arquivo na pasta de backups, usando a
+/// API de backup online do SQLite (funciona com o banco em uso).
+pub fn create_backup(
+    conn: &Connection,
+    dir: &Path,
+    kind: BackupKind,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let name = format!(
+        "{FILE_PREFIX}{}_{}",
+        kind.tag(),
+        Utc::now().format(DATE_FORMAT)
+    );
+    let mut path = dir.join(format!("{name}.db"));
+    // Dois backups no mesmo segundo não podem sobrescrever um ao outro
+    let mut counter = 2;
+    while path.exists() {
+        path = dir.join(format!("{name}-{counter}.db"));
+        counter += 1;
+    }
+    conn.backup(MAIN_DB, &path, None)?;
+    Ok(path)
+}
+
+/// Apaga os backups mais antigos de cada tipo além do limite configurado.
+pub fn rotate(dir: &Path, config: &BackupConfig) -> std::io::Result<()> {
+    let backups = list_backups(dir)?;
+    for kind in BackupKind::ALL {
+        let keep = config.keep(kind).max(1) as usize;
+        for old in backups.iter().filter(|b| b.kind == kind).skip(keep) {
+            fs::remove_file(&old.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Faz o backup diário e o semanal quando ainda não existem para o período
+/// e aplica a rotação. Chamado na abertura do programa.
+pub fn run_scheduled(conn: &Connection, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let backups = list_backups(dir).unwrap_or_default();
+    let now = Utc::now().naive_utc();
+    let latest = |kind: BackupKind| {
+        backups
+            .iter()
+            .find(|b| b.kind == kind)
+            .map(|b| b.created_at)
+    };
+
+    if latest(BackupKind::Daily).is_none_or(|at| at.date() != now.date()) {
+        create_backup(conn, dir, BackupKind::Daily)?;
+    }
+    if latest(BackupKind::Weekly).is_none_or(|at| now - at >= Duration::days(7)) {
+        create_backup(conn, dir, BackupKind::Weekly)?;
+    }
+    rotate(dir, &load_config(conn))?;
+    Ok(())
+}
+
+/// Substitui o conteúdo do banco aberto pelo do backup.
+pub fn restore_backup(conn: &mut Connection, path: &Path) -> rusqlite::Result<()> {
+    conn.restore(MAIN_DB, path, None::<fn(rusqlite::backup::Progress)>)
+}
+
+/// Estado da janela de restauração de backup.
+#[derive(Default)]
+pub struct BackupView {
+    pub open: bool,
+    pub backups: Vec<BackupFile>,
+    pub config: BackupConfig,
+    /// Backup aguardando confirmação para ser restaurado.
+    pub confirm_restore: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rusqlite::Connection;
+
+    use super::{BackupConfig, BackupKind, create_backup, list_backups, parse_file_name, rotate};
+
+    #[test]
+    fn parses_names_with_and_without_counter() {
+        let (kind, at, counter) =
+            parse_file_name("infra_items_manual_2024-03-05_141516.db").unwrap();
+        assert!(kind == BackupKind::Manual);
+        assert_eq!(at.to_string(), "2024-03-05 14:15:16");
+        assert_eq!(counter, 1);
+        let (kind, same_at, counter) =
+            parse_file_name("infra_items_diario_2024-03-05_141516-3.db").unwrap();
+        assert!(kind == BackupKind::Daily);
+        assert_eq!(same_at, at);
+        assert_eq!(counter, 3);
+        assert!(parse_file_name("infra_items_manual_2024-03-05_141516-x.db").is_none());
+        assert!(parse_file_name("outro_arquivo.db").is_none());
+    }
+
+    #[test]
+    fn same_second_backups_get_a_counter() {
+        let dir = std::env::temp_dir().join(format!("catalogo-backup-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+
+        let first = create_backup(&conn, &dir, BackupKind::Manual).unwrap();
+        let second = create_backup(&conn, &dir, BackupKind::Manual).unwrap();
+        let stem = first.file_stem().unwrap().to_string_lossy().to_string();
+        // Se o relógio virou o segundo entre os dois, o nome já é outro
+        let second_name = second.file_name().unwrap().to_string_lossy().to_string();
+        assert!(second_name == format!("{stem}-2.db") || !second_name.starts_with(&stem));
+        assert!(first.exists() && second.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotation_keeps_highest_counter_of_the_same_second() {
+        let dir = std::env::temp_dir().join(format!("catalogo-rotacao-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["", "-2", "-10"] {
+            fs::write(
+                dir.join(format!("infra_items_manual_2024-03-05_141516{name}.db")),
+                b"",
+            )
+            .unwrap();
+        }
+        let names: Vec<String> = list_backups(&dir)
+            .unwrap()
+            .iter()
+            .map(|b| b.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "infra_items_manual_2024-03-05_141516-10.db",
+                "infra_items_manual_2024-03-05_141516-2.db",
+                "infra_items_manual_2024-03-05_141516.db",
+            ]
+        );
+
+        rotate(
+            &dir,
+            &BackupConfig {
+                keep_other: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(list_backups(&dir).unwrap().len(), 1);
+        assert!(
+            dir.join("infra_items_manual_2024-03-05_141516-10.db")
+                .exists()
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
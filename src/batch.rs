@@ -0,0 +1,229 @@
+use rusqlite::Connection;
+
+use crate::{InfraItem, find_item_id, tags};
+
+/// Estado da janela de edição em lote. Só os campos marcados são alterados.
+#[derive(Default)]
+pub struct BatchEdit {
+    pub open: bool,
+    pub change_vendor: bool,
+    pub vendor: String,
+    pub change_brand: bool,
+    pub brand: String,
+    pub change_category: bool,
+    pub category: String,
+    pub change_unit: bool,
+    pub unit: String,
+    pub add_tags: String,
+    pub remove_tags: String,
+}
+
+impl BatchEdit {
+    pub fn has_changes(&self) -> bool {
+        self.change_vendor
+            || self.change_brand
+            || self.change_category
+            || self.change_unit
+            || !tags::parse_tags(&self.add_tags).is_empty()
+            || !tags::parse_tags(&self.remove_tags).is_empty()
+    }
+}
+
+/// Resultado da edição em lote. Fora `Updated`, nenhum item foi alterado.
+pub enum BatchOutcome {
+    Updated(usize),
+    /// Itens alterados ou excluídos por outra pessoa depois de lidos.
+    Conflict(Vec<String>),
+    /// Item que ficaria com a mesma descrição, marca e fornecedor de outro.
+    Duplicate(String),
+}
+
+/// Aplica a edição a todos os itens numa única transação, só se nenhum foi
+/// alterado desde que foi lido e nenhum colide com outro já existente.
+pub fn apply_batch_edit(
+    conn: &mut Connection,
+    items: &[InfraItem],
+    edit: &BatchEdit,
+) -> rusqlite::Result<BatchOutcome> {
+    let add_tags = tags::parse_tags(&edit.add_tags);
+    let remove_tags = tags::parse_tags(&edit.remove_tags);
+    fn field(change: bool, value: &str) -> Option<&str> {
+        change.then(|| value.trim())
+    }
+    let vendor = field(edit.change_vendor, &edit.vendor);
+    let brand = field(edit.change_brand, &edit.brand);
+    let tx = conn.savepoint()?;
+    let mut conflicts = vec![];
+    for item in items {
+        if vendor.is_some() || brand.is_some() {
+            let vendor = vendor.unwrap_or(&item.vendor);
+            let brand = brand.unwrap_or(&item.brand);
+            if find_item_id(&tx, &item.description, brand, vendor)?.is_some_and(|id| id != item.id)
+            {
+                return Ok(BatchOutcome::Duplicate(item.description.clone()));
+            }
+        }
+        // Um só UPDATE conferindo a versão, como na edição de um item
+        let updated = tx.execute(
+            "UPDATE infra_item SET vendor = COALESCE(?1, vendor), brand = COALESCE(?2, brand),
+                category = COALESCE(?3, category), unit = COALESCE(?4, unit)
+            WHERE id = ?5 AND version = ?6 AND deleted_at IS NULL",
+            (
+                vendor,
+                brand,
+                field(edit.change_category, &edit.category),
+                field(edit.change_unit, &edit.unit),
+                item.id,
+                item.version,
+            ),
+        )?;
+        if updated == 0 {
+            conflicts.push(item.description.clone());
+            continue;
+        }
+        if !add_tags.is_empty() || !remove_tags.is_empty() {
+            let mut item_tags: Vec<String> = item
+                .tags
+                .iter()
+                .filter(|t| !remove_tags.iter().any(|r| r.eq_ignore_ascii_case(t)))
+                .cloned()
+                .collect();
+            for tag in &add_tags {
+                if !item_tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    item_tags.push(tag.clone());
+                }
+            }
+            tags::set_item_tags(&tx, item.id, &item_tags)?;
+        }
+    }
+    if !conflicts.is_empty() {
+        return Ok(BatchOutcome::Conflict(conflicts));
+    }
+    tx.commit()?;
+    Ok(BatchOutcome::Updated(items.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{BatchEdit, BatchOutcome, apply_batch_edit};
+    use crate::{ITEM_COLUMNS, InfraItem, item_from_row, tags};
+
+    fn load_items(conn: &Connection) -> Vec<InfraItem> {
+        conn.prepare(&format!("SELECT {ITEM_COLUMNS} FROM infra_item"))
+            .unwrap()
+            .query_map([], item_from_row)
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn changes_only_checked_fields() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        for description in ["Cabo 2,5mm", "Cabo 4mm"] {
+            conn.execute(
+                "INSERT INTO infra_item (description, brand, vendor, price, updated_at, category, unit)
+                VALUES (?1, 'Marca', 'Fornecedor', 10, '2024-01-01', 'Cabos', 'rolo')",
+                [description],
+            )
+            .unwrap();
+            let id = conn.last_insert_rowid() as i32;
+            tags::set_item_tags(&conn, id, &["cobre".to_string()]).unwrap();
+        }
+        let items = load_items(&conn);
+        let edit = BatchEdit {
+            change_category: true,
+            category: " Condutores ".to_string(),
+            change_unit: true,
+            unit: "m".to_string(),
+            brand: "Ignorada".to_string(),
+            add_tags: "flexível".to_string(),
+            ..Default::default()
+        };
+        assert!(edit.has_changes());
+        assert!(matches!(
+            apply_batch_edit(&mut conn, &items, &edit).unwrap(),
+            BatchOutcome::Updated(2)
+        ));
+
+        for item in load_items(&conn) {
+            assert_eq!(item.category, "Condutores");
+            assert_eq!(item.unit, "m");
+            assert_eq!(item.brand, "Marca");
+            assert_eq!(item.vendor, "Fornecedor");
+            assert_eq!(item.tags.len(), 2);
+        }
+    }
+
+    fn insert(conn: &Connection, description: &str, vendor: &str) -> i32 {
+        conn.execute(
+            "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
+            VALUES (?1, 'Marca', ?2, 10, '2024-01-01')",
+            (description, vendor),
+        )
+        .unwrap();
+        conn.last_insert_rowid() as i32
+    }
+
+    fn vendors(conn: &Connection) -> Vec<String> {
+        load_items(conn)
+            .into_iter()
+            .map(|item| item.vendor)
+            .collect()
+    }
+
+    #[test]
+    fn items_changed_by_someone_else_block_the_edit() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        insert(&conn, "Cabo 2,5mm", "Eletro");
+        let changed = insert(&conn, "Cabo 4mm", "Eletro");
+        let items = load_items(&conn);
+        // Edição feita por outra instância depois da leitura
+        conn.execute("UPDATE infra_item SET price = 12 WHERE id = ?1", [changed])
+            .unwrap();
+
+        let edit = BatchEdit {
+            change_category: true,
+            category: "Cabos".to_string(),
+            ..Default::default()
+        };
+        match apply_batch_edit(&mut conn, &items, &edit).unwrap() {
+            BatchOutcome::Conflict(items) => assert_eq!(items, ["Cabo 4mm"]),
+            _ => panic!("esperava conflito"),
+        }
+        assert!(
+            load_items(&conn)
+                .iter()
+                .all(|item| item.category.is_empty())
+        );
+    }
+
+    #[test]
+    fn key_collision_rolls_back_every_item() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let other = insert(&conn, "Cabo 4mm", "Eletro");
+        let colliding = insert(&conn, "Cabo 2,5mm", "Eletro");
+        insert(&conn, "Cabo 2,5mm", "Casa Elétrica");
+        // O primeiro item é gravado antes de o segundo colidir
+        let items: Vec<InfraItem> = [other, colliding]
+            .iter()
+            .map(|id| crate::items::load_item(&conn, *id).unwrap().unwrap())
+            .collect();
+
+        let edit = BatchEdit {
+            change_vendor: true,
+            vendor: "Casa Elétrica".to_string(),
+            ..Default::default()
+        };
+        match apply_batch_edit(&mut conn, &items, &edit).unwrap() {
+            BatchOutcome::Duplicate(description) => assert_eq!(description, "Cabo 2,5mm"),
+            _ => panic!("esperava colisão"),
+        }
+        assert_eq!(vendors(&conn), ["Eletro", "Eletro", "Casa Elétrica"]);
+    }
+}
@@ -0,0 +1,193 @@
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::{ITEM_COLUMNS, InfraItem, format_money, item_from_row};
+
+/// Orçamento em montagem: os itens escolhidos no menu da lista, com a
+/// quantidade de cada um. Fica no catálogo até ser limpo. Itens no orçamento
+/// não são excluídos definitivamente da lixeira (ver `trash::purge`).
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS budget_item (
+            item_id INTEGER PRIMARY KEY REFERENCES infra_item(id) ON DELETE CASCADE,
+            quantity REAL NOT NULL DEFAULT 1,
+            added_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub struct BudgetLine {
+    pub item: InfraItem,
+    pub quantity: f64,
+    /// O item foi para a lixeira depois de entrar no orçamento.
+    pub deleted: bool,
+}
+
+impl BudgetLine {
+    pub fn subtotal(&self) -> f64 {
+        self.item.price as f64 * self.quantity
+    }
+}
+
+/// Inclui os itens no orçamento; os que já estão nele ganham mais uma unidade.
+pub fn add_items(conn: &mut Connection, ids: &[i32]) -> rusqlite::Result<()> {
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let tx = conn.transaction()?;
+    for id in ids {
+        tx.execute(
+            "INSERT INTO budget_item (item_id, quantity, added_at) VALUES (?1, 1, ?2)
+            ON CONFLICT(item_id) DO UPDATE SET quantity = quantity + 1",
+            (id, &now),
+        )?;
+    }
+    tx.commit()
+}
+
+/// Itens do orçamento, na ordem em que foram incluídos. Os que estão na
+/// lixeira continuam nele, marcados, para o total não mudar.
+pub fn load_lines(conn: &Connection) -> rusqlite::Result<Vec<BudgetLine>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS}, b.quantity, infra_item.deleted_at IS NOT NULL AS deleted
+         FROM budget_item b
+         JOIN infra_item ON infra_item.id = b.item_id
+         ORDER BY b.added_at, b.item_id"
+    ))?;
+    let lines = stmt.query_map([], |row| {
+        Ok(BudgetLine {
+            item: item_from_row(row)?,
+            quantity: row.get("quantity")?,
+            deleted: row.get("deleted")?,
+        })
+    })?;
+    lines.collect()
+}
+
+pub fn set_quantity(conn: &Connection, item_id: i32, quantity: f64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE budget_item SET quantity = ?1 WHERE item_id = ?2",
+        (quantity, item_id),
+    )?;
+    Ok(())
+}
+
+pub fn remove_item(conn: &Connection, item_id: i32) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM budget_item WHERE item_id = ?1", [item_id])?;
+    Ok(())
+}
+
+pub fn clear(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM budget_item", [])?;
+    Ok(())
+}
+
+pub fn total(lines: &[BudgetLine]) -> f64 {
+    lines.iter().map(BudgetLine::subtotal).sum()
+}
+
+/// Quantidade sem casas decimais quando é inteira; senão, com vírgula.
+pub fn format_quantity(quantity: f64) -> String {
+    if quantity.fract() == 0.0 {
+        format!("{}", quantity)
+    } else {
+        quantity.to_string().replace('.', ",")
+    }
+}
+
+/// Uma linha por item (descrição, marca, fornecedor, unidade, quantidade,
+/// preço e subtotal, separados por tabulação) e o total no fim.
+pub fn render_lines(lines: &[BudgetLine]) -> Vec<String> {
+    let mut rendered: Vec<String> = lines
+        .iter()
+        .map(|line| {
+            let item = &line.item;
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                item.description,
+                item.brand,
+                item.vendor,
+                item.unit,
+                format_quantity(line.quantity),
+                format_money(item.price),
+                format_money(line.subtotal() as f32)
+            )
+        })
+        .collect();
+    rendered.push(format!(
+        "Total\t\t\t\t\t\t{}",
+        format_money(total(lines) as f32)
+    ));
+    rendered
+}
+
+#[derive(Default)]
+pub struct BudgetView {
+    pub open: bool,
+    pub lines: Vec<BudgetLine>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{add_items, load_lines, remove_item, render_lines, set_quantity, total};
+    use crate::trash;
+
+    #[test]
+    fn adding_twice_increments_quantity() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Cabo', '', 'Eletro', 3.5, '2024-01-01'),
+                   (2, 'Fita', '', 'Eletro', 10, '2024-01-01');",
+        )
+        .unwrap();
+
+        add_items(&mut conn, &[1, 2]).unwrap();
+        add_items(&mut conn, &[1]).unwrap();
+        set_quantity(&conn, 2, 2.5).unwrap();
+        let lines = load_lines(&conn).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].quantity, 2.0);
+        assert_eq!(total(&lines), 32.0);
+        assert_eq!(
+            render_lines(&lines),
+            [
+                "Cabo\t\tEletro\t\t2\t3,50\t7,00",
+                "Fita\t\tEletro\t\t2,5\t10,00\t25,00",
+                "Total\t\t\t\t\t\t32,00",
+            ]
+        );
+
+        remove_item(&conn, 1).unwrap();
+        assert_eq!(load_lines(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn deleted_items_stay_in_the_budget() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Cabo', '', 'Eletro', 3.5, '2024-01-01'),
+                   (2, 'Fita', '', 'Eletro', 10, '2024-01-01');",
+        )
+        .unwrap();
+        add_items(&mut conn, &[1]).unwrap();
+        trash::soft_delete(&mut conn, &[1, 2], "teste").unwrap();
+
+        let lines = load_lines(&conn).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].deleted);
+        assert_eq!(total(&lines), 3.5);
+
+        // A exclusão definitiva pula o item do orçamento, manual ou pelo prazo
+        assert_eq!(trash::purge(&mut conn, &[1, 2]).unwrap(), 1);
+        conn.execute("UPDATE infra_item SET deleted_at = '2000-01-01'", [])
+            .unwrap();
+        assert_eq!(trash::purge_expired(&mut conn, 30).unwrap(), 0);
+        assert_eq!(total(&load_lines(&conn).unwrap()), 3.5);
+    }
+}
@@ -0,0 +1,394 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::{InfraItem, history, money};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AdjustmentMode {
+    Percent,
+    Fixed,
+    Index,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rounding {
+    Cents,
+    FiveCents,
+    TenCents,
+    Whole,
+}
+
+impl Rounding {
+    pub const ALL: [Rounding; 4] = [
+        Rounding::Cents,
+        Rounding::FiveCents,
+        Rounding::TenCents,
+        Rounding::Whole,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Rounding::Cents => "Centavos (0,01)",
+            Rounding::FiveCents => "0,05",
+            Rounding::TenCents => "0,10",
+            Rounding::Whole => "Real inteiro (1,00)",
+        }
+    }
+
+    fn step(self) -> f64 {
+        match self {
+            Rounding::Cents => 0.01,
+            Rounding::FiveCents => 0.05,
+            Rounding::TenCents => 0.10,
+            Rounding::Whole => 1.0,
+        }
+    }
+
+    pub fn round(self, value: f64) -> f64 {
+        let step = self.step();
+        ((value / step).round() * step * 100.0).round() / 100.0
+    }
+}
+
+/// Série de um índice de inflação (IPCA, INCC...): variação mensal em %.
+pub struct IndexSeries {
+    pub name: String,
+    pub months: Vec<(String, f64)>,
+}
+
+impl IndexSeries {
+    /// Fator acumulado entre os meses `from` e `to` (inclusive). Um intervalo
+    /// invertido ou sem meses da série é um erro, e não um reajuste de 0%.
+    pub fn factor(&self, from: &str, to: &str) -> Result<f64, String> {
+        if from > to {
+            return Err(format!(
+                "O mês inicial ({}) é posterior ao final ({})",
+                from, to
+            ));
+        }
+        let months: Vec<f64> = self
+            .months
+            .iter()
+            .filter(|(month, _)| month.as_str() >= from && month.as_str() <= to)
+            .map(|(_, pct)| *pct)
+            .collect();
+        if months.is_empty() {
+            return Err(format!("O índice não tem meses entre {} e {}", from, to));
+        }
+        Ok(months
+            .iter()
+            .fold(1.0, |acc, pct| acc * (1.0 + pct / 100.0)))
+    }
+}
+
+/// Lê um arquivo CSV com linhas "AAAA-MM;variação %". Linhas que não
+/// puderem ser interpretadas (cabeçalho, comentários) são ignoradas.
+pub fn load_index_file(path: &Path) -> Result<IndexSeries, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(BufReader::new(file));
+
+    let mut months = vec![];
+    for result in rdr.records() {
+        let record = result?;
+        let month = record
+            .get(0)
+            .unwrap_or("")
+            .trim()
+            .trim_start_matches('\u{feff}');
+        let value = record.get(1).unwrap_or("").trim().replace(",", ".");
+        if let Ok(value) = value.parse::<f64>()
+            && month.len() == 7
+        {
+            months.push((month.to_string(), value));
+        }
+    }
+    if months.is_empty() {
+        return Err("Nenhum mês válido encontrado (formato esperado: AAAA-MM;variação)".into());
+    }
+    months.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(IndexSeries { name, months })
+}
+
+#[derive(Clone)]
+pub struct PriceChange {
+    pub item_id: i32,
+    pub description: String,
+    pub vendor: String,
+    pub old_price: f32,
+    pub new_price: f32,
+    /// Versão do item usada no cálculo; se mudou, o reajuste pula o item.
+    pub version: i64,
+}
+
+impl PriceChange {
+    /// Reajustes que zerariam ou deixariam o preço negativo não podem ser aplicados.
+    pub fn is_valid(&self) -> bool {
+        self.new_price > 0.0
+    }
+}
+
+/// `value` é a porcentagem, o valor fixo em reais ou o fator do índice,
+/// conforme `mode`. Resultados menores ou iguais a zero são mantidos, para
+/// aparecerem como erro na pré-visualização (ver `PriceChange::is_valid`).
+pub fn preview(
+    items: &[InfraItem],
+    mode: AdjustmentMode,
+    value: f64,
+    rounding: Rounding,
+) -> Vec<PriceChange> {
+    items
+        .iter()
+        .map(|item| {
+            let old = item.price as f64;
+            let adjusted = match mode {
+                AdjustmentMode::Percent => old * (1.0 + value / 100.0),
+                AdjustmentMode::Fixed => old + value,
+                AdjustmentMode::Index => old * value,
+            };
+            PriceChange {
+                item_id: item.id,
+                description: item.description.clone(),
+                vendor: item.vendor.clone(),
+                old_price: item.price,
+                new_price: rounding.round(adjusted) as f32,
+                version: item.version,
+            }
+        })
+        .collect()
+}
+
+/// Aplica as alterações numa única transação, registrando o histórico. Itens
+/// alterados ou excluídos por outra pessoa depois da pré-visualização ficam
+/// de fora; devolve as descrições deles.
+pub fn apply_changes(
+    conn: &mut Connection,
+    changes: &[PriceChange],
+    source: &str,
+) -> rusqlite::Result<Vec<String>> {
+    let now = Utc::now().format("%Y-%m-%d").to_string();
+    let tx = conn.savepoint()?;
+    let mut skipped = vec![];
+    for change in changes {
+        let updated = tx.execute(
+            "UPDATE infra_item SET price = ?1, updated_at = ?2
+            WHERE id = ?3 AND version = ?4 AND deleted_at IS NULL",
+            (change.new_price, &now, change.item_id, change.version),
+        )?;
+        if updated == 0 {
+            skipped.push(change.description.clone());
+            continue;
+        }
+        history::record_price(&tx, change.item_id, change.new_price, &now, source)?;
+    }
+    tx.commit()?;
+    Ok(skipped)
+}
+
+/// Itens a reajustar: os selecionados na lista ou, sem seleção, os exibidos.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scope {
+    Selection,
+    Visible,
+}
+
+impl Scope {
+    pub fn describe(self, count: usize) -> String {
+        match self {
+            Scope::Selection => format!("{} itens selecionados", count),
+            Scope::Visible => format!("{} itens exibidos na lista (busca e filtros atuais)", count),
+        }
+    }
+}
+
+/// Estado da janela de reajuste em lote.
+pub struct BulkAdjustState {
+    pub open: bool,
+    pub mode: AdjustmentMode,
+    pub value: String,
+    pub rounding: Rounding,
+    pub index: Option<IndexSeries>,
+    pub index_from: String,
+    pub index_to: String,
+    pub preview: Vec<PriceChange>,
+    /// De onde vieram os itens da pré-visualização.
+    pub preview_scope: Scope,
+    /// Por que a última pré-visualização não pôde ser feita.
+    pub error: Option<String>,
+}
+
+impl Default for BulkAdjustState {
+    fn default() -> Self {
+        BulkAdjustState {
+            open: false,
+            mode: AdjustmentMode::Percent,
+            value: String::new(),
+            rounding: Rounding::Cents,
+            index: None,
+            index_from: String::new(),
+            index_to: String::new(),
+            preview: vec![],
+            preview_scope: Scope::Visible,
+            error: None,
+        }
+    }
+}
+
+impl BulkAdjustState {
+    /// Valor a aplicar conforme o modo escolhido, ou mensagem de erro.
+    pub fn adjustment_value(&self) -> Result<f64, String> {
+        match self.mode {
+            AdjustmentMode::Percent | AdjustmentMode::Fixed => money::parse_amount(&self.value)
+                .map_err(|e| format!("Valor inválido: '{}' ({})", self.value, e)),
+            AdjustmentMode::Index => match &self.index {
+                Some(series) => series.factor(&self.index_from, &self.index_to),
+                None => Err("Carregue um arquivo de índice".to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{AdjustmentMode, BulkAdjustState, IndexSeries, Rounding, apply_changes, preview};
+    use crate::InfraItem;
+
+    fn item(id: i32, price: f32) -> InfraItem {
+        InfraItem {
+            id,
+            description: format!("Item {}", id),
+            brand: String::new(),
+            vendor: "Fornecedor".to_string(),
+            price,
+            updated_at: "2024-01-01".to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: String::new(),
+        }
+    }
+
+    fn new_prices(mode: AdjustmentMode, value: f64, rounding: Rounding) -> Vec<f32> {
+        preview(&[item(1, 10.0), item(2, 189.9)], mode, value, rounding)
+            .into_iter()
+            .map(|change| change.new_price)
+            .collect()
+    }
+
+    #[test]
+    fn rounds_to_the_chosen_step() {
+        assert_eq!(Rounding::Cents.round(10.234), 10.23);
+        assert_eq!(Rounding::FiveCents.round(10.234), 10.25);
+        assert_eq!(Rounding::TenCents.round(10.234), 10.2);
+        assert_eq!(Rounding::Whole.round(10.5), 11.0);
+    }
+
+    #[test]
+    fn previews_each_mode() {
+        assert_eq!(
+            new_prices(AdjustmentMode::Percent, 8.0, Rounding::Cents),
+            [10.8, 205.09]
+        );
+        assert_eq!(
+            new_prices(AdjustmentMode::Fixed, -2.5, Rounding::Cents),
+            [7.5, 187.4]
+        );
+        assert_eq!(
+            new_prices(AdjustmentMode::Index, 1.1, Rounding::Whole),
+            [11.0, 209.0]
+        );
+    }
+
+    #[test]
+    fn marks_non_positive_results_as_invalid() {
+        let changes = preview(
+            &[item(1, 10.0), item(2, 189.9)],
+            AdjustmentMode::Fixed,
+            -10.0,
+            Rounding::Cents,
+        );
+        assert_eq!(changes[0].new_price, 0.0);
+        assert!(!changes[0].is_valid());
+        assert!(changes[1].is_valid());
+
+        let changes = preview(
+            &[item(1, 10.0)],
+            AdjustmentMode::Percent,
+            -150.0,
+            Rounding::Cents,
+        );
+        assert_eq!(changes[0].new_price, -5.0);
+        assert!(!changes[0].is_valid());
+    }
+
+    #[test]
+    fn skips_items_changed_after_the_preview() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Item 1', '', 'Fornecedor', 10, '2024-01-01'),
+                   (2, 'Item 2', '', 'Fornecedor', 20, '2024-01-01');",
+        )
+        .unwrap();
+        let items = crate::items::load_items(&conn).unwrap();
+        let changes = preview(&items, AdjustmentMode::Percent, 10.0, Rounding::Cents);
+        // Outra pessoa edita o item 2 depois da pré-visualização
+        conn.execute("UPDATE infra_item SET price = 25 WHERE id = 2", [])
+            .unwrap();
+
+        let skipped = apply_changes(&mut conn, &changes, "reajuste").unwrap();
+        assert_eq!(skipped, ["Item 2"]);
+        let price = |id: i32| crate::items::load_item(&conn, id).unwrap().unwrap().price;
+        assert_eq!((price(1), price(2)), (11.0, 25.0));
+    }
+
+    #[test]
+    fn accumulates_index_months_in_range() {
+        let series = IndexSeries {
+            name: "IPCA".to_string(),
+            months: vec![
+                ("2024-01".to_string(), 1.0),
+                ("2024-02".to_string(), 2.0),
+                ("2024-03".to_string(), 3.0),
+            ],
+        };
+        assert!((series.factor("2024-02", "2024-03").unwrap() - 1.02 * 1.03).abs() < 1e-9);
+        assert!(series.factor("2024-03", "2024-02").is_err());
+        assert!(series.factor("2025-01", "2025-06").is_err());
+    }
+
+    #[test]
+    fn parses_values_like_prices() {
+        let mut state = BulkAdjustState {
+            mode: AdjustmentMode::Fixed,
+            ..Default::default()
+        };
+        for (text, expected) in [
+            ("-2,5", -2.5),
+            ("+8", 8.0),
+            ("12.5", 12.5),
+            ("1.234,5", 1234.5),
+            ("R$ 1.234", 1234.0),
+        ] {
+            state.value = text.to_string();
+            assert_eq!(state.adjustment_value(), Ok(expected));
+        }
+        for text in ["", "abc", "1,2,3", "--1"] {
+            state.value = text.to_string();
+            assert!(state.adjustment_value().is_err(), "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{export, settings, staleness};
+
+/// Identifica os arquivos gerados por este aplicativo.
+const FORMAT_NAME: &str = "catalogo-eletrico";
+
+/// Versão do formato. Aumente ao mudar os campos de forma incompatível; a
+/// importação recusa arquivos de versões mais novas.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Cabeçalho do arquivo: primeira linha no JSON Lines, campos de topo no JSON.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Header {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub default_stale_days: i64,
+}
+
+/// Fornecedor ou categoria. O nome é a chave (é assim que os itens e os
+/// prazos se referem a eles); `stale_days` só existe quando há prazo próprio
+/// e `contact`, só em fornecedores com contato cadastrado.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct NamedRecord {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TagRecord {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Item com todos os campos gravados, inclusive os da lixeira.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ItemRecord {
+    pub id: i64,
+    pub description: String,
+    pub brand: String,
+    pub vendor: String,
+    pub price: f64,
+    pub updated_at: String,
+    pub category: String,
+    pub unit: String,
+    pub version: i64,
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PriceRecord {
+    pub id: i64,
+    pub item_id: i64,
+    pub price: f64,
+    pub recorded_at: String,
+    pub source: String,
+}
+
+/// Catálogo inteiro num único documento JSON.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CatalogDocument {
+    #[serde(flatten)]
+    pub header: Header,
+    pub vendors: Vec<NamedRecord>,
+    pub categories: Vec<NamedRecord>,
+    pub tags: Vec<TagRecord>,
+    pub items: Vec<ItemRecord>,
+    pub price_history: Vec<PriceRecord>,
+}
+
+/// Uma linha do JSON Lines, na ordem em que são gravadas.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Line {
+    Catalog(Header),
+    Vendor(NamedRecord),
+    Category(NamedRecord),
+    Tag(TagRecord),
+    Item(ItemRecord),
+    Price(PriceRecord),
+}
+
+/// Quantidades importadas, para a mensagem de status.
+pub struct ImportSummary {
+    pub items: usize,
+    pub prices: usize,
+}
+
+/// `true` para `.jsonl`/`.ndjson`, gravados uma linha por registro.
+pub fn is_json_lines(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jsonl") || ext.eq_ignore_ascii_case("ndjson"))
+}
+
+/// Percorre o catálogo na ordem do arquivo: cabeçalho, fornecedores,
+/// categorias, tags, itens e histórico de preços, cada grupo por nome ou id.
+/// Lê linha a linha do banco, sem carregar o catálogo inteiro na memória.
+fn for_each_line(
+    conn: &Connection,
+    exported_at: &str,
+    mut emit: impl FnMut(Line) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let config = staleness::load_config(conn)?;
+    emit(Line::Catalog(Header {
+        format: FORMAT_NAME.to_string(),
+        version: FORMAT_VERSION,
+        exported_at: exported_at.to_string(),
+        default_stale_days: config.default_days,
+    }))?;
+
+    let contacts = export::load_contacts(conn)?;
+    let vendors = named_records(conn, "vendor", &config.vendor_days, &contacts)?;
+    let categories = named_records(conn, "category", &config.category_days, &BTreeMap::new())?;
+    for record in vendors {
+        emit(Line::Vendor(record))?;
+    }
+    for record in categories {
+        emit(Line::Category(record))?;
+    }
+
+    let mut stmt = conn.prepare("SELECT id, name FROM tag ORDER BY id")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        emit(Line::Tag(TagRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        }))?;
+    }
+
+    let mut tags_stmt = conn.prepare(
+        "SELECT t.name FROM item_tag it JOIN tag t ON t.id = it.tag_id
+        WHERE it.item_id = ?1 ORDER BY t.id",
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT id, description, brand, vendor, price, updated_at, category, unit, version,
+            deleted_at, deleted_by
+        FROM infra_item ORDER BY id",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let tags = tags_stmt
+            .query_map([id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        emit(Line::Item(ItemRecord {
+            id,
+            description: row.get(1)?,
+            brand: row.get(2)?,
+            vendor: row.get(3)?,
+            price: row.get(4)?,
+            updated_at: row.get(5)?,
+            category: row.get(6)?,
+            unit: row.get(7)?,
+            version: row.get(8)?,
+            tags,
+            deleted_at: row.get(9)?,
+            deleted_by: row.get(10)?,
+        }))?;
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT id, item_id, price, recorded_at, source FROM price_history ORDER BY id")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        emit(Line::Price(PriceRecord {
+            id: row.get(0)?,
+            item_id: row.get(1)?,
+            price: row.get(2)?,
+            recorded_at: row.get(3)?,
+            source: row.get(4)?,
+        }))?;
+    }
+    Ok(())
+}
+
+/// Nomes usados nos itens, nos prazos ou nos contatos, em ordem alfabética.
+fn named_records(
+    conn: &Connection,
+    column: &str,
+    days: &[(String, i64)],
+    contacts: &BTreeMap<String, String>,
+) -> rusqlite::Result<Vec<NamedRecord>> {
+    let mut names: BTreeSet<String> = conn
+        .prepare(&format!(
+            "SELECT DISTINCT {column} FROM infra_item WHERE {column} <> ''"
+        ))?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    names.extend(days.iter().map(|(name, _)| name.clone()));
+    names.extend(contacts.keys().cloned());
+    Ok(names
+        .into_iter()
+        .map(|name| NamedRecord {
+            stale_days: days.iter().find(|(n, _)| *n == name).map(|(_, d)| *d),
+            contact: contacts.get(&name).cloned(),
+            name,
+        })
+        .collect())
+}
+
+/// Monta o documento JSON com o catálogo inteiro.
+pub fn load_document(
+    conn: &Connection,
+    exported_at: &str,
+) -> Result<CatalogDocument, Box<dyn Error>> {
+    let mut document = CatalogDocument::default();
+    for_each_line(conn, exported_at, |line| {
+        match line {
+            Line::Catalog(header) => document.header = header,
+            Line::Vendor(record) => document.vendors.push(record),
+            Line::Category(record) => document.categories.push(record),
+            Line::Tag(record) => document.tags.push(record),
+            Line::Item(record) => document.items.push(record),
+            Line::Price(record) => document.price_history.push(record),
+        }
+        Ok(())
+    })?;
+    Ok(document)
+}
+
+/// Grava o catálogo em `writer`, como um documento JSON ou em JSON Lines.
+pub fn write_catalog(
+    conn: &Connection,
+    exported_at: &str,
+    json_lines: bool,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    if json_lines {
+        for_each_line(conn, exported_at, |line| {
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        })?;
+    } else {
+        serde_json::to_writer_pretty(&mut writer, &load_document(conn, exported_at)?)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_file(conn: &Connection, path: &Path) -> Result<(), Box<dyn Error>> {
+    let exported_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let file = BufWriter::new(File::create(path)?);
+    write_catalog(conn, &exported_at, is_json_lines(path), file)
+}
+
+/// Lê o arquivo e substitui o catálogo pelo conteúdo dele, mantendo os ids.
+pub fn import_file(conn: &mut Connection, path: &Path) -> Result<ImportSummary, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    if is_json_lines(path) {
+        let lines = reader
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| text.as_ref().map_or(true, |text| !text.trim().is_empty()))
+            .map(|(index, text)| -> Result<Line, Box<dyn Error>> {
+                serde_json::from_str(&text?)
+                    .map_err(|e| format!("linha {}: {}", index + 1, e).into())
+            });
+        replace_catalog(conn, lines)
+    } else {
+        let document: CatalogDocument = serde_json::from_reader(reader)?;
+        let lines = std::iter::once(Line::Catalog(document.header))
+            .chain(document.vendors.into_iter().map(Line::Vendor))
+            .chain(document.categories.into_iter().map(Line::Category))
+            .chain(document.tags.into_iter().map(Line::Tag))
+            .chain(document.items.into_iter().map(Line::Item))
+            .chain(document.price_history.into_iter().map(Line::Price))
+            .map(Ok);
+        replace_catalog(conn, lines)
+    }
+}
+
+/// Apaga itens, tags, histórico e prazos e grava os do arquivo, numa única
+/// transação. A primeira linha precisa ser o cabeçalho.
+pub fn replace_catalog(
+    conn: &mut Connection,
+    mut lines: impl Iterator<Item = Result<Line, Box<dyn Error>>>,
+) -> Result<ImportSummary, Box<dyn Error>> {
+    let header = match lines.next().transpose()? {
+        Some(Line::Catalog(header)) => header,
+        _ => return Err("o arquivo não começa com o cabeçalho do catálogo".into()),
+    };
+    if header.format != FORMAT_NAME {
+        return Err(format!("formato desconhecido: '{}'", header.format).into());
+    }
+    if header.version > FORMAT_VERSION {
+        return Err(format!(
+            "o arquivo é da versão {} do formato, mais nova que a deste aplicativo ({})",
+            header.version, FORMAT_VERSION
+        )
+        .into());
+    }
+
+    let tx = conn.savepoint()?;
+    // O histórico pode vir antes do item em arquivos editados à mão
+    tx.execute_batch(
+        "PRAGMA defer_foreign_keys = ON;
+        DELETE FROM item_tag;
+        DELETE FROM price_history;
+        DELETE FROM infra_item;
+        DELETE FROM tag;
+        DELETE FROM vendor_staleness;
+        DELETE FROM category_staleness;
+        DELETE FROM vendor_contact;",
+    )?;
+    settings::set(
+        &tx,
+        staleness::STALE_DAYS_KEY,
+        &header.default_stale_days.to_string(),
+    )?;
+
+    let mut summary = ImportSummary {
+        items: 0,
+        prices: 0,
+    };
+    for line in lines {
+        match line? {
+            Line::Catalog(_) => return Err("cabeçalho repetido no meio do arquivo".into()),
+            Line::Vendor(NamedRecord {
+                name,
+                stale_days,
+                contact,
+            }) => {
+                if let Some(contact) = contact {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO vendor_contact (vendor, contact) VALUES (?1, ?2)",
+                        (&name, contact),
+                    )?;
+                }
+                if let Some(days) = stale_days {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO vendor_staleness (vendor, stale_days) VALUES (?1, ?2)",
+                        (name, days),
+                    )?;
+                }
+            }
+            Line::Category(NamedRecord {
+                name, stale_days, ..
+            }) => {
+                if let Some(days) = stale_days {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO category_staleness (category, stale_days)
+                        VALUES (?1, ?2)",
+                        (name, days),
+                    )?;
+                }
+            }
+            Line::Tag(TagRecord { id, name }) => {
+                tx.execute("INSERT INTO tag (id, name) VALUES (?1, ?2)", (id, name))?;
+            }
+            Line::Item(item) => {
+                insert_item(&tx, &item)?;
+                summary.items += 1;
+            }
+            Line::Price(price) => {
+                tx.execute(
+                    "INSERT INTO price_history (id, item_id, price, recorded_at, source)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (
+                        price.id,
+                        price.item_id,
+                        price.price,
+                        &price.recorded_at,
+                        &price.source,
+                    ),
+                )?;
+                summary.prices += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(summary)
+}
+
+fn insert_item(conn: &Connection, item: &ItemRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at, category,
+            unit, version, deleted_at, deleted_by)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        rusqlite::params![
+            item.id,
+            item.description,
+            item.brand,
+            item.vendor,
+            item.price,
+            item.updated_at,
+            item.category,
+            item.unit,
+            item.version,
+            item.deleted_at,
+            item.deleted_by,
+        ],
+    )?;
+    for name in &item.tags {
+        // Tags citadas pelo item mas ausentes da lista ganham um id novo
+        let tag_id: Option<i64> = conn
+            .query_row("SELECT id FROM tag WHERE name = ?1", [name], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let tag_id = match tag_id {
+            Some(id) => id,
+            None => {
+                conn.execute("INSERT INTO tag (name) VALUES (?1)", [name])?;
+                conn.last_insert_rowid()
+            }
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO item_tag (item_id, tag_id) VALUES (?1, ?2)",
+            (item.id, tag_id),
+        )?;
+    }
+    if !item.tags.is_empty() {
+        // Os gatilhos de item_tag incrementaram a versão
+        conn.execute(
+            "UPDATE infra_item SET version = ?1 WHERE id = ?2",
+            (item.version, item.id),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{Line, replace_catalog, write_catalog};
+    use crate::{export, items, staleness, trash};
+
+    fn catalog() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn
+    }
+
+    fn export(conn: &Connection, json_lines: bool) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_catalog(conn, "2025-01-01T00:00:00Z", json_lines, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn import(conn: &mut Connection, bytes: &[u8], json_lines: bool) {
+        let extension = if json_lines { "jsonl" } else { "json" };
+        let path = std::env::temp_dir().join(format!(
+            "catalogo-ida-e-volta-{}.{}",
+            std::process::id(),
+            extension
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let result = super::import_file(conn, &path);
+        std::fs::remove_file(path).unwrap();
+        result.unwrap();
+    }
+
+    #[test]
+    fn export_import_export_is_identical() {
+        let mut source = catalog();
+        let fields = |description: &str, price: f32, tags: &[&str]| items::ItemFields {
+            description: description.to_string(),
+            brand: "Marca".to_string(),
+            vendor: "Fornecedor".to_string(),
+            price,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            category: "Cabos".to_string(),
+            unit: "m".to_string(),
+        };
+        let id = items::upsert_item(
+            &source,
+            &fields("Cabo 2,5mm", 3.1, &["obra", "NBR"]),
+            "2024-05-01",
+        )
+        .unwrap();
+        items::upsert_item(
+            &source,
+            &fields("Cabo 2,5mm", 3.37, &["obra"]),
+            "2024-06-01",
+        )
+        .unwrap();
+        let other =
+            items::upsert_item(&source, &fields("Cabo 4mm", 5.0, &[]), "2024-06-02").unwrap();
+        trash::soft_delete(&mut source, &[other], "maria").unwrap();
+        let mut config = staleness::load_config(&source).unwrap();
+        config.default_days = 45;
+        config.vendor_days.push(("Fornecedor".to_string(), 15));
+        staleness::save_config(&mut source, &config).unwrap();
+        let contacts = [("Fornecedor".to_string(), "(11) 5555-0000".to_string())];
+        export::save_contacts(&mut source, &contacts.into_iter().collect()).unwrap();
+        assert!(id > 0);
+
+        for json_lines in [false, true] {
+            let first = export(&source, json_lines);
+            let mut target = catalog();
+            // O que já existia no destino é substituído
+            items::upsert_item(&target, &fields("Outro", 1.0, &["x"]), "2024-01-01").unwrap();
+            import(&mut target, &first, json_lines);
+            assert_eq!(
+                String::from_utf8(export(&target, json_lines)).unwrap(),
+                String::from_utf8(first).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn newer_format_versions_are_rejected() {
+        let source = catalog();
+        let text = String::from_utf8(export(&source, true)).unwrap();
+        let text = text.replacen("\"version\":1", "\"version\":99", 1);
+        let lines = text
+            .lines()
+            .map(|line| Ok(serde_json::from_str::<Line>(line)?));
+        let error = replace_catalog(&mut catalog(), lines).err().unwrap();
+        assert!(error.to_string().contains("99"));
+    }
+}
@@ -0,0 +1,286 @@
+use rusqlite::Connection;
+
+use crate::{InfraItem, format_money, settings, staleness};
+
+const DEFAULT_TEMPLATE_KEY: &str = "copy_template_default";
+
+/// Layout usado antes dos modelos: o da planilha de orçamento.
+const BUDGET_SHEET_NAME: &str = "Planilha de orçamento";
+const BUDGET_SHEET_TEMPLATE: &str = r"{description} {brand}\t\t\t\t{vendor}\t{price}";
+
+/// Campos que podem ser usados nos modelos, com a descrição mostrada na janela.
+pub const PLACEHOLDERS: [(&str, &str); 11] = [
+    ("{description}", "descrição"),
+    ("{brand}", "marca"),
+    ("{vendor}", "fornecedor"),
+    ("{price}", "preço (1.234,50)"),
+    ("{unit}", "unidade"),
+    ("{date}", "data do preço"),
+    ("{age}", "idade do preço em dias"),
+    ("{category}", "categoria"),
+    ("{tags}", "tags"),
+    ("{id}", "código do item"),
+    (r"\t", "tabulação (muda de coluna na planilha)"),
+];
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS copy_template (
+            name TEXT PRIMARY KEY,
+            template TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+#[derive(Clone, PartialEq)]
+pub struct CopyTemplate {
+    pub name: String,
+    /// Texto de uma linha, com os campos entre chaves e `\t` para tabulação.
+    pub template: String,
+}
+
+impl CopyTemplate {
+    /// Texto do item, com cada campo preenchido. Chaves que não são de um
+    /// campo conhecido ficam como estão.
+    pub fn render(&self, item: &InfraItem) -> String {
+        let mut output = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find(['{', '\\']) {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix(r"\t") {
+                output.push('\t');
+                rest = after;
+                continue;
+            }
+            let field = rest
+                .find('}')
+                .and_then(|end| Some((field(item, &rest[1..end])?, end)));
+            match field {
+                Some((value, end)) => {
+                    output.push_str(&value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    output.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+fn field(item: &InfraItem, name: &str) -> Option<String> {
+    Some(match name {
+        "description" => item.description.clone(),
+        "brand" => item.brand.clone(),
+        "vendor" => item.vendor.clone(),
+        "price" => format_money(item.price),
+        "unit" => item.unit.clone(),
+        "date" => item.updated_at.clone(),
+        "age" => staleness::age_in_days(&item.updated_at).map_or(String::new(), |d| d.to_string()),
+        "category" => item.category.clone(),
+        "tags" => item.tags.join(", "),
+        "id" => item.id.to_string(),
+        _ => return None,
+    })
+}
+
+/// Modelos salvos, ou o da planilha de orçamento se ainda não há nenhum.
+pub fn load_templates(conn: &Connection) -> rusqlite::Result<Vec<CopyTemplate>> {
+    let mut stmt = conn.prepare("SELECT name, template FROM copy_template ORDER BY name")?;
+    let templates = stmt
+        .query_map([], |row| {
+            Ok(CopyTemplate {
+                name: row.get(0)?,
+                template: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if templates.is_empty() {
+        return Ok(vec![CopyTemplate {
+            name: BUDGET_SHEET_NAME.to_string(),
+            template: BUDGET_SHEET_TEMPLATE.to_string(),
+        }]);
+    }
+    Ok(templates)
+}
+
+/// Substitui os modelos salvos pela lista editada.
+pub fn save_templates(
+    conn: &mut Connection,
+    templates: &[CopyTemplate],
+    default_name: &str,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM copy_template", [])?;
+    for template in templates {
+        tx.execute(
+            "INSERT OR REPLACE INTO copy_template (name, template) VALUES (?1, ?2)",
+            (template.name.trim(), &template.template),
+        )?;
+    }
+    settings::set(&tx, DEFAULT_TEMPLATE_KEY, default_name)?;
+    tx.commit()
+}
+
+/// Nome do modelo usado no clique com o botão direito e em "Copiar selecionados".
+pub fn default_template_name(conn: &Connection) -> String {
+    settings::get(conn, DEFAULT_TEMPLATE_KEY)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| BUDGET_SHEET_NAME.to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Linhas copiadas: texto com tabulações e uma tabela HTML com as mesmas
+/// células, para o Word e o Excel colarem como tabela.
+pub struct Copied {
+    pub text: String,
+    pub html: String,
+}
+
+pub fn render_items(template: &CopyTemplate, items: &[InfraItem]) -> Copied {
+    from_lines(items.iter().map(|item| template.render(item)).collect())
+}
+
+/// Linhas com as células separadas por tabulação.
+pub fn from_lines(lines: Vec<String>) -> Copied {
+    let mut html = String::from("<table>\n");
+    for line in &lines {
+        html.push_str("<tr>");
+        for cell in line.split('\t') {
+            html.push_str(&format!("<td>{}</td>", escape_html(cell)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>");
+    Copied {
+        text: lines.join("\n"),
+        html,
+    }
+}
+
+/// Área de transferência do sistema, para gravar também o HTML (o egui só
+/// copia texto). Fica aberta enquanto o aplicativo roda: no Linux, o conteúdo
+/// copiado é servido por ela.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    fn system(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.system.is_none() {
+            self.system = arboard::Clipboard::new()
+                .inspect_err(|e| log::warn!("Área de transferência indisponível: {}", e))
+                .ok();
+        }
+        self.system.as_mut()
+    }
+
+    /// Copia texto e HTML; sem acesso à área de transferência do sistema,
+    /// copia só o texto pelo egui.
+    pub fn copy(&mut self, ctx: &eframe::egui::Context, copied: Copied) {
+        let result = match self.system() {
+            Some(system) => system.set_html(copied.html, Some(copied.text.clone())),
+            None => Err(arboard::Error::ClipboardNotSupported),
+        };
+        if let Err(e) = result {
+            log::warn!("Falha ao copiar HTML, copiando só o texto: {}", e);
+            ctx.copy_text(copied.text);
+        }
+    }
+
+    /// Texto da área de transferência, se houver.
+    pub fn paste_text(&mut self) -> Option<String> {
+        self.system()?
+            .get_text()
+            .inspect_err(|e| log::warn!("Falha ao ler a área de transferência: {}", e))
+            .ok()
+    }
+}
+
+#[derive(Default)]
+pub struct TemplatesView {
+    pub open: bool,
+    pub templates: Vec<CopyTemplate>,
+    pub default_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{
+        CopyTemplate, default_template_name, load_templates, render_items, save_templates,
+    };
+    use crate::InfraItem;
+
+    fn item() -> InfraItem {
+        InfraItem {
+            id: 7,
+            description: "Disjuntor <DIN>".to_string(),
+            brand: "Marca".to_string(),
+            vendor: "Fornecedor & Cia".to_string(),
+            price: 1234.5,
+            updated_at: "2024-03-01".to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: "pç".to_string(),
+        }
+    }
+
+    #[test]
+    fn legacy_layout_is_the_default() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let templates = load_templates(&conn).unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(default_template_name(&conn), templates[0].name);
+        assert_eq!(
+            templates[0].render(&item()),
+            "Disjuntor <DIN> Marca\t\t\t\tFornecedor & Cia\t1.234,50"
+        );
+    }
+
+    #[test]
+    fn html_has_one_cell_per_column() {
+        let template = CopyTemplate {
+            name: "Orçamento".to_string(),
+            template: r"{description}\t{unit}\t{price}".to_string(),
+        };
+        let copied = render_items(&template, &[item(), item()]);
+        assert_eq!(copied.text.lines().count(), 2);
+        assert!(
+            copied
+                .html
+                .contains("<tr><td>Disjuntor &lt;DIN&gt;</td><td>pç</td><td>1.234,50</td></tr>")
+        );
+    }
+
+    #[test]
+    fn saved_templates_replace_the_default() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let template = CopyTemplate {
+            name: "ERP".to_string(),
+            template: "{id};{price};{outro}".to_string(),
+        };
+        save_templates(&mut conn, std::slice::from_ref(&template), "ERP").unwrap();
+        assert!(load_templates(&conn).unwrap() == [template.clone()]);
+        assert_eq!(default_template_name(&conn), "ERP");
+        assert_eq!(template.render(&item()), "7;1.234,50;{outro}");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{path::Path, time::Duration};
+
+use rusqlite::Connection;
+
+/// Quanto tempo uma escrita espera enquanto outra instância está gravando.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Caminho numa pasta de rede: `\\servidor\pasta`, uma unidade mapeada para
+/// um compartilhamento (`Z:\catalogo.db`) ou uma montagem NFS/SMB. O modo WAL
+/// depende de memória compartilhada entre os processos e não funciona nelas.
+fn is_network_path(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    text.starts_with(r"\\") || text.starts_with("//") || is_remote_mount(path)
+}
+
+/// No Windows, pergunta ao sistema o tipo da unidade (`DRIVE_REMOTE`).
+#[cfg(windows)]
+fn is_remote_mount(path: &Path) -> bool {
+    use std::{os::windows::ffi::OsStrExt, path::Component};
+
+    const DRIVE_REMOTE: u32 = 4;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetDriveTypeW(root_path_name: *const u16) -> u32;
+    }
+
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(Component::Prefix(prefix)) = absolute.components().next() else {
+        return false;
+    };
+    let root: Vec<u16> = prefix
+        .as_os_str()
+        .encode_wide()
+        .chain("\\".encode_utf16())
+        .chain([0])
+        .collect();
+    // SAFETY: `root` é uma string terminada em zero que vive durante a chamada
+    unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+}
+
+/// No Linux, procura em `/proc/mounts` o sistema de arquivos que contém o caminho.
+#[cfg(target_os = "linux")]
+fn is_remote_mount(path: &Path) -> bool {
+    const REMOTE_TYPES: [&str; 7] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "9p"];
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let mount_point = fields.next()?.replace("\\040", " ");
+            Some((mount_point, fields.next()?.to_string()))
+        })
+        .filter(|(mount_point, _)| absolute.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .is_some_and(|(_, fs_type)| REMOTE_TYPES.contains(&fs_type.as_str()))
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn is_remote_mount(_path: &Path) -> bool {
+    false
+}
+
+/// Configura a conexão para uso simultâneo por mais de uma instância.
+pub fn configure(conn: &Connection, path: &Path) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let mode = if is_network_path(path) {
+        "DELETE"
+    } else {
+        "WAL"
+    };
+    conn.pragma_update_and_check(None, "journal_mode", mode, |row| row.get::<_, String>(0))?;
+    Ok(())
+}
+
+/// Coluna `version`, incrementada por gatilhos a cada alteração do item ou de
+/// suas tags, usada para detectar edições simultâneas.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    crate::add_column_if_missing(conn, "infra_item", "version", "INTEGER NOT NULL DEFAULT 0")?;
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS infra_item_version AFTER UPDATE ON infra_item
+            FOR EACH ROW WHEN NEW.version = OLD.version
+            BEGIN
+                UPDATE infra_item SET version = OLD.version + 1 WHERE id = NEW.id;
+            END;
+        CREATE TRIGGER IF NOT EXISTS item_tag_insert_version AFTER INSERT ON item_tag
+            BEGIN
+                UPDATE infra_item SET version = version + 1 WHERE id = NEW.item_id;
+            END;
+        CREATE TRIGGER IF NOT EXISTS item_tag_delete_version AFTER DELETE ON item_tag
+            BEGIN
+                UPDATE infra_item SET version = version + 1 WHERE id = OLD.item_id;
+            END;",
+    )
+}
+
+/// Muda sempre que outra conexão grava no banco (mas não com as gravações
+/// desta própria conexão).
+pub fn data_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.pragma_query_value(None, "data_version", |row| row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::is_network_path;
+
+    #[test]
+    fn unc_paths_are_network_paths() {
+        assert!(is_network_path(Path::new(
+            r"\\servidor\compras\catalogo.db"
+        )));
+        assert!(is_network_path(Path::new("//servidor/compras/catalogo.db")));
+    }
+
+    #[test]
+    fn local_paths_are_not_network_paths() {
+        assert!(!is_network_path(&std::env::temp_dir().join("catalogo.db")));
+        assert!(!is_network_path(Path::new("infra_items.db")));
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::{
+    InfraItem,
+    filters::fold,
+    items::{self, ItemFields, UpdateOutcome},
+};
+
+/// Palavras ignoradas na comparação das descrições.
+const STOPWORDS: [&str; 8] = ["de", "da", "do", "das", "dos", "com", "para", "e"];
+
+/// Palavras da descrição, sem acentos nem maiúsculas. Números e letras
+/// grudados são separados, para "20A" e "20 A" darem as mesmas palavras.
+pub fn tokens(description: &str) -> BTreeSet<String> {
+    let mut tokens = BTreeSet::new();
+    let mut current = String::new();
+    let mut current_is_digit = false;
+    for c in fold(description).chars() {
+        if !c.is_alphanumeric() {
+            tokens.insert(std::mem::take(&mut current));
+            continue;
+        }
+        let is_digit = c.is_ascii_digit();
+        if !current.is_empty() && is_digit != current_is_digit {
+            tokens.insert(std::mem::take(&mut current));
+        }
+        current.push(c);
+        current_is_digit = is_digit;
+    }
+    tokens.insert(current);
+    tokens.retain(|token| !token.is_empty() && !STOPWORDS.contains(&token.as_str()));
+    tokens
+}
+
+/// Semelhança entre duas descrições, de 0 a 1: palavras em comum sobre o
+/// total de palavras diferentes das duas (índice de Jaccard).
+pub fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+/// Onde procurar itens repetidos.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Scope {
+    /// Só itens do mesmo fornecedor: o mesmo material cadastrado duas vezes.
+    #[default]
+    SameVendor,
+    /// Itens de qualquer fornecedor, para padronizar as descrições.
+    AllVendors,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::SameVendor, Scope::AllVendors];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scope::SameVendor => "No mesmo fornecedor",
+            Scope::AllVendors => "Entre todos os fornecedores",
+        }
+    }
+}
+
+/// Itens ligados entre si por descrições parecidas.
+pub struct Cluster {
+    pub items: Vec<InfraItem>,
+    /// Maior semelhança entre dois itens do grupo.
+    pub score: f32,
+}
+
+/// Agrupa os itens cujas descrições têm semelhança de pelo menos `threshold`.
+/// Os grupos vêm dos mais parecidos para os menos.
+pub fn find_clusters(items: &[InfraItem], scope: Scope, threshold: f32) -> Vec<Cluster> {
+    let token_sets: Vec<BTreeSet<String>> =
+        items.iter().map(|item| tokens(&item.description)).collect();
+    let vendors: Vec<String> = items.iter().map(|item| fold(item.vendor.trim())).collect();
+
+    // Só compara itens com alguma palavra em comum
+    let mut by_token: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, set) in token_sets.iter().enumerate() {
+        for token in set {
+            by_token.entry(token.as_str()).or_default().push(index);
+        }
+    }
+
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    let mut best = vec![0.0f32; items.len()];
+    for (a, set) in token_sets.iter().enumerate() {
+        let mut candidates: BTreeSet<usize> = BTreeSet::new();
+        for token in set {
+            candidates.extend(by_token[token.as_str()].iter().filter(|b| **b > a));
+        }
+        for b in candidates {
+            if scope == Scope::SameVendor && vendors[a] != vendors[b] {
+                continue;
+            }
+            let score = similarity(set, &token_sets[b]);
+            if score >= threshold {
+                let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+                parent[root_b] = root_a;
+                best[a] = best[a].max(score);
+                best[b] = best[b].max(score);
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..items.len() {
+        let root = find(&mut parent, index);
+        groups.entry(root).or_default().push(index);
+    }
+    let mut clusters: Vec<Cluster> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| Cluster {
+            score: members.iter().map(|i| best[*i]).fold(0.0, f32::max),
+            items: members.iter().map(|i| items[*i].clone()).collect(),
+        })
+        .collect();
+    clusters.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.items.len().cmp(&a.items.len()))
+            .then(a.items[0].description.cmp(&b.items[0].description))
+    });
+    clusters
+}
+
+fn find(parent: &mut [usize], mut index: usize) -> usize {
+    while parent[index] != index {
+        parent[index] = parent[parent[index]];
+        index = parent[index];
+    }
+    index
+}
+
+/// O que levar dos itens mesclados para o item mantido.
+#[derive(Clone, Copy)]
+pub struct MergeOptions {
+    pub combine_history: bool,
+    pub repoint_budget: bool,
+}
+
+/// Mescla `others` em `kept`: o item mantido recebe as tags dos outros e,
+/// conforme `options`, o histórico de preços e as linhas do orçamento; os
+/// outros vão para a lixeira.
+pub fn merge(
+    conn: &mut Connection,
+    kept: i32,
+    others: &[i32],
+    options: MergeOptions,
+    user: &str,
+) -> rusqlite::Result<()> {
+    let tx = conn.savepoint()?;
+    merge_into(&tx, kept, others, options, user)?;
+    tx.commit()
+}
+
+/// Grava `fields` no item `existing` e mescla nele o item `editing`, numa
+/// única transação: a edição que repetiria a chave de outro item. Se
+/// `existing` mudou desde que foi lido, nada é gravado.
+pub fn merge_edit(
+    conn: &mut Connection,
+    editing: i32,
+    existing: &InfraItem,
+    fields: &ItemFields,
+    user: &str,
+) -> rusqlite::Result<UpdateOutcome> {
+    let now = Utc::now().format("%Y-%m-%d").to_string();
+    let tx = conn.savepoint()?;
+    let outcome = items::update_item(&tx, existing.id, existing.version, fields, &now)?;
+    if let UpdateOutcome::Updated = outcome {
+        let options = MergeOptions {
+            combine_history: true,
+            repoint_budget: true,
+        };
+        merge_into(&tx, existing.id, &[editing], options, user)?;
+        tx.commit()?;
+    }
+    Ok(outcome)
+}
+
+fn merge_into(
+    tx: &Connection,
+    kept: i32,
+    others: &[i32],
+    options: MergeOptions,
+    user: &str,
+) -> rusqlite::Result<()> {
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    for other in others {
+        tx.execute(
+            "INSERT OR IGNORE INTO item_tag (item_id, tag_id)
+            SELECT ?1, tag_id FROM item_tag WHERE item_id = ?2",
+            (kept, other),
+        )?;
+        if options.combine_history {
+            tx.execute(
+                "UPDATE price_history SET item_id = ?1 WHERE item_id = ?2",
+                (kept, other),
+            )?;
+        }
+        if options.repoint_budget {
+            tx.execute(
+                "INSERT INTO budget_item (item_id, quantity, added_at)
+                SELECT ?1, quantity, added_at FROM budget_item WHERE item_id = ?2
+                ON CONFLICT(item_id) DO UPDATE SET quantity = quantity + excluded.quantity",
+                (kept, other),
+            )?;
+            tx.execute("DELETE FROM budget_item WHERE item_id = ?1", [other])?;
+        }
+        tx.execute(
+            "UPDATE infra_item SET deleted_at = ?1, deleted_by = ?2
+            WHERE id = ?3 AND deleted_at IS NULL",
+            (&now, user, other),
+        )?;
+    }
+    Ok(())
+}
+
+pub struct DuplicatesView {
+    pub open: bool,
+    pub scope: Scope,
+    /// Semelhança mínima, de 0 a 1.
+    pub threshold: f32,
+    pub clusters: Vec<Cluster>,
+    pub selected: Option<usize>,
+    /// Item do grupo escolhido que fica; os outros são mesclados nele.
+    pub kept: Option<i32>,
+    pub options: MergeOptions,
+}
+
+impl Default for DuplicatesView {
+    fn default() -> Self {
+        DuplicatesView {
+            open: false,
+            scope: Scope::default(),
+            threshold: 0.8,
+            clusters: vec![],
+            selected: None,
+            kept: None,
+            options: MergeOptions {
+                combine_history: true,
+                repoint_budget: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{MergeOptions, Scope, find_clusters, merge, merge_edit, similarity, tokens};
+    use crate::{
+        InfraItem, budget, history,
+        items::{self, ItemFields, UpdateOutcome},
+    };
+
+    fn item(id: i32, description: &str, vendor: &str) -> InfraItem {
+        InfraItem {
+            id,
+            description: description.to_string(),
+            brand: String::new(),
+            vendor: vendor.to_string(),
+            price: 10.0,
+            updated_at: "2024-01-01".to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: String::new(),
+        }
+    }
+
+    #[test]
+    fn reordered_descriptions_are_the_same() {
+        let a = tokens("Disjuntor DIN 20A bipolar");
+        let b = tokens("Disjuntor bipolar 20 A DIN");
+        assert_eq!(a, b);
+        assert_eq!(similarity(&a, &b), 1.0);
+        assert!(similarity(&a, &tokens("Disjuntor DIN 25A bipolar")) < 0.8);
+    }
+
+    #[test]
+    fn clusters_respect_scope() {
+        let items = [
+            item(1, "Disjuntor DIN 20A bipolar", "Eletro"),
+            item(2, "Disjuntor bipolar 20 A DIN", "Eletro"),
+            item(3, "disjuntor bipolar de 20A din", "Outra"),
+            item(4, "Cabo flexível 2,5mm", "Eletro"),
+        ];
+        let same_vendor = find_clusters(&items, Scope::SameVendor, 0.8);
+        assert_eq!(same_vendor.len(), 1);
+        assert_eq!(same_vendor[0].items.len(), 2);
+        let all = find_clusters(&items, Scope::AllVendors, 0.8);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].items.len(), 3);
+    }
+
+    #[test]
+    fn merge_moves_history_budget_and_tags() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Disjuntor DIN 20A bipolar', '', 'Eletro', 30, '2024-01-01'),
+                   (2, 'Disjuntor bipolar 20 A DIN', '', 'Eletro', 32, '2024-02-01');",
+        )
+        .unwrap();
+        history::record_price(&conn, 1, 30.0, "2024-01-01", "cadastro").unwrap();
+        history::record_price(&conn, 2, 32.0, "2024-02-01", "cadastro").unwrap();
+        crate::tags::set_item_tags(&conn, 2, &["obra".to_string()]).unwrap();
+        budget::add_items(&mut conn, &[1, 2]).unwrap();
+        budget::add_items(&mut conn, &[2]).unwrap();
+
+        let options = MergeOptions {
+            combine_history: true,
+            repoint_budget: true,
+        };
+        merge(&mut conn, 1, &[2], options, "teste").unwrap();
+
+        assert!(items::load_item(&conn, 2).unwrap().is_none());
+        let kept = items::load_item(&conn, 1).unwrap().unwrap();
+        assert_eq!(kept.tags, ["obra"]);
+        assert_eq!(history::load_history(&conn, 1).unwrap().len(), 2);
+        let lines = budget::load_lines(&conn).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].quantity, 3.0);
+    }
+
+    #[test]
+    fn merge_edit_writes_nothing_when_existing_changed() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Cabo 2,5mm', 'Sil', 'Eletro', 3, '2024-01-01'),
+                   (2, 'Cabo 2,5 mm', 'Sil', 'Eletro', 4, '2024-01-01');",
+        )
+        .unwrap();
+        let existing = items::load_item(&conn, 1).unwrap().unwrap();
+        let mut fields = ItemFields {
+            description: existing.description.clone(),
+            brand: existing.brand.clone(),
+            vendor: existing.vendor.clone(),
+            price: 4.5,
+            tags: vec![],
+            category: String::new(),
+            unit: String::new(),
+        };
+
+        let stale = InfraItem {
+            version: existing.version - 1,
+            ..existing.clone()
+        };
+        let outcome = merge_edit(&mut conn, 2, &stale, &fields, "teste").unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Conflict(_)));
+        assert!(items::load_item(&conn, 2).unwrap().is_some());
+
+        fields.unit = "m".to_string();
+        let outcome = merge_edit(&mut conn, 2, &existing, &fields, "teste").unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Updated));
+        assert!(items::load_item(&conn, 2).unwrap().is_none());
+        let kept = items::load_item(&conn, 1).unwrap().unwrap();
+        assert_eq!((kept.price, kept.unit.as_str()), (4.5, "m"));
+    }
+}
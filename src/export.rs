@@ -0,0 +1,332 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{InfraItem, staleness};
+
+pub fn init_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS export_preset (
+            name TEXT PRIMARY KEY,
+            options TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS vendor_contact (
+            vendor TEXT PRIMARY KEY,
+            contact TEXT NOT NULL
+        );",
+    )
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportColumn {
+    Description,
+    Brand,
+    Vendor,
+    Price,
+    UpdatedAt,
+    AgeDays,
+    Category,
+    Unit,
+    Tags,
+    VendorContact,
+}
+
+impl ExportColumn {
+    pub const ALL: [ExportColumn; 10] = [
+        ExportColumn::Description,
+        ExportColumn::Brand,
+        ExportColumn::Vendor,
+        ExportColumn::Price,
+        ExportColumn::UpdatedAt,
+        ExportColumn::AgeDays,
+        ExportColumn::Category,
+        ExportColumn::Unit,
+        ExportColumn::Tags,
+        ExportColumn::VendorContact,
+    ];
+
+    /// Cabeçalho da coluna na planilha (os cinco primeiros, iguais aos do
+    /// "Exportar CSV").
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportColumn::Description => "descrição",
+            ExportColumn::Brand => "marca",
+            ExportColumn::Vendor => "fornecedor",
+            ExportColumn::Price => "preço",
+            ExportColumn::UpdatedAt => "última atualização",
+            ExportColumn::AgeDays => "idade (dias)",
+            ExportColumn::Category => "categoria",
+            ExportColumn::Unit => "unidade",
+            ExportColumn::Tags => "tags",
+            ExportColumn::VendorContact => "contato do fornecedor",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Delimiter {
+    Semicolon,
+    Comma,
+    Tab,
+}
+
+impl Delimiter {
+    pub const ALL: [Delimiter; 3] = [Delimiter::Semicolon, Delimiter::Comma, Delimiter::Tab];
+
+    fn byte(self) -> u8 {
+        match self {
+            Delimiter::Semicolon => b';',
+            Delimiter::Comma => b',',
+            Delimiter::Tab => b'\t',
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Delimiter::Semicolon => "Ponto e vírgula (;)",
+            Delimiter::Comma => "Vírgula (,)",
+            Delimiter::Tab => "Tabulação",
+        }
+    }
+}
+
+/// Colunas e formato da planilha exportada; é o que fica salvo num modelo.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ExportOptions {
+    pub columns: Vec<ExportColumn>,
+    pub delimiter: Delimiter,
+    /// Vírgula como separador decimal (padrão brasileiro); senão, ponto.
+    pub decimal_comma: bool,
+}
+
+impl Default for ExportOptions {
+    /// O mesmo formato do "Exportar CSV".
+    fn default() -> Self {
+        ExportOptions {
+            columns: ExportColumn::ALL[..5].to_vec(),
+            delimiter: Delimiter::Semicolon,
+            decimal_comma: true,
+        }
+    }
+}
+
+impl ExportOptions {
+    fn cell(
+        &self,
+        column: ExportColumn,
+        item: &InfraItem,
+        contacts: &BTreeMap<String, String>,
+    ) -> String {
+        match column {
+            ExportColumn::Description => item.description.clone(),
+            ExportColumn::Brand => item.brand.clone(),
+            ExportColumn::Vendor => item.vendor.clone(),
+            ExportColumn::Price => {
+                let price = format!("{:.2}", item.price);
+                if self.decimal_comma {
+                    price.replace('.', ",")
+                } else {
+                    price
+                }
+            }
+            ExportColumn::UpdatedAt => item.updated_at.clone(),
+            ExportColumn::AgeDays => staleness::age_in_days(&item.updated_at)
+                .map_or(String::new(), |days| days.to_string()),
+            ExportColumn::Category => item.category.clone(),
+            ExportColumn::Unit => item.unit.clone(),
+            ExportColumn::Tags => item.tags.join(", "),
+            ExportColumn::VendorContact => contacts.get(&item.vendor).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Grava os itens com as colunas e o formato escolhidos.
+pub fn write_csv(
+    path: &Path,
+    items: &[InfraItem],
+    options: &ExportOptions,
+    contacts: &BTreeMap<String, String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    // BOM para o Excel reconhecer os acentos
+    file.write_all(b"\xEF\xBB\xBF")?;
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(options.delimiter.byte())
+        .from_writer(file);
+    wtr.write_record(options.columns.iter().map(|column| column.label()))?;
+    for item in items {
+        wtr.write_record(
+            options
+                .columns
+                .iter()
+                .map(|column| options.cell(*column, item, contacts)),
+        )?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct ExportPreset {
+    pub name: String,
+    pub options: ExportOptions,
+}
+
+pub fn load_presets(conn: &Connection) -> rusqlite::Result<Vec<ExportPreset>> {
+    let mut stmt = conn.prepare("SELECT name, options FROM export_preset ORDER BY name")?;
+    let presets = stmt.query_map([], |row| {
+        let json: String = row.get(1)?;
+        Ok(ExportPreset {
+            name: row.get(0)?,
+            options: serde_json::from_str(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?,
+        })
+    })?;
+    presets.collect()
+}
+
+pub fn save_preset(
+    conn: &Connection,
+    preset: &ExportPreset,
+) -> Result<(), Box<dyn std::error::Error>> {
+    conn.execute(
+        "INSERT INTO export_preset (name, options) VALUES (?1, ?2)
+        ON CONFLICT(name) DO UPDATE SET options = excluded.options",
+        (&preset.name, serde_json::to_string(&preset.options)?),
+    )?;
+    Ok(())
+}
+
+pub fn delete_preset(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    let affected = conn.execute("DELETE FROM export_preset WHERE name = ?1", [name])?;
+    Ok(affected > 0)
+}
+
+/// Contato de cada fornecedor (e-mail, telefone...), exportado como coluna.
+pub fn load_contacts(conn: &Connection) -> rusqlite::Result<BTreeMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT vendor, contact FROM vendor_contact")?;
+    let contacts = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    contacts.collect()
+}
+
+/// Grava os contatos; os deixados em branco são apagados.
+pub fn save_contacts(
+    conn: &mut Connection,
+    contacts: &BTreeMap<String, String>,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for (vendor, contact) in contacts {
+        if contact.trim().is_empty() {
+            tx.execute("DELETE FROM vendor_contact WHERE vendor = ?1", [vendor])?;
+        } else {
+            tx.execute(
+                "INSERT INTO vendor_contact (vendor, contact) VALUES (?1, ?2)
+                ON CONFLICT(vendor) DO UPDATE SET contact = excluded.contact",
+                (vendor, contact.trim()),
+            )?;
+        }
+    }
+    tx.commit()
+}
+
+/// Exporta os itens visíveis (com a busca e os filtros aplicados) ou só os
+/// selecionados.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum ExportScope {
+    #[default]
+    Visible,
+    Selection,
+}
+
+#[derive(Default)]
+pub struct ExportView {
+    pub open: bool,
+    pub scope: ExportScope,
+    pub options: ExportOptions,
+    pub presets: Vec<ExportPreset>,
+    pub new_preset_name: String,
+    pub contacts: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rusqlite::Connection;
+
+    use super::{
+        Delimiter, ExportColumn, ExportOptions, ExportPreset, load_presets, save_preset, write_csv,
+    };
+    use crate::InfraItem;
+
+    #[test]
+    fn writes_chosen_columns_and_format() {
+        let item = InfraItem {
+            id: 1,
+            description: "Cabo 2,5mm".to_string(),
+            brand: "Marca".to_string(),
+            vendor: "Fornecedor".to_string(),
+            price: 1234.5,
+            updated_at: "2024-01-01".to_string(),
+            tags: vec!["obra".to_string(), "NBR".to_string()],
+            version: 0,
+            category: "Cabos".to_string(),
+            unit: "m".to_string(),
+        };
+        let contacts = BTreeMap::from([(
+            "Fornecedor".to_string(),
+            "vendas@fornecedor.com.br".to_string(),
+        )]);
+        let options = ExportOptions {
+            columns: vec![
+                ExportColumn::Description,
+                ExportColumn::Price,
+                ExportColumn::Unit,
+                ExportColumn::Tags,
+                ExportColumn::VendorContact,
+            ],
+            delimiter: Delimiter::Tab,
+            decimal_comma: false,
+        };
+        let path = std::env::temp_dir().join(format!("catalogo-export-{}.csv", std::process::id()));
+        write_csv(&path, &[item], &options, &contacts).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.trim_start_matches('\u{feff}').lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "descrição\tpreço\tunidade\ttags\tcontato do fornecedor",
+                "Cabo 2,5mm\t1234.50\tm\tobra, NBR\tvendas@fornecedor.com.br",
+            ]
+        );
+    }
+
+    #[test]
+    fn presets_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let preset = ExportPreset {
+            name: "Mensal ERP".to_string(),
+            options: ExportOptions {
+                columns: vec![ExportColumn::Vendor, ExportColumn::AgeDays],
+                delimiter: Delimiter::Comma,
+                decimal_comma: false,
+            },
+        };
+        save_preset(&conn, &preset).unwrap();
+        let loaded = load_presets(&conn).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "Mensal ERP");
+        assert_eq!(loaded[0].options, preset.options);
+    }
+}
@@ -0,0 +1,355 @@
+use chrono::NaiveDate;
+use eframe::egui;
+use rusqlite::{Connection, functions::FunctionFlags, types::Value};
+use serde::{Deserialize, Serialize};
+
+use crate::staleness::{AGE_DAYS_SQL, stale_days_sql};
+use crate::{ITEM_COLUMNS, InfraItem, item_from_row, money};
+
+/// Texto em minúsculas e sem acentos, para comparar "Disjuntor" com "DISJUNTOR"
+/// e "Elétrico" com "eletrico".
+pub fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'õ' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            c => c,
+        })
+        .collect()
+}
+
+/// Registra `fold(texto)` na conexão, para as consultas compararem textos
+/// como `fold`. O LIKE do SQLite só ignora a caixa de letras ASCII.
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(fold(&ctx.get::<String>(0)?)),
+    )
+}
+
+/// Protege `%`, `_` e a própria barra para o texto ser buscado literalmente
+/// num `LIKE ... ESCAPE '\'`.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AgeBucket {
+    UpToWeek,
+    UpToMonth,
+    UpToQuarter,
+    OlderThanQuarter,
+}
+
+impl AgeBucket {
+    pub const ALL: [AgeBucket; 4] = [
+        AgeBucket::UpToWeek,
+        AgeBucket::UpToMonth,
+        AgeBucket::UpToQuarter,
+        AgeBucket::OlderThanQuarter,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AgeBucket::UpToWeek => "Até 7 dias",
+            AgeBucket::UpToMonth => "8 a 30 dias",
+            AgeBucket::UpToQuarter => "31 a 90 dias",
+            AgeBucket::OlderThanQuarter => "Mais de 90 dias",
+        }
+    }
+
+    /// Faixa de idade em dias (mínimo inclusivo, máximo inclusivo).
+    fn day_range(self) -> (i64, Option<i64>) {
+        match self {
+            AgeBucket::UpToWeek => (0, Some(7)),
+            AgeBucket::UpToMonth => (8, Some(30)),
+            AgeBucket::UpToQuarter => (31, Some(90)),
+            AgeBucket::OlderThanQuarter => (91, None),
+        }
+    }
+}
+
+/// Estado do painel de filtros. Preços e datas ficam como texto digitado
+/// pelo usuário e só são interpretados ao montar a consulta. É gravado
+/// inteiro, em JSON, nos filtros salvos.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemFilter {
+    pub query: String,
+    pub tags: Vec<String>,
+    pub vendors: Vec<String>,
+    pub brands: Vec<String>,
+    pub min_price: String,
+    pub max_price: String,
+    pub updated_from: String,
+    pub updated_to: String,
+    pub age: Option<AgeBucket>,
+    pub outdated_only: bool,
+}
+
+impl ItemFilter {
+    pub fn clear(&mut self) {
+        let outdated_only = self.outdated_only;
+        *self = ItemFilter {
+            outdated_only,
+            ..Default::default()
+        };
+    }
+
+    /// Mensagens para os campos preenchidos com valores que não puderam ser interpretados.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if parse_price(&self.min_price).is_err() {
+            errors.push(format!("Preço mínimo inválido: '{}'", self.min_price));
+        }
+        if parse_price(&self.max_price).is_err() {
+            errors.push(format!("Preço máximo inválido: '{}'", self.max_price));
+        }
+        if parse_date(&self.updated_from).is_err() {
+            errors.push(format!("Data inicial inválida: '{}'", self.updated_from));
+        }
+        if parse_date(&self.updated_to).is_err() {
+            errors.push(format!("Data final inválida: '{}'", self.updated_to));
+        }
+        errors
+    }
+
+    /// Monta a cláusula WHERE e os parâmetros correspondentes.
+    /// Campos inválidos são ignorados (ver `errors`).
+    fn where_clause(&self) -> (String, Vec<Value>) {
+        let mut conditions: Vec<String> = vec!["deleted_at IS NULL".to_string()];
+        let mut params: Vec<Value> = vec![];
+
+        let query = self.query.trim();
+        if !query.is_empty() {
+            params.push(Value::Text(format!("%{}%", escape_like(&fold(query)))));
+            let n = params.len();
+            conditions.push(format!(
+                "(fold(description) LIKE ?{n} ESCAPE '\\' OR fold(vendor) LIKE ?{n} ESCAPE '\\'
+                  OR fold(brand) LIKE ?{n} ESCAPE '\\')"
+            ));
+        }
+
+        for tag in &self.tags {
+            params.push(Value::Text(tag.clone()));
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM item_tag it JOIN tag t ON t.id = it.tag_id
+                         WHERE it.item_id = infra_item.id AND t.name = ?{})",
+                params.len()
+            ));
+        }
+
+        for (column, values) in [("vendor", &self.vendors), ("brand", &self.brands)] {
+            if values.is_empty() {
+                continue;
+            }
+            let placeholders: Vec<String> = values
+                .iter()
+                .map(|v| {
+                    params.push(Value::Text(v.clone()));
+                    format!("?{}", params.len())
+                })
+                .collect();
+            conditions.push(format!("{} IN ({})", column, placeholders.join(", ")));
+        }
+
+        if let Ok(Some(min)) = parse_price(&self.min_price) {
+            params.push(Value::Real(min as f64));
+            conditions.push(format!("price >= ?{}", params.len()));
+        }
+        if let Ok(Some(max)) = parse_price(&self.max_price) {
+            params.push(Value::Real(max as f64));
+            conditions.push(format!("price <= ?{}", params.len()));
+        }
+
+        if let Ok(Some(from)) = parse_date(&self.updated_from) {
+            params.push(Value::Text(from.format("%Y-%m-%d").to_string()));
+            conditions.push(format!("updated_at >= ?{}", params.len()));
+        }
+        if let Ok(Some(to)) = parse_date(&self.updated_to) {
+            params.push(Value::Text(to.format("%Y-%m-%d").to_string()));
+            conditions.push(format!("updated_at <= ?{}", params.len()));
+        }
+
+        if let Some(age) = self.age {
+            let (min_days, max_days) = age.day_range();
+            params.push(Value::Integer(min_days));
+            conditions.push(format!("{} >= ?{}", AGE_DAYS_SQL, params.len()));
+            if let Some(max_days) = max_days {
+                params.push(Value::Integer(max_days));
+                conditions.push(format!("{} <= ?{}", AGE_DAYS_SQL, params.len()));
+            }
+        }
+
+        if self.outdated_only {
+            conditions.push(format!("{} > {}", AGE_DAYS_SQL, stale_days_sql()));
+        }
+
+        (format!("WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+fn parse_price(input: &str) -> Result<Option<f32>, ()> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    money::parse_amount(input)
+        .map(|value| Some(value as f32))
+        .map_err(|_| ())
+}
+
+fn parse_date(input: &str) -> Result<Option<NaiveDate>, ()> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(input, "%d/%m/%Y"))
+        .map(Some)
+        .map_err(|_| ())
+}
+
+pub fn query_items(conn: &Connection, filter: &ItemFilter) -> rusqlite::Result<Vec<InfraItem>> {
+    let (where_clause, params) = filter.where_clause();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS} FROM infra_item {where_clause} ORDER BY id DESC"
+    ))?;
+    let items = stmt.query_map(rusqlite::params_from_iter(params), item_from_row)?;
+    items.collect()
+}
+
+pub fn distinct_values(conn: &Connection, column: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT {column} FROM infra_item
+         WHERE {column} <> '' AND deleted_at IS NULL
+         ORDER BY {column} COLLATE NOCASE"
+    ))?;
+    let values = stmt.query_map([], |row| row.get(0))?;
+    values.collect()
+}
+
+/// Botão com menu de caixas de seleção para escolher vários valores.
+pub fn multi_select_menu(ui: &mut egui::Ui, options: &[String], selected: &mut Vec<String>) {
+    let text = if selected.is_empty() {
+        "Todos".to_string()
+    } else {
+        selected.join(", ")
+    };
+    ui.menu_button(text, |ui| {
+        if options.is_empty() {
+            ui.label("Nenhum valor cadastrado");
+        }
+        if !selected.is_empty() && ui.button("Limpar seleção").clicked() {
+            selected.clear();
+        }
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for option in options {
+                    let mut checked = selected.contains(option);
+                    if ui.checkbox(&mut checked, option).changed() {
+                        if checked {
+                            selected.push(option.clone());
+                        } else {
+                            selected.retain(|s| s != option);
+                        }
+                    }
+                }
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{ItemFilter, query_items};
+
+    fn catalog_with(descriptions: &[&str]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        for (i, description) in descriptions.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
+                VALUES (?1, 'Marca', 'Fornecedor', ?2, '2024-01-01')",
+                (description, 10.0 + i as f64),
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn search(conn: &Connection, query: &str) -> Vec<String> {
+        let filter = ItemFilter {
+            query: query.to_string(),
+            ..Default::default()
+        };
+        let mut found: Vec<String> = query_items(conn, &filter)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.description)
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn search_ignores_case_and_accents() {
+        let conn = catalog_with(&["Quadro elétrico", "Conduíte", "Cabo"]);
+        assert_eq!(search(&conn, "ELÉTRICO"), ["Quadro elétrico"]);
+        assert_eq!(search(&conn, "eletrico"), ["Quadro elétrico"]);
+        assert_eq!(search(&conn, "CONDUITE"), ["Conduíte"]);
+    }
+
+    #[test]
+    fn search_treats_wildcards_literally() {
+        let conn = catalog_with(&[
+            "Desconto 10%",
+            "Desconto 100",
+            "Fio_2",
+            "Fio 22",
+            r"Barra 1\2",
+        ]);
+        assert_eq!(search(&conn, "10%"), ["Desconto 10%"]);
+        assert_eq!(search(&conn, "Fio_"), ["Fio_2"]);
+        assert_eq!(search(&conn, r"1\2"), [r"Barra 1\2"]);
+    }
+
+    #[test]
+    fn price_and_vendor_filters_combine() {
+        let conn = catalog_with(&["A", "B", "C"]);
+        let filter = ItemFilter {
+            min_price: "11".to_string(),
+            max_price: "11,00".to_string(),
+            vendors: vec!["Fornecedor".to_string()],
+            ..Default::default()
+        };
+        let found = query_items(&conn, &filter).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].description, "B");
+
+        let invalid = ItemFilter {
+            min_price: "abc".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(invalid.errors().len(), 1);
+        // O campo inválido é ignorado na consulta
+        assert_eq!(query_items(&conn, &invalid).unwrap().len(), 3);
+    }
+}
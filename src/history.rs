@@ -0,0 +1,63 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_history (
+            id INTEGER PRIMARY KEY,
+            item_id INTEGER NOT NULL REFERENCES infra_item(id) ON DELETE CASCADE,
+            price REAL NOT NULL,
+            recorded_at TEXT NOT NULL,
+            source TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Registra o preço de um item. `source` indica a origem da alteração
+/// ("cadastro", "edição", "importação", "cotação", ...).
+pub fn record_price(
+    conn: &Connection,
+    item_id: i32,
+    price: f32,
+    recorded_at: &str,
+    source: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO price_history (item_id, price, recorded_at, source)
+        VALUES (?1, ?2, ?3, ?4)",
+        (item_id, price, recorded_at, source),
+    )?;
+    Ok(())
+}
+
+/// Um preço registrado no histórico do item.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PriceRecord {
+    pub price: f32,
+    pub recorded_at: String,
+    pub source: String,
+}
+
+/// Histórico de preços do item, do mais antigo ao mais recente.
+pub fn load_history(conn: &Connection, item_id: i32) -> rusqlite::Result<Vec<PriceRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT price, recorded_at, source FROM price_history WHERE item_id = ?1 ORDER BY id",
+    )?;
+    let records = stmt.query_map([item_id], |row| {
+        Ok(PriceRecord {
+            price: row.get(0)?,
+            recorded_at: row.get(1)?,
+            source: row.get(2)?,
+        })
+    })?;
+    records.collect()
+}
+
+/// Janela "Histórico de preços" do menu da lista.
+#[derive(Default)]
+pub struct HistoryView {
+    pub item: Option<crate::InfraItem>,
+    pub records: Vec<PriceRecord>,
+}
@@ -0,0 +1,197 @@
+use std::io::Read;
+
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{find_item_id, history, money};
+
+/// Linha lida de um CSV ou colada de uma planilha, nas colunas do
+/// "Exportar CSV": descrição, marca, fornecedor, preço e data.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImportRow {
+    /// Número da linha no arquivo (ou no texto colado), para as mensagens.
+    pub line: usize,
+    pub description: String,
+    pub brand: String,
+    pub vendor: String,
+    pub price: f32,
+    pub updated_at: String,
+}
+
+/// Linhas válidas e a mensagem de cada linha recusada.
+#[derive(Default)]
+pub struct ParsedRows {
+    pub rows: Vec<ImportRow>,
+    pub errors: Vec<String>,
+}
+
+/// Lê as linhas com o separador `delimiter`. `has_headers` pula a primeira
+/// linha; linhas totalmente em branco são ignoradas.
+pub fn parse_rows(
+    reader: impl Read,
+    delimiter: u8,
+    has_headers: bool,
+) -> Result<ParsedRows, csv::Error> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(has_headers)
+        .flexible(true)
+        .from_reader(reader);
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let first_line = if has_headers { 2 } else { 1 };
+
+    let mut parsed = ParsedRows::default();
+    for (index, result) in rdr.records().enumerate() {
+        let record = result?;
+        let line = record
+            .position()
+            .map_or(index + first_line, |position| position.line() as usize);
+        let cell = |i: usize| record.get(i).unwrap_or("").trim().to_string();
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let (description, vendor) = (cell(0), cell(2));
+        if description.is_empty() || vendor.is_empty() {
+            parsed.errors.push(format!(
+                "Linha {}: descrição e fornecedor são obrigatórios",
+                line
+            ));
+            continue;
+        }
+        let price = match money::parse_price(&cell(3)) {
+            Ok(p) => p,
+            Err(e) => {
+                parsed.errors.push(format!(
+                    "Preço inválido na linha {}: '{}' ({})",
+                    line,
+                    cell(3),
+                    e
+                ));
+                continue;
+            }
+        };
+        let updated_at = match cell(4) {
+            date if date.is_empty() => today.clone(),
+            date => date,
+        };
+        parsed.rows.push(ImportRow {
+            line,
+            description,
+            brand: cell(1),
+            vendor,
+            price,
+            updated_at,
+        });
+    }
+    Ok(parsed)
+}
+
+/// Texto copiado de uma planilha (células separadas por tabulação). A
+/// primeira linha é pulada quando é um cabeçalho, isto é, quando a coluna de
+/// preço não tem número.
+pub fn parse_clipboard(text: &str) -> Result<ParsedRows, csv::Error> {
+    let first_line = text.lines().find(|line| !line.trim().is_empty());
+    let has_headers = first_line.is_some_and(|line| {
+        let price = line.split('\t').nth(3).unwrap_or("").trim();
+        money::parse_amount(price).is_err()
+    });
+    parse_rows(text.as_bytes(), b'\t', has_headers)
+}
+
+/// Preço atual do item com a mesma descrição, marca e fornecedor, se houver
+/// (mesmo na lixeira: a importação o restaura).
+pub fn current_price(conn: &Connection, row: &ImportRow) -> rusqlite::Result<Option<f32>> {
+    conn.query_row(
+        "SELECT price FROM infra_item WHERE description = ?1 AND brand = ?2 AND vendor = ?3",
+        (&row.description, &row.brand, &row.vendor),
+        |r| r.get(0),
+    )
+    .optional()
+}
+
+/// Inclui os itens novos e atualiza o preço dos existentes, registrando o
+/// histórico com a origem `source`. Devolve quantas linhas foram gravadas.
+pub fn apply_rows(conn: &Connection, rows: &[ImportRow], source: &str) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(description, brand, vendor) DO UPDATE SET
+            price = excluded.price,
+            updated_at = excluded.updated_at,
+            deleted_at = NULL,
+            deleted_by = NULL",
+    )?;
+    for row in rows {
+        stmt.execute((
+            &row.description,
+            &row.brand,
+            &row.vendor,
+            row.price,
+            &row.updated_at,
+        ))?;
+        if let Some(id) = find_item_id(conn, &row.description, &row.brand, &row.vendor)? {
+            history::record_price(conn, id, row.price, &row.updated_at, source)?;
+        }
+    }
+    Ok(rows.len())
+}
+
+/// Janela "Colar do Excel": o texto colado e a prévia do que será gravado.
+#[derive(Default)]
+pub struct PasteView {
+    pub open: bool,
+    pub text: String,
+    pub parsed: ParsedRows,
+    /// Preço atual de cada linha válida (`None` para item novo).
+    pub current_prices: Vec<Option<f32>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{apply_rows, current_price, parse_clipboard};
+
+    #[test]
+    fn pasted_rows_skip_header_and_report_errors() {
+        let text = "Descrição\tMarca\tFornecedor\tPreço\n\
+                    Cabo 2,5mm\tSil\tEletro\t3,45\t2024-05-01\n\
+                    \n\
+                    Disjuntor 20A\t\tEletro\t12.9\n\
+                    Tomada\tTramontina\tEletro\tconsultar\n\
+                    \tSil\t\t1,00\n";
+        let parsed = parse_clipboard(text).unwrap();
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(parsed.rows[0].line, 2);
+        assert_eq!(parsed.rows[0].price, 3.45);
+        assert_eq!(parsed.rows[0].updated_at, "2024-05-01");
+        assert_eq!(parsed.rows[1].brand, "");
+        assert_eq!(parsed.rows[1].updated_at.len(), 10);
+        assert_eq!(parsed.errors.len(), 2);
+        assert!(parsed.errors[0].contains("linha 5"));
+    }
+
+    #[test]
+    fn rows_without_header_are_all_kept() {
+        let parsed = parse_clipboard("Cabo\t\tEletro\t3,45\nFita\t\tEletro\t9,90").unwrap();
+        assert_eq!(parsed.rows.len(), 2);
+    }
+
+    #[test]
+    fn applying_updates_existing_items() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let parsed = parse_clipboard("Cabo\t\tEletro\t3,45\t2024-01-01").unwrap();
+        assert_eq!(current_price(&conn, &parsed.rows[0]).unwrap(), None);
+        apply_rows(&conn, &parsed.rows, "colagem").unwrap();
+
+        let parsed = parse_clipboard("Cabo\t\tEletro\t3,99\t2024-02-01").unwrap();
+        assert_eq!(current_price(&conn, &parsed.rows[0]).unwrap(), Some(3.45));
+        apply_rows(&conn, &parsed.rows, "colagem").unwrap();
+        assert_eq!(current_price(&conn, &parsed.rows[0]).unwrap(), Some(3.99));
+        let history: i64 = conn
+            .query_row("SELECT COUNT(*) FROM price_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(history, 2);
+    }
+}
@@ -0,0 +1,186 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ITEM_COLUMNS, InfraItem, find_item_id,
+    history::{self, PriceRecord},
+    in_savepoint, item_from_row, tags,
+};
+
+/// Valores do formulário de cadastro e edição de um item.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemFields {
+    pub description: String,
+    pub brand: String,
+    pub vendor: String,
+    pub price: f32,
+    pub tags: Vec<String>,
+    pub category: String,
+    pub unit: String,
+}
+
+/// Resultado de uma edição que só grava se o item não mudou desde que foi lido.
+pub enum UpdateOutcome {
+    Updated,
+    /// Outra pessoa alterou o item; contém a versão atual.
+    Conflict(InfraItem),
+    /// O item foi excluído (ou movido para a lixeira) por outra pessoa.
+    Deleted,
+}
+
+/// Item com o histórico de preços, como é devolvido pela API.
+#[derive(Serialize, Deserialize)]
+pub struct ItemWithHistory {
+    #[serde(flatten)]
+    pub item: InfraItem,
+    pub history: Vec<PriceRecord>,
+}
+
+/// Itens fora da lixeira, mais recentes primeiro.
+pub fn load_items(conn: &Connection) -> rusqlite::Result<Vec<InfraItem>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS} FROM infra_item WHERE deleted_at IS NULL ORDER BY id DESC"
+    ))?;
+    let items = stmt.query_map([], item_from_row)?;
+    items.collect()
+}
+
+pub fn load_item(conn: &Connection, id: i32) -> rusqlite::Result<Option<InfraItem>> {
+    conn.query_row(
+        &format!("SELECT {ITEM_COLUMNS} FROM infra_item WHERE id = ?1 AND deleted_at IS NULL"),
+        [id],
+        item_from_row,
+    )
+    .optional()
+}
+
+pub fn with_history(conn: &Connection, item: InfraItem) -> rusqlite::Result<ItemWithHistory> {
+    let history = history::load_history(conn, item.id)?;
+    Ok(ItemWithHistory { item, history })
+}
+
+/// Inclui o item, ou atualiza o preço do que já tem a mesma descrição, marca
+/// e fornecedor (tirando-o da lixeira). Devolve o id do item gravado.
+pub fn upsert_item(conn: &Connection, fields: &ItemFields, now: &str) -> rusqlite::Result<i32> {
+    in_savepoint(conn, || {
+        // Categoria e unidade em branco não apagam as do item existente
+        conn.execute(
+            "INSERT INTO infra_item (description, brand, vendor, price, updated_at, category, unit)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT(description, brand, vendor) DO UPDATE SET
+            price = excluded.price,
+            updated_at = excluded.updated_at,
+            category = COALESCE(NULLIF(excluded.category, ''), category),
+            unit = COALESCE(NULLIF(excluded.unit, ''), unit),
+            deleted_at = NULL,
+            deleted_by = NULL",
+            (
+                &fields.description,
+                &fields.brand,
+                &fields.vendor,
+                fields.price,
+                now,
+                fields.category.trim(),
+                fields.unit.trim(),
+            ),
+        )?;
+        let id = find_item_id(conn, &fields.description, &fields.brand, &fields.vendor)?
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        history::record_price(conn, id, fields.price, now, "cadastro")?;
+        tags::set_item_tags(conn, id, &fields.tags)?;
+        Ok(id)
+    })
+}
+
+/// Grava a edição só se o item ainda está na versão `version`.
+pub fn update_item(
+    conn: &Connection,
+    id: i32,
+    version: i64,
+    fields: &ItemFields,
+    now: &str,
+) -> rusqlite::Result<UpdateOutcome> {
+    in_savepoint(conn, || {
+        let Some(current) = load_item(conn, id)? else {
+            return Ok(UpdateOutcome::Deleted);
+        };
+        if current.version != version {
+            return Ok(UpdateOutcome::Conflict(current));
+        }
+        let updated_rows = conn.execute(
+        "UPDATE infra_item SET description = ?1, brand = ?2, vendor = ?3, price = ?4, updated_at = ?5,
+            category = ?6, unit = ?7
+        WHERE id = ?8 AND version = ?9 AND deleted_at IS NULL",
+        (
+            &fields.description,
+            &fields.brand,
+            &fields.vendor,
+            fields.price,
+            now,
+            fields.category.trim(),
+            fields.unit.trim(),
+            id,
+            version,
+        ),
+    )?;
+        if updated_rows == 0 {
+            // Alterado entre a leitura e a gravação
+            return Ok(match load_item(conn, id)? {
+                Some(item) => UpdateOutcome::Conflict(item),
+                None => UpdateOutcome::Deleted,
+            });
+        }
+        if (fields.price - current.price).abs() > f32::EPSILON {
+            history::record_price(conn, id, fields.price, now, "edição")?;
+        }
+        tags::set_item_tags(conn, id, &fields.tags)?;
+        Ok(UpdateOutcome::Updated)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{ItemFields, UpdateOutcome, load_item, update_item, upsert_item};
+
+    fn fields(price: f32, tags: &[&str]) -> ItemFields {
+        ItemFields {
+            description: "Cabo 2,5mm".to_string(),
+            brand: "Sil".to_string(),
+            vendor: "Eletro".to_string(),
+            price,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            category: String::new(),
+            unit: String::new(),
+        }
+    }
+
+    #[test]
+    fn failed_write_leaves_nothing_behind() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let id = upsert_item(&conn, &fields(3.5, &[]), "2024-01-01").unwrap();
+        let version = load_item(&conn, id).unwrap().unwrap().version;
+        conn.execute_batch(
+            "CREATE TEMP TRIGGER fail_tag BEFORE INSERT ON item_tag
+            BEGIN SELECT RAISE(ABORT, 'falha'); END;",
+        )
+        .unwrap();
+
+        assert!(update_item(&conn, id, version, &fields(4.0, &["obra"]), "2024-02-01").is_err());
+        assert!(upsert_item(&conn, &fields(5.0, &["obra"]), "2024-03-01").is_err());
+        let item = load_item(&conn, id).unwrap().unwrap();
+        assert_eq!((item.price, item.version), (3.5, version));
+        let history: i64 = conn
+            .query_row("SELECT COUNT(*) FROM price_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(history, 1);
+
+        conn.execute_batch("DROP TRIGGER fail_tag").unwrap();
+        assert!(matches!(
+            update_item(&conn, id, version, &fields(4.0, &["obra"]), "2024-02-01").unwrap(),
+            UpdateOutcome::Updated
+        ));
+    }
+}
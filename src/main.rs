@@ -4,14 +4,103 @@
 use std::{
     fs::File,
     io::{BufReader, Write},
+    path::{Path, PathBuf},
 };
 
 use chrono::Utc;
 use eframe::egui::{self, Button, TextEdit, vec2};
-use num_format::{Locale, ToFormattedString};
+use money::format_money;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use shortcuts::Command;
 
-fn init_db() -> rusqlite::Result<rusqlite::Connection> {
-    let conn = rusqlite::Connection::open("infra_items.db")?;
+mod api;
+mod audit;
+mod autocomplete;
+mod backup;
+mod batch;
+mod budget;
+mod bulk;
+mod catalog_json;
+mod clipboard;
+mod concurrency;
+mod duplicates;
+mod export;
+mod filters;
+mod history;
+mod import;
+mod items;
+mod money;
+mod remote;
+mod requote;
+mod server;
+mod settings;
+mod shortcuts;
+mod staleness;
+mod startup;
+mod sync;
+mod tags;
+mod trash;
+mod undo;
+mod validation;
+mod vendors;
+
+/// Quantidade de registros de auditoria carregados na janela.
+const AUDIT_ROWS_SHOWN: usize = 500;
+
+const DB_PATH: &str = "infra_items.db";
+
+/// Campos do formulário de cadastro; o Enter neles grava o item.
+const FORM_FIELDS: [&str; 7] = [
+    "description",
+    "brand",
+    "vendor",
+    "price",
+    "tags",
+    "category",
+    "unit",
+];
+
+fn form_field(name: &str) -> egui::Id {
+    egui::Id::new(("form", name))
+}
+
+const SEARCH_FIELD: &str = "search_query";
+
+/// Itens parecidos mostrados abaixo da descrição ao cadastrar.
+const SIMILAR_ITEMS_SHOWN: usize = 3;
+
+/// Intervalo entre as verificações de alterações feitas por outras instâncias.
+const EXTERNAL_CHANGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Versão do esquema gravada em `PRAGMA user_version`. Aumente ao incluir uma
+/// migração em `init_schema`, para que o banco seja copiado antes de migrar.
+const SCHEMA_VERSION: i64 = 7;
+
+fn init_db(path: &Path) -> Result<rusqlite::Connection, Box<dyn std::error::Error>> {
+    let conn = rusqlite::Connection::open(path)?;
+    concurrency::configure(&conn, path)?;
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let has_items: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'infra_item')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_items && version < SCHEMA_VERSION {
+        backup::create_backup(
+            &conn,
+            &backup::backup_dir(path),
+            backup::BackupKind::BeforeMigration,
+        )?;
+    }
+    init_schema(&conn)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    Ok(conn)
+}
+
+fn init_schema(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    filters::register_functions(conn)?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS infra_item (
             id INTEGER PRIMARY KEY,
@@ -24,136 +113,561 @@ fn init_db() -> rusqlite::Result<rusqlite::Connection> {
         )",
         [],
     )?;
-    Ok(conn)
+    add_column_if_missing(conn, "infra_item", "category", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(conn, "infra_item", "unit", "TEXT NOT NULL DEFAULT ''")?;
+    trash::migrate(conn)?;
+    audit::init_table(conn)?;
+    settings::init_table(conn)?;
+    history::init_table(conn)?;
+    staleness::init_table(conn)?;
+    tags::init_tables(conn)?;
+    concurrency::migrate(conn)?;
+    sync::init_table(conn)?;
+    export::init_tables(conn)?;
+    clipboard::init_table(conn)?;
+    budget::init_table(conn)?;
+    Ok(())
+}
+
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    declaration: &str,
+) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {declaration}"),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Nome do usuário do sistema, usado para registrar quem fez cada alteração.
+fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "desconhecido".to_string())
+}
+
+fn find_item_id(
+    conn: &rusqlite::Connection,
+    description: &str,
+    brand: &str,
+    vendor: &str,
+) -> rusqlite::Result<Option<i32>> {
+    conn.query_row(
+        "SELECT id FROM infra_item WHERE description = ?1 AND brand = ?2 AND vendor = ?3",
+        (description, brand, vendor),
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Roda `f` num savepoint: se algum comando falhar, nada do que ele gravou
+/// fica. Funciona também dentro de uma transação já aberta.
+fn in_savepoint<T>(
+    conn: &rusqlite::Connection,
+    f: impl FnOnce() -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    conn.execute_batch("SAVEPOINT write")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE write")?;
+            Ok(value)
+        }
+        Err(e) => {
+            conn.execute_batch("ROLLBACK TO write; RELEASE write")?;
+            Err(e)
+        }
+    }
 }
 
-pub fn format_money(valor: f32) -> String {
-    let inteiro = valor.trunc() as u64;
-    let centavos = format!("{:.2}", valor.fract())[2..].to_string(); // pega só os dígitos após o ponto
-    format!("{},{}", inteiro.to_formatted_string(&Locale::de), centavos)
+const ITEM_COLUMNS: &str = "id, description, brand, vendor, price, updated_at,
+    COALESCE((SELECT group_concat(t.name, ',') FROM item_tag it
+              JOIN tag t ON t.id = it.tag_id
+              WHERE it.item_id = infra_item.id), ''),
+    version, category, unit";
+
+fn item_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<InfraItem> {
+    Ok(InfraItem {
+        id: row.get(0)?,
+        description: row.get(1)?,
+        brand: row.get(2)?,
+        vendor: row.get(3)?,
+        price: row.get(4)?,
+        updated_at: row.get(5)?,
+        tags: tags::parse_tags(&row.get::<_, String>(6)?),
+        version: row.get(7)?,
+        category: row.get(8)?,
+        unit: row.get(9)?,
+    })
 }
 
+/// `catalogo serve [--db ARQUIVO] [--addr ENDEREÇO:PORTA]` roda o servidor do
+/// catálogo; `catalogo --server http://ENDEREÇO:PORTA` abre o aplicativo como
+/// cliente dele. Sem argumentos, abre `infra_items.db` direto.
 fn main() -> eframe::Result<()> {
     env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let source = match args.first().map(String::as_str) {
+        Some("serve") => {
+            if let Err(e) = server::serve(&args[1..]) {
+                eprintln!("Erro ao iniciar o servidor: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("--server") => match args.get(1) {
+            Some(url) => CatalogSource::Server(url.clone()),
+            None => {
+                eprintln!("--server precisa do endereço do servidor");
+                std::process::exit(2);
+            }
+        },
+        _ => CatalogSource::File(PathBuf::from(DB_PATH)),
+    };
     let options = eframe::NativeOptions::default();
 
     eframe::run_native(
         "Catálogo Elétrico de Preços",
         options,
         Box::new(|cc| {
-            let app = MyApp::new(cc);
-            Ok(Box::new(app))
+            let mut visuals = egui::Visuals::light(); // or .dark()
+            visuals.selection.bg_fill = egui::Color32::from_rgb(255, 212, 128);
+            visuals.selection.stroke = egui::Stroke::new(1.0, egui::Color32::WHITE);
+            cc.egui_ctx.set_visuals(visuals);
+
+            Ok(Box::new(match source {
+                CatalogSource::File(path) => CatalogApp::open(path),
+                CatalogSource::Server(url) => CatalogApp::connect(url, &cc.egui_ctx),
+            }))
         }),
     )
 }
 
+enum CatalogSource {
+    File(PathBuf),
+    Server(String),
+}
+
+/// Tela exibida: o catálogo, ou a tela de erro quando o banco não pôde ser
+/// aberto ou o servidor não respondeu.
+enum CatalogApp {
+    Catalog(Box<MyApp>),
+    StartupError(startup::StartupScreen),
+    ServerUnavailable { url: String, details: String },
+}
+
+impl CatalogApp {
+    fn open(db_path: PathBuf) -> Self {
+        match MyApp::new(db_path.clone()) {
+            Ok(app) => CatalogApp::Catalog(Box::new(app)),
+            Err(e) => CatalogApp::StartupError(startup::StartupScreen::new(db_path, e.as_ref())),
+        }
+    }
+
+    fn connect(url: String, ctx: &egui::Context) -> Self {
+        match MyApp::connect(&url, ctx) {
+            Ok(app) => CatalogApp::Catalog(Box::new(app)),
+            Err(e) => CatalogApp::ServerUnavailable {
+                url,
+                details: e.to_string(),
+            },
+        }
+    }
+}
+
+impl eframe::App for CatalogApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        match self {
+            CatalogApp::Catalog(app) => app.update(ctx, frame),
+            CatalogApp::StartupError(screen) => {
+                if let Some(path) = screen.show(ctx) {
+                    *self = CatalogApp::open(path);
+                }
+            }
+            CatalogApp::ServerUnavailable { url, details } => {
+                if remote::show_connection_error(ctx, url, details) {
+                    *self = CatalogApp::connect(url.clone(), ctx);
+                }
+            }
+        }
+    }
+}
+
 struct MyApp {
+    /// No modo cliente fica vazia, em memória: o catálogo está no servidor, e
+    /// as janelas que dependem do banco local (lixeira, backups, ...) ficam ocultas.
     conn: rusqlite::Connection,
+    db_path: PathBuf,
+    remote: Option<remote::RemoteSession>,
     selected_item_id: Option<i32>,
+    selected_ids: Vec<i32>,
+    selection_anchor: Option<usize>,
     items: Vec<InfraItem>,
     visible_items: Vec<InfraItem>,
     new_description: String,
     new_brand: String,
     new_vendor: String,
     new_price: String,
+    new_tags: String,
+    new_category: String,
+    new_unit: String,
     status_message: Option<String>,
     status_message_timer: Option<std::time::Instant>,
     // copied_feedback_timer: Option<std::time::Instant>,
-    search_query: String,
-    last_search_query: String,
-    show_outdated: bool,
+    filter: filters::ItemFilter,
+    last_filter: Option<filters::ItemFilter>,
     confirm_delete: bool,
+    confirm_json_import: Option<PathBuf>,
+    all_tags: Vec<String>,
+    all_vendors: Vec<String>,
+    all_categories: Vec<String>,
+    all_brands: Vec<String>,
+    /// Fornecedores e marcas sugeridos no formulário, dos mais usados aos menos.
+    ranked_vendors: Vec<String>,
+    ranked_brands: Vec<String>,
+    completion: autocomplete::Completion,
+    staleness: staleness::StalenessConfig,
+    show_staleness_settings: bool,
+    new_vendor_stale_days: (String, i64),
+    new_category_stale_days: (String, i64),
+    show_requote: bool,
+    requote_groups: Vec<requote::VendorQuote>,
+    bulk: bulk::BulkAdjustState,
+    batch_edit: batch::BatchEdit,
+    undo_stack: undo::UndoStack,
+    trash: trash::TrashView,
+    audit: audit::AuditView,
+    backups: backup::BackupView,
+    sync: sync::SyncView,
+    export: export::ExportView,
+    clipboard: clipboard::Clipboard,
+    copy_templates: Vec<clipboard::CopyTemplate>,
+    default_copy_template: String,
+    templates_view: clipboard::TemplatesView,
+    paste: import::PasteView,
+    history: history::HistoryView,
+    other_vendors: vendors::OtherVendorsView,
+    vendor_view: vendors::VendorView,
+    budget: budget::BudgetView,
+    duplicates: duplicates::DuplicatesView,
+    shortcuts: shortcuts::Shortcuts,
+    shortcuts_view: shortcuts::ShortcutsView,
+    palette: shortcuts::PaletteView,
+    /// Campo que recebe o foco no próximo quadro (Ctrl+F, Ctrl+N...).
+    focus_request: Option<egui::Id>,
+    /// Linha a trazer para a área visível depois de uma seta.
+    scroll_to_row: Option<usize>,
+    /// Linhas desenhadas no último quadro.
+    visible_rows: std::ops::Range<usize>,
+    api: api::ApiView,
+    /// API local ligada pelo usuário; para quando é descartada.
+    api_server: Option<server::CatalogServer>,
+    edit_conflict: Option<InfraItem>,
+    /// Edição que repetiria a descrição, marca e fornecedor de outro item.
+    key_conflict: Option<validation::KeyConflict>,
+    form_errors: validation::FormErrors,
+    data_version: i64,
+    last_change_check: std::time::Instant,
+    saved_filters: Vec<tags::SavedFilter>,
+    new_filter_name: String,
 }
 
 impl MyApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let mut visuals = egui::Visuals::light(); // or .dark()
-        visuals.selection.bg_fill = egui::Color32::from_rgb(255, 212, 128);
-        visuals.selection.stroke = egui::Stroke::new(1.0, egui::Color32::WHITE);
-        cc.egui_ctx.set_visuals(visuals);
-
-        let conn = init_db().unwrap();
-        let mut app = MyApp {
+    pub fn new(db_path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = init_db(&db_path)?;
+        let mut app = MyApp::with_connection(conn, db_path, None);
+        if let Err(e) = backup::run_scheduled(&app.conn, &app.backup_dir()) {
+            app.status_message = Some(format!("Erro ao fazer backup automático: {}", e));
+        }
+        let retention_days = trash::retention_days(&app.conn);
+        if let Err(e) = trash::purge_expired(&mut app.conn, retention_days) {
+            app.status_message = Some(format!("Erro ao esvaziar a lixeira: {}", e));
+        }
+        app.data_version = concurrency::data_version(&app.conn)?;
+        app.try_load_items()?;
+        app.saved_filters = tags::load_saved_filters(&app.conn)?;
+        app.staleness = staleness::load_config(&app.conn)?;
+        app.restart_api();
+        app.load_copy_templates();
+        app.shortcuts = shortcuts::load(&app.conn);
+        Ok(app)
+    }
+
+    /// Abre o catálogo de um servidor (`catalogo serve`). Leituras e gravações
+    /// passam pela API, e a lista é recarregada quando alguém grava.
+    pub fn connect(url: &str, ctx: &egui::Context) -> Result<Self, Box<dyn std::error::Error>> {
+        let session = remote::RemoteSession::connect(url, ctx)?;
+        let conn = rusqlite::Connection::open_in_memory()?;
+        init_schema(&conn)?;
+        let mut app = MyApp::with_connection(conn, PathBuf::new(), Some(session));
+        if let Some(remote) = &app.remote {
+            app.data_version = remote.revision();
+            app.staleness = remote.catalog.load_staleness()?;
+        }
+        app.try_load_items()?;
+        // Só os modelos padrão: os salvos ficam no catálogo do servidor
+        app.load_copy_templates();
+        Ok(app)
+    }
+
+    fn with_connection(
+        conn: rusqlite::Connection,
+        db_path: PathBuf,
+        remote: Option<remote::RemoteSession>,
+    ) -> Self {
+        MyApp {
             conn,
+            db_path,
+            remote,
             selected_item_id: None,
+            selected_ids: vec![],
+            selection_anchor: None,
             items: vec![],
             visible_items: vec![],
             new_description: String::new(),
             new_brand: String::new(),
             new_vendor: String::new(),
             new_price: String::new(),
+            new_tags: String::new(),
+            new_category: String::new(),
+            new_unit: String::new(),
             status_message: None,
             status_message_timer: None,
             // copied_feedback_timer: None,
-            search_query: String::new(),
-            last_search_query: String::new(),
-            show_outdated: false,
+            filter: filters::ItemFilter::default(),
+            last_filter: None,
             confirm_delete: false,
-        };
-        app.load_items();
-        app
+            confirm_json_import: None,
+            all_tags: vec![],
+            all_vendors: vec![],
+            all_categories: vec![],
+            all_brands: vec![],
+            ranked_vendors: vec![],
+            ranked_brands: vec![],
+            completion: autocomplete::Completion::default(),
+            staleness: staleness::StalenessConfig::default(),
+            show_staleness_settings: false,
+            new_vendor_stale_days: (String::new(), staleness::DEFAULT_STALE_DAYS),
+            new_category_stale_days: (String::new(), staleness::DEFAULT_STALE_DAYS),
+            show_requote: false,
+            requote_groups: vec![],
+            bulk: bulk::BulkAdjustState::default(),
+            batch_edit: batch::BatchEdit::default(),
+            undo_stack: undo::UndoStack::default(),
+            trash: trash::TrashView::default(),
+            audit: audit::AuditView::default(),
+            backups: backup::BackupView::default(),
+            sync: sync::SyncView::default(),
+            export: export::ExportView::default(),
+            clipboard: clipboard::Clipboard::default(),
+            copy_templates: vec![],
+            default_copy_template: String::new(),
+            templates_view: clipboard::TemplatesView::default(),
+            paste: import::PasteView::default(),
+            history: history::HistoryView::default(),
+            other_vendors: vendors::OtherVendorsView::default(),
+            vendor_view: vendors::VendorView::default(),
+            budget: budget::BudgetView::default(),
+            duplicates: duplicates::DuplicatesView::default(),
+            shortcuts: shortcuts::Shortcuts::default(),
+            shortcuts_view: shortcuts::ShortcutsView::default(),
+            palette: shortcuts::PaletteView::default(),
+            focus_request: None,
+            scroll_to_row: None,
+            visible_rows: 0..0,
+            api: api::ApiView::default(),
+            api_server: None,
+            edit_conflict: None,
+            key_conflict: None,
+            form_errors: validation::FormErrors::default(),
+            data_version: 0,
+            last_change_check: std::time::Instant::now(),
+            saved_filters: vec![],
+            new_filter_name: String::new(),
+        }
+    }
+
+    /// Recarrega a lista quando outra instância gravou no banco. Consultado a
+    /// cada poucos segundos, inclusive com a janela parada.
+    fn check_external_changes(&mut self, ctx: &egui::Context) {
+        // O servidor avisa das alterações e a janela é redesenhada na hora
+        if let Some(remote) = &self.remote {
+            let revision = remote.revision();
+            if revision != self.data_version {
+                self.data_version = revision;
+                self.load_items();
+            }
+            return;
+        }
+        if self.last_change_check.elapsed() >= EXTERNAL_CHANGE_INTERVAL {
+            self.last_change_check = std::time::Instant::now();
+            if let Ok(version) = concurrency::data_version(&self.conn)
+                && version != self.data_version
+            {
+                self.data_version = version;
+                self.load_items();
+                if self.trash.open {
+                    self.trash.items = trash::load_trash(&self.conn).unwrap_or_default();
+                }
+                if self.audit.open {
+                    self.reload_audit_entries();
+                }
+            }
+        }
+        ctx.request_repaint_after(EXTERNAL_CHANGE_INTERVAL);
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        backup::backup_dir(&self.db_path)
     }
 
+    /// Recarrega o catálogo; erros do banco aparecem na notificação.
     fn load_items(&mut self) {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT id, description, brand, vendor, price, updated_at FROM infra_item ORDER BY id DESC")
-            .unwrap();
-
-        let item_iter = stmt
-            .query_map([], |row| {
-                Ok(InfraItem {
-                    id: row.get(0)?,
-                    description: row.get(1)?,
-                    brand: row.get(2)?,
-                    vendor: row.get(3)?,
-                    price: row.get(4)?,
-                    updated_at: row.get(5)?,
-                })
-            })
-            .unwrap();
-        self.items = item_iter.filter_map(Result::ok).collect();
-        self.visible_items = self.items.clone();
-    }
-
-    pub fn load_outdated_items(&mut self) {
-        let mut stmt = self
-            .conn
-            .prepare(
-                "SELECT id, description, brand, vendor, price, updated_at
-                 FROM infra_item
-                 WHERE updated_at < DATE('now', '-1 month')",
-            )
-            .unwrap();
-
-        let item_iter = stmt
-            .query_map([], |row| {
-                Ok(InfraItem {
-                    id: row.get(0)?,
-                    description: row.get(1)?,
-                    brand: row.get(2)?,
-                    vendor: row.get(3)?,
-                    price: row.get(4)?,
-                    updated_at: row.get(5)?,
-                })
-            })
-            .unwrap();
+        if let Err(e) = self.try_load_items() {
+            self.status_message = Some(format!("Erro ao carregar o catálogo: {}", e));
+            self.status_message_timer = None;
+        }
+    }
+
+    fn try_load_items(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(remote) = &self.remote {
+            self.items = remote.catalog.load_items()?;
+            let facets = remote.catalog.load_facets()?;
+            self.all_tags = facets.tags;
+            self.all_vendors = facets.vendors;
+            self.all_categories = facets.categories;
+            self.all_brands = facets.brands;
+        } else {
+            self.items = items::load_items(&self.conn)?;
+            self.all_tags = tags::load_tag_names(&self.conn)?;
+            self.all_vendors = filters::distinct_values(&self.conn, "vendor")?;
+            self.all_categories = filters::distinct_values(&self.conn, "category")?;
+            self.all_brands = filters::distinct_values(&self.conn, "brand")?;
+        }
+        self.ranked_vendors = autocomplete::ranked_values(&self.items, |item| &item.vendor);
+        self.ranked_brands = autocomplete::ranked_values(&self.items, |item| &item.brand);
+        self.refresh_visible_items();
+        Ok(())
+    }
+
+    /// Executa o filtro atual no banco (ou no servidor) e atualiza `visible_items`.
+    fn refresh_visible_items(&mut self) {
+        let result = match &self.remote {
+            Some(remote) => remote.catalog.query_items(&self.filter),
+            None => filters::query_items(&self.conn, &self.filter).map_err(Into::into),
+        };
+        match result {
+            Ok(items) => {
+                self.visible_items = items;
+                // Itens que saíram da lista deixam de estar selecionados
+                let visible = &self.visible_items;
+                let editing = self.selected_item_id;
+                self.selected_ids
+                    .retain(|id| Some(*id) == editing || visible.iter().any(|item| item.id == *id));
+                self.selection_anchor = None;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao filtrar: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+        self.last_filter = Some(self.filter.clone());
+    }
+
+    fn apply_saved_filter(&mut self, saved: &tags::SavedFilter) {
+        if self.filter.outdated_only != saved.filter.outdated_only {
+            self.clear_selection();
+        }
+        self.filter = saved.filter.clone();
+        self.new_filter_name = saved.name.clone();
+        self.refresh_visible_items();
+    }
+
+    fn save_current_filter(&mut self) {
+        let name = self.new_filter_name.trim().to_string();
+        if name.is_empty() {
+            self.status_message = Some("Informe um nome para o filtro.".into());
+            self.status_message_timer = None;
+            return;
+        }
+        let saved = tags::SavedFilter {
+            name,
+            filter: self.filter.clone(),
+        };
+        match tags::save_filter(&self.conn, &saved) {
+            Ok(()) => {
+                self.saved_filters = tags::load_saved_filters(&self.conn).unwrap_or_default();
+                self.status_message = Some(format!("Filtro \"{}\" salvo.", saved.name));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao salvar filtro: {}", e));
+            }
+        }
+        self.status_message_timer = None;
+    }
 
-        self.items = item_iter.filter_map(Result::ok).collect();
-        self.visible_items = self.items.clone();
+    fn delete_current_filter(&mut self) {
+        let name = self.new_filter_name.trim().to_string();
+        match tags::delete_saved_filter(&self.conn, &name) {
+            Ok(true) => {
+                self.saved_filters = tags::load_saved_filters(&self.conn).unwrap_or_default();
+                self.new_filter_name.clear();
+                self.status_message = Some(format!("Filtro \"{}\" excluído.", name));
+            }
+            Ok(false) => {
+                self.status_message = Some("Nenhum filtro salvo com esse nome.".into());
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao excluir filtro: {}", e));
+            }
+        }
+        self.status_message_timer = None;
     }
 
-    fn insert_item(&mut self, description: &str, brand: &str, vendor: &str, price: f32) {
+    fn insert_item(
+        &mut self,
+        description: &str,
+        brand: &str,
+        vendor: &str,
+        price: f32,
+        item_tags: &[String],
+    ) {
         let now = Utc::now().format("%Y-%m-%d").to_string();
-        match self.conn.execute(
-            "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5)
-            ON CONFLICT(description, brand, vendor) DO UPDATE SET
-                price = excluded.price,
-                updated_at = excluded.updated_at",
-            (description, brand, vendor, price, now),
-        ) {
-            Ok(_) => {
+        let fields = items::ItemFields {
+            description: description.to_string(),
+            brand: brand.to_string(),
+            vendor: vendor.to_string(),
+            price,
+            tags: item_tags.to_vec(),
+            category: self.new_category.trim().to_string(),
+            unit: self.new_unit.trim().to_string(),
+        };
+        let result: Result<(), Box<dyn std::error::Error>> = match &self.remote {
+            Some(remote) => remote.catalog.insert_item(&fields).map(|_| ()),
+            None => find_item_id(&self.conn, description, brand, vendor)
+                .map_err(Into::into)
+                .and_then(|existing| {
+                    let ids: Vec<i32> = existing.into_iter().collect();
+                    self.change(
+                        &ids,
+                        |conn| items::upsert_item(conn, &fields, &now),
+                        |id| Some((format!("Inserir \"{}\"", description), vec![*id])),
+                    )
+                })
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => {
                 self.status_message = Some("Item inserido".to_string());
                 self.status_message_timer = None;
                 self.load_items();
@@ -163,218 +677,3556 @@ impl MyApp {
                 self.status_message_timer = None;
             }
         }
-        self.selected_item_id = None;
+        self.clear_selection();
     }
 
     fn update_item(&mut self) {
-        if let Some(id) = self.selected_item_id {
-            let now = Utc::now().format("%Y-%m-%d").to_string();
-            let parsed_price = self.new_price.replace(",", ".").parse::<f32>();
-
-            match parsed_price {
-                Ok(price) => {
-                    if let Some(original_item) = self.items.iter().find(|item| item.id == id) {
-                        // Verifica se houve alguma mudança
-                        let changed = self.new_description != original_item.description
-                            || self.new_brand != original_item.brand
-                            || self.new_vendor != original_item.vendor
-                            || (price - original_item.price).abs() > f32::EPSILON;
-
-                        if !changed {
-                            self.status_message = Some("Nenhuma alteração detectada.".to_owned());
-                            self.status_message_timer = None;
-                            return;
-                        }
+        let Some(id) = self.selected_item_id else {
+            self.status_message = Some("Nenhum item selecionado.".to_owned());
+            self.status_message_timer = None;
+            return;
+        };
+        let Some(price) = self.validate_form() else {
+            return;
+        };
+        let Some(original_item) = self.items.iter().find(|item| item.id == id) else {
+            self.status_message = Some(
+                "O item não existe mais; pode ter sido excluído por outra pessoa. \
+                 Suas alterações não foram salvas."
+                    .into(),
+            );
+            self.status_message_timer = None;
+            self.clear_selection();
+            self.load_items();
+            return;
+        };
+
+        let fields = items::ItemFields {
+            description: self.new_description.trim().to_string(),
+            brand: self.new_brand.trim().to_string(),
+            vendor: self.new_vendor.trim().to_string(),
+            price,
+            tags: tags::parse_tags(&self.new_tags),
+            category: self.new_category.trim().to_string(),
+            unit: self.new_unit.trim().to_string(),
+        };
+        // Verifica se houve alguma mudança
+        let changed = fields.description != original_item.description
+            || fields.brand != original_item.brand
+            || fields.vendor != original_item.vendor
+            || (price - original_item.price).abs() > f32::EPSILON
+            || fields.tags != original_item.tags
+            || fields.category != original_item.category
+            || fields.unit != original_item.unit;
+        if !changed {
+            self.status_message = Some("Nenhuma alteração detectada.".to_owned());
+            self.status_message_timer = None;
+            return;
+        }
 
-                        // Executa o update
-                        let result = self.conn.execute(
-                        "UPDATE infra_item SET description = ?1, brand = ?2, vendor = ?3, price = ?4, updated_at = ?5 WHERE id = ?6",
-                        (
-                            &self.new_description,
-                            &self.new_brand,
-                            &self.new_vendor,
-                            price,
-                            now,
-                            id,
-                        ),
+        // A descrição, a marca e o fornecedor não podem repetir os de outro item
+        if let Some(existing) = validation::key_conflict(&self.items, id, &fields) {
+            self.key_conflict = Some(validation::KeyConflict {
+                editing: id,
+                existing: existing.clone(),
+                fields,
+            });
+            return;
+        }
+        if self.remote.is_none() {
+            match find_item_id(
+                &self.conn,
+                &fields.description,
+                &fields.brand,
+                &fields.vendor,
+            ) {
+                Ok(Some(trashed)) if trashed != id => {
+                    self.form_errors.description = Some(
+                        "Já existe um item com esta descrição, marca e fornecedor na lixeira"
+                            .to_string(),
                     );
+                    self.focus_request = Some(form_field("description"));
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.status_message = Some(format!("Erro ao atualizar:\n{}", e));
+                    self.status_message_timer = None;
+                    return;
+                }
+            }
+        }
 
-                        match result {
-                            Ok(updated_rows) => {
-                                if updated_rows == 1 {
-                                    self.status_message = Some("Item atualizado.".to_owned());
-                                    self.status_message_timer = None;
-                                    self.load_items();
-                                    self.new_description.clear();
-                                    self.new_brand.clear();
-                                    self.new_vendor.clear();
-                                    self.new_price.clear();
-                                    self.selected_item_id = None;
-                                } else {
-                                    self.status_message =
-                                        Some("Nenhum item foi atualizado.".to_owned());
-                                    self.status_message_timer = None;
-                                }
-                            }
-                            Err(e) => {
-                                self.status_message = Some(format!("Erro ao atualizar:\n{}", e));
-                                self.status_message_timer = None;
-                            }
+        // Só grava se ninguém alterou o item desde que foi carregado
+        let version = original_item.version;
+        let result: Result<items::UpdateOutcome, Box<dyn std::error::Error>> = match &self.remote {
+            Some(remote) => remote.catalog.update_item(id, version, &fields),
+            None => {
+                let now = Utc::now().format("%Y-%m-%d").to_string();
+                self.change(
+                    &[id],
+                    |conn| items::update_item(conn, id, version, &fields, &now),
+                    |outcome| {
+                        matches!(outcome, items::UpdateOutcome::Updated)
+                            .then(|| (format!("Editar \"{}\"", fields.description), vec![]))
+                    },
+                )
+            }
+        };
+
+        match result {
+            Ok(items::UpdateOutcome::Updated) => {
+                self.status_message = Some("Item atualizado.".to_owned());
+                self.status_message_timer = None;
+                self.load_items();
+                self.new_description.clear();
+                self.new_brand.clear();
+                self.new_vendor.clear();
+                self.new_price.clear();
+                self.new_tags.clear();
+                self.new_category.clear();
+                self.new_unit.clear();
+                self.clear_selection();
+            }
+            // O item mudou desde que foi carregado: abre o diálogo de conflito
+            Ok(items::UpdateOutcome::Conflict(current)) => self.edit_conflict = Some(current),
+            Ok(items::UpdateOutcome::Deleted) => {
+                self.status_message = Some(
+                    "O item foi excluído por outra pessoa. Suas alterações não foram salvas."
+                        .into(),
+                );
+                self.status_message_timer = None;
+                self.clear_selection();
+                self.load_items();
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao atualizar:\n{}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_edit_conflict_window(&mut self, ctx: &egui::Context) {
+        let Some(current) = self.edit_conflict.clone() else {
+            return;
+        };
+        let mut keep_mine = false;
+        let mut use_current = false;
+        let mut cancel = false;
+        egui::Window::new("Conflito de edição")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Outra pessoa alterou este item enquanto você editava.");
+                ui.add_space(4.0);
+                egui::Grid::new("edit_conflict_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        ui.strong("Campo");
+                        ui.strong("Sua versão");
+                        ui.strong("Versão atual");
+                        ui.end_row();
+                        let rows = [
+                            (
+                                "Descrição",
+                                self.new_description.clone(),
+                                current.description.clone(),
+                            ),
+                            ("Marca", self.new_brand.clone(), current.brand.clone()),
+                            (
+                                "Fornecedor",
+                                self.new_vendor.clone(),
+                                current.vendor.clone(),
+                            ),
+                            ("Preço", self.new_price.clone(), format_money(current.price)),
+                            ("Tags", self.new_tags.clone(), current.tags.join(", ")),
+                            (
+                                "Categoria",
+                                self.new_category.clone(),
+                                current.category.clone(),
+                            ),
+                            ("Unidade", self.new_unit.clone(), current.unit.clone()),
+                        ];
+                        for (field, mine, theirs) in rows {
+                            ui.label(field);
+                            ui.label(mine);
+                            ui.label(theirs);
+                            ui.end_row();
                         }
+                    });
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    keep_mine = ui.button("Salvar a minha versão").clicked();
+                    use_current = ui.button("Usar a versão atual").clicked();
+                    cancel = ui.button("Cancelar").clicked();
+                });
+            });
+
+        if keep_mine || use_current || cancel {
+            self.edit_conflict = None;
+            self.load_items();
+        }
+        if keep_mine {
+            // A comparação passa a ser com a versão atual
+            self.update_item();
+        } else if use_current {
+            self.new_description = current.description.clone();
+            self.new_brand = current.brand.clone();
+            self.new_vendor = current.vendor.clone();
+            self.new_price = format_money(current.price);
+            self.new_tags = current.tags.join(", ");
+            self.new_category = current.category.clone();
+            self.new_unit = current.unit.clone();
+        }
+    }
+
+    fn show_key_conflict_window(&mut self, ctx: &egui::Context) {
+        let Some(conflict) = &self.key_conflict else {
+            return;
+        };
+        let existing = &conflict.existing;
+        let local = self.remote.is_none();
+        let mut merge = false;
+        let mut keep_editing = false;
+        egui::Window::new("Item já cadastrado")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Já existe \"{}\" de {} em {} por R$ {}.",
+                    existing.description,
+                    if existing.brand.is_empty() {
+                        "marca não informada"
+                    } else {
+                        &existing.brand
+                    },
+                    existing.vendor,
+                    format_money(existing.price)
+                ));
+                ui.label("Dois itens não podem ter a mesma descrição, marca e fornecedor.");
+                if local {
+                    ui.label(
+                        "Ao mesclar, o item existente recebe os dados do formulário, as tags, \
+                         o histórico de preços e as linhas do orçamento do item em edição, \
+                         que vai para a lixeira.",
+                    );
+                } else {
+                    ui.label("A mesclagem só está disponível no catálogo local.");
+                }
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if local {
+                        merge = ui.button("Mesclar no item existente").clicked();
+                    }
+                    keep_editing = ui.button("Continuar editando").clicked();
+                });
+            });
+
+        if merge && let Some(conflict) = self.key_conflict.take() {
+            self.merge_into_existing(conflict);
+        } else if keep_editing {
+            self.key_conflict = None;
+            self.form_errors.description =
+                Some("Já existe outro item com esta descrição, marca e fornecedor".to_string());
+            self.focus_request = Some(form_field("description"));
+        }
+    }
+
+    /// Grava o formulário no item existente e mescla nele o item em edição.
+    fn merge_into_existing(&mut self, conflict: validation::KeyConflict) {
+        let validation::KeyConflict {
+            editing,
+            existing,
+            mut fields,
+        } = conflict;
+        for tag in &existing.tags {
+            if !fields.tags.contains(tag) {
+                fields.tags.push(tag.clone());
+            }
+        }
+        let result = self.change(
+            &[existing.id, editing],
+            |conn| duplicates::merge_edit(conn, editing, &existing, &fields, &current_user()),
+            |outcome| {
+                matches!(outcome, items::UpdateOutcome::Updated)
+                    .then(|| (format!("Mesclar em \"{}\"", fields.description), vec![]))
+            },
+        );
+        match result {
+            Ok(items::UpdateOutcome::Updated) => {
+                self.status_message = Some("Item mesclado no já cadastrado.".to_string());
+                self.status_message_timer = None;
+                self.clear_selection();
+                self.clear_form();
+                self.load_items();
+            }
+            Ok(_) => {
+                self.status_message = Some(
+                    "O item existente foi alterado ou excluído por outra pessoa. Nada foi mesclado."
+                        .to_string(),
+                );
+                self.status_message_timer = None;
+                self.load_items();
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao mesclar: {}", e));
+                self.status_message_timer = None;
+                self.load_items();
+            }
+        }
+    }
+
+    fn delete_selected_items(&mut self) {
+        if self.selected_ids.is_empty() {
+            self.status_message = Some("Nenhum item selecionado para excluir.".to_string());
+            self.status_message_timer = None;
+            return;
+        }
+
+        let ids = self.selected_ids.clone();
+        let result: Result<usize, Box<dyn std::error::Error>> = match &self.remote {
+            Some(remote) => remote.catalog.delete_items(&ids),
+            None => self.change(
+                &ids,
+                |conn| trash::soft_delete(conn, &ids, &current_user()),
+                |affected| {
+                    (*affected > 0).then(|| (format!("Excluir {} item(ns)", affected), vec![]))
+                },
+            ),
+        };
+        match result {
+            Ok(0) => {
+                self.status_message = Some("Nenhum item foi excluído.".to_string());
+                self.status_message_timer = None;
+            }
+            Ok(affected) => {
+                self.status_message = Some(if affected == 1 {
+                    "Item movido para a lixeira.".to_string()
+                } else {
+                    format!("{} itens movidos para a lixeira.", affected)
+                });
+                self.status_message_timer = None;
+                self.clear_selection();
+                self.load_items(); // Refresh the list
+                self.clear_form();
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao excluir: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    /// Faz a alteração dos itens `ids` e a registra na auditoria numa só
+    /// transação, e a empilha no desfazer. `describe` dá, pelo resultado, o
+    /// nome da alteração e os ids dos itens incluídos por ela, ou `None`
+    /// quando nada mudou. Se o retrato dos itens falhar, nada é gravado.
+    fn change<T, E: Into<Box<dyn std::error::Error>>>(
+        &mut self,
+        ids: &[i32],
+        change: impl FnOnce(&mut rusqlite::Connection) -> Result<T, E>,
+        describe: impl FnOnce(&T) -> Option<(String, Vec<i32>)>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let mut before = undo::capture(&self.conn, ids)?;
+        self.in_change(|conn| {
+            let value = change(conn).map_err(Into::into)?;
+            let Some((label, new_ids)) = describe(&value) else {
+                return Ok((value, None));
+            };
+            for id in new_ids {
+                if !before.iter().any(|(b, _)| *b == id) {
+                    before.push((id, None));
+                }
+            }
+            let ids: Vec<i32> = before.iter().map(|(id, _)| *id).collect();
+            let after = undo::capture(conn, &ids)?;
+            audit::record_changes(conn, &label, &before, &after)?;
+            Ok((value, Some((label, before, after))))
+        })
+    }
+
+    /// Como `change`, para alterações que podem tocar qualquer item
+    /// (importações).
+    fn change_all<T, E: Into<Box<dyn std::error::Error>>>(
+        &mut self,
+        change: impl FnOnce(&mut rusqlite::Connection) -> Result<T, E>,
+        describe: impl FnOnce(&T) -> String,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let before = undo::capture_all(&self.conn)?;
+        self.in_change(|conn| {
+            let value = change(conn).map_err(Into::into)?;
+            let (before, after) = undo::diff(before, undo::capture_all(conn)?);
+            let label = describe(&value);
+            audit::record_changes(conn, &label, &before, &after)?;
+            Ok((value, Some((label, before, after))))
+        })
+    }
+
+    /// Roda `run` num savepoint e empilha no desfazer o que ele registrou;
+    /// se falhar, nada do que gravou fica.
+    fn in_change<T>(
+        &mut self,
+        run: impl FnOnce(
+            &mut rusqlite::Connection,
+        ) -> Result<
+            (T, Option<(String, undo::Snapshot, undo::Snapshot)>),
+            Box<dyn std::error::Error>,
+        >,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        self.conn.execute_batch("SAVEPOINT change")?;
+        let result = run(&mut self.conn).and_then(|recorded| {
+            self.conn.execute_batch("RELEASE change")?;
+            Ok(recorded)
+        });
+        match result {
+            Ok((value, recorded)) => {
+                if let Some((label, before, after)) = recorded {
+                    self.undo_stack.push(label, before, after);
+                    if self.audit.open {
+                        self.reload_audit_entries();
                     }
                 }
-                Err(_) => {
-                    self.status_message = Some(format!("Preço inválido ou vazio"));
-                    self.status_message_timer = None;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(rollback) = self
+                    .conn
+                    .execute_batch("ROLLBACK TO change; RELEASE change")
+                {
+                    log::error!("Falha ao desfazer a alteração incompleta: {}", rollback);
                 }
+                Err(e)
             }
-        } else {
-            self.status_message = Some("Nenhum item selecionado.".to_owned());
-            self.status_message_timer = None;
         }
     }
 
-    fn delete_selected_item(&mut self) {
-        if let Some(id) = self.selected_item_id {
-            let result = self
-                .conn
-                .execute("DELETE FROM infra_item WHERE id = ?1", [id]);
+    fn undo(&mut self) {
+        let result = self
+            .undo_stack
+            .undo(&mut self.conn, |conn, label, from, to| {
+                audit::record_changes(conn, &format!("Desfeito: {}", label), from, to)
+            });
+        self.after_undo_redo(result, "Desfeito");
+    }
+
+    fn redo(&mut self) {
+        let result = self
+            .undo_stack
+            .redo(&mut self.conn, |conn, label, from, to| {
+                audit::record_changes(conn, &format!("Refeito: {}", label), from, to)
+            });
+        self.after_undo_redo(result, "Refeito");
+    }
 
-            match result {
-                Ok(affected) if affected == 1 => {
-                    self.status_message = Some("Item excluído com sucesso.".to_string());
-                    self.status_message_timer = None;
-                    self.selected_item_id = None;
-                    self.load_items(); // Refresh the list
-                    self.new_description.clear();
-                    self.new_brand.clear();
-                    self.new_vendor.clear();
-                    self.new_price.clear();
-                }
-                Ok(_) => {
-                    self.status_message = Some("Nenhum item foi excluído.".to_string());
-                    self.status_message_timer = None;
+    fn after_undo_redo(&mut self, result: rusqlite::Result<Option<undo::Outcome>>, verb: &str) {
+        match result {
+            Ok(Some(undo::Outcome::Applied(applied))) => {
+                self.status_message = Some(format!("{}: {}", verb, applied.label));
+                if self.audit.open {
+                    self.reload_audit_entries();
                 }
-                Err(e) => {
-                    self.status_message = Some(format!("Erro ao excluir: {}", e));
-                    self.status_message_timer = None;
+                self.clear_selection();
+                self.clear_form();
+                self.load_items();
+                if self.trash.open {
+                    self.trash.items = trash::load_trash(&self.conn).unwrap_or_default();
                 }
             }
-        } else {
-            self.status_message = Some("Nenhum item selecionado para excluir.".to_string());
-            self.status_message_timer = None;
+            Ok(Some(undo::Outcome::Conflict { label, items })) => {
+                self.status_message = Some(format!(
+                    "\"{}\" não pode ser {}: alterado por outra pessoa depois disso ({}).",
+                    label,
+                    verb.to_lowercase(),
+                    items.join(", ")
+                ));
+                self.load_items();
+            }
+            Ok(None) => return,
+            Err(e) => self.status_message = Some(format!("Erro ao desfazer/refazer: {}", e)),
         }
+        self.status_message_timer = None;
     }
 
-    pub fn import_csv_to_db(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let file = File::open(path)?;
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .has_headers(true)
-            .from_reader(BufReader::new(file));
+    fn clear_selection(&mut self) {
+        self.selected_item_id = None;
+        self.selected_ids.clear();
+        self.selection_anchor = None;
+    }
 
-        let tx = self.conn.transaction()?;
+    fn selected_items(&self) -> Vec<InfraItem> {
+        self.items
+            .iter()
+            .filter(|item| self.selected_ids.contains(&item.id))
+            .cloned()
+            .collect()
+    }
+
+    /// Clique numa linha da lista: Ctrl alterna o item na seleção, Shift
+    /// seleciona o intervalo a partir do último clique e o clique simples
+    /// seleciona só o item (ou desmarca, se já era o único selecionado).
+    fn click_row(&mut self, row: usize, modifiers: egui::Modifiers) {
+        let Some(id) = self.visible_items.get(row).map(|item| item.id) else {
+            return;
+        };
 
+        if modifiers.shift
+            && let Some(anchor) = self.selection_anchor
         {
-            let mut stmt = tx.prepare(
-                "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
-                VALUES (?1, ?2, ?3, ?4, ?5)
-                ON CONFLICT(description, brand, vendor) DO UPDATE SET
-                    price = excluded.price,
-                    updated_at = excluded.updated_at",
-            )?;
-
-            for (index, result) in rdr.records().enumerate() {
-                let record = result?;
-                let description = record.get(0).unwrap_or("").trim();
-                let brand = record.get(1).unwrap_or("").trim();
-                let vendor = record.get(2).unwrap_or("").trim();
-                let price_str = record.get(3).unwrap_or("0").trim().replace(",", ".");
-                let updated_at = record.get(4).unwrap_or("").trim();
-                let price: f32 = match price_str.parse() {
-                    Ok(p) => p,
-                    Err(_) => {
+            let (start, end) = (anchor.min(row), anchor.max(row));
+            self.selected_ids = self.visible_items[start..=end.min(self.visible_items.len() - 1)]
+                .iter()
+                .map(|item| item.id)
+                .collect();
+        } else if modifiers.command {
+            if let Some(pos) = self.selected_ids.iter().position(|s| *s == id) {
+                self.selected_ids.remove(pos);
+            } else {
+                self.selected_ids.push(id);
+            }
+            self.selection_anchor = Some(row);
+        } else if self.selected_ids == [id] {
+            self.selected_ids.clear();
+            self.selection_anchor = None;
+        } else {
+            self.selected_ids = vec![id];
+            self.selection_anchor = Some(row);
+        }
+
+        self.sync_form_with_selection();
+    }
+
+    /// O formulário só edita quando há exatamente um item selecionado.
+    fn sync_form_with_selection(&mut self) {
+        if let [single] = self.selected_ids[..] {
+            if self.selected_item_id != Some(single)
+                && let Some(item) = self.items.iter().find(|item| item.id == single).cloned()
+            {
+                self.selected_item_id = Some(single); // select item
+                self.fill_form(&item);
+            }
+        } else if self.selected_item_id.is_some() {
+            self.selected_item_id = None; // unselect if clicked again
+            self.clear_form();
+        }
+    }
+
+    /// Seleciona só o item da linha e o carrega no formulário.
+    fn select_only(&mut self, row: usize) {
+        let Some(id) = self.visible_items.get(row).map(|item| item.id) else {
+            return;
+        };
+        self.selected_ids = vec![id];
+        self.selection_anchor = Some(row);
+        self.sync_form_with_selection();
+    }
+
+    fn clear_form(&mut self) {
+        self.new_description.clear();
+        self.new_brand.clear();
+        self.new_vendor.clear();
+        self.new_price.clear();
+        self.new_tags.clear();
+        self.new_category.clear();
+        self.new_unit.clear();
+        self.form_errors = validation::FormErrors::default();
+    }
+
+    /// Preenche o formulário com os dados do item para cadastrar outro parecido.
+    fn duplicate_into_form(&mut self, item: &InfraItem) {
+        self.clear_selection();
+        self.fill_form(item);
+        self.focus_request = Some(form_field("description"));
+        self.status_message = Some(
+            "Dados copiados para o formulário: altere o fornecedor, a marca ou a \
+             descrição e clique em Adicionar."
+                .into(),
+        );
+        self.status_message_timer = None;
+    }
+
+    /// Enter no formulário: atualiza o item em edição ou inclui um novo.
+    fn submit_form(&mut self) {
+        if self.selected_item_id.is_some() {
+            self.update_item();
+        } else {
+            self.add_from_form();
+        }
+    }
+
+    /// Confere o formulário e marca os erros ao lado dos campos; devolve o
+    /// preço quando está tudo certo.
+    fn validate_form(&mut self) -> Option<f32> {
+        match validation::validate(&self.new_description, &self.new_vendor, &self.new_price) {
+            Ok(price) => {
+                self.form_errors = validation::FormErrors::default();
+                Some(price)
+            }
+            Err(errors) => {
+                self.focus_request = errors.first_field().map(form_field);
+                self.form_errors = errors;
+                self.status_message = Some("Corrija os campos destacados no formulário.".into());
+                self.status_message_timer = None;
+                None
+            }
+        }
+    }
+
+    fn add_from_form(&mut self) {
+        let Some(price) = self.validate_form() else {
+            return;
+        };
+        let description = self.new_description.trim().to_string();
+        let brand = self.new_brand.trim().to_string();
+        let vendor = self.new_vendor.trim().to_string();
+        self.insert_item(
+            &description,
+            &brand,
+            &vendor,
+            price,
+            &tags::parse_tags(&self.new_tags),
+        );
+        self.clear_form();
+        self.focus_request = Some(form_field("description"));
+    }
+
+    /// Seleciona o item para edição, limpando os filtros se ele estiver oculto.
+    fn select_item_by_id(&mut self, id: i32) {
+        if !self.visible_items.iter().any(|item| item.id == id) {
+            self.filter.clear();
+            self.refresh_visible_items();
+        }
+        if let Some(row) = self.visible_items.iter().position(|item| item.id == id) {
+            self.select_only(row);
+            self.scroll_to_row = Some(row);
+        }
+    }
+
+    fn fill_form(&mut self, item: &InfraItem) {
+        self.new_description = item.description.clone();
+        self.new_brand = item.brand.clone();
+        self.new_vendor = item.vendor.clone();
+        self.new_price = format_money(item.price);
+        self.new_tags = item.tags.join(", ");
+        self.new_category = item.category.clone();
+        self.new_unit = item.unit.clone();
+        self.form_errors = validation::FormErrors::default();
+    }
+
+    /// Ação escolhida no menu do botão direito de uma linha. Copiar, excluir e
+    /// incluir no orçamento valem para toda a seleção quando a linha faz parte
+    /// de uma seleção múltipla.
+    fn apply_row_action(&mut self, ctx: &egui::Context, row: usize, action: RowAction) {
+        let Some(item) = self.visible_items.get(row).cloned() else {
+            return;
+        };
+        let in_selection = self.selected_ids.contains(&item.id) && self.selected_ids.len() > 1;
+        match action {
+            RowAction::Copy(template) => {
+                let items = if in_selection {
+                    self.selected_items()
+                } else {
+                    vec![item]
+                };
+                self.copy_items(ctx, &items, template.as_deref());
+            }
+            RowAction::CopyTemplates => self.open_templates_window(),
+            RowAction::Edit => self.select_only(row),
+            RowAction::Duplicate => self.duplicate_into_form(&item),
+            RowAction::Delete => {
+                if !in_selection {
+                    self.select_only(row);
+                }
+                self.confirm_delete = true;
+            }
+            RowAction::PriceHistory => self.open_history_window(item),
+            RowAction::OtherVendors => {
+                self.other_vendors = vendors::OtherVendorsView {
+                    others: vendors::other_vendors(&self.items, &item),
+                    item: Some(item),
+                };
+            }
+            RowAction::AddToBudget => {
+                let ids = if in_selection {
+                    self.selected_ids.clone()
+                } else {
+                    vec![item.id]
+                };
+                self.add_to_budget(&ids);
+            }
+            RowAction::VendorDetails => self.open_vendor_window(item.vendor),
+        }
+    }
+
+    fn open_history_window(&mut self, item: InfraItem) {
+        let records = match &self.remote {
+            Some(remote) => remote.catalog.load_history(item.id),
+            None => history::load_history(&self.conn, item.id).map_err(Into::into),
+        };
+        match records {
+            Ok(records) => {
+                self.history = history::HistoryView {
+                    item: Some(item),
+                    records,
+                }
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao carregar o histórico: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_history_window(&mut self, ctx: &egui::Context) {
+        let Some(item) = &self.history.item else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Histórico de preços")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(450.0)
+            .show(ctx, |ui| {
+                ui.strong(format!(
+                    "{} {} - {}",
+                    item.description, item.brand, item.vendor
+                ));
+                if self.history.records.is_empty() {
+                    ui.label("Nenhum preço registrado para este item.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("history_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                ui.strong("Data");
+                                ui.strong("Preço");
+                                ui.strong("Variação");
+                                ui.strong("Origem");
+                                ui.end_row();
+                                let mut previous: Option<f32> = None;
+                                for record in &self.history.records {
+                                    ui.label(&record.recorded_at);
+                                    ui.label(format!("R$ {}", format_money(record.price)));
+                                    ui.label(match previous {
+                                        Some(before) if before > 0.0 => format!(
+                                            "{:+.1}%",
+                                            (record.price / before - 1.0) * 100.0
+                                        )
+                                        .replace('.', ","),
+                                        _ => String::new(),
+                                    });
+                                    ui.label(&record.source);
+                                    ui.end_row();
+                                    previous = Some(record.price);
+                                }
+                            });
+                    });
+            });
+        if !open {
+            self.history = history::HistoryView::default();
+        }
+    }
+
+    fn show_other_vendors_window(&mut self, ctx: &egui::Context) {
+        let Some(item) = &self.other_vendors.item else {
+            return;
+        };
+        let mut open = true;
+        let mut go_to = None;
+        egui::Window::new("Outros fornecedores")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.strong(format!(
+                    "{}: R$ {} em {}",
+                    item.description,
+                    format_money(item.price),
+                    item.vendor
+                ));
+                if self.other_vendors.others.is_empty() {
+                    ui.label("Nenhum outro fornecedor tem este item cadastrado.");
+                    return;
+                }
+                egui::Grid::new("other_vendors_grid")
+                    .striped(true)
+                    .num_columns(6)
+                    .show(ui, |ui| {
+                        ui.strong("Fornecedor");
+                        ui.strong("Marca");
+                        ui.strong("Preço");
+                        ui.strong("Diferença");
+                        ui.strong("Atualizado em");
+                        ui.end_row();
+                        for other in &self.other_vendors.others {
+                            ui.label(&other.vendor);
+                            ui.label(&other.brand);
+                            ui.label(format!("R$ {}", format_money(other.price)));
+                            let difference = other.price - item.price;
+                            let difference = if difference < 0.0 {
+                                format!("- R$ {}", format_money(-difference))
+                            } else {
+                                format!("+ R$ {}", format_money(difference))
+                            };
+                            ui.label(difference);
+                            ui.label(&other.updated_at);
+                            if ui.button("Selecionar").clicked() {
+                                go_to = Some(other.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(id) = go_to {
+            self.select_item_by_id(id);
+        }
+        if !open {
+            self.other_vendors = vendors::OtherVendorsView::default();
+        }
+    }
+
+    fn open_vendor_window(&mut self, vendor: String) {
+        let contact = export::load_contacts(&self.conn)
+            .unwrap_or_default()
+            .remove(&vendor)
+            .unwrap_or_default();
+        self.vendor_view = vendors::VendorView {
+            vendor: Some(vendor),
+            contact,
+        };
+    }
+
+    fn show_vendor_window(&mut self, ctx: &egui::Context) {
+        let Some(vendor) = self.vendor_view.vendor.clone() else {
+            return;
+        };
+        let vendor_items: Vec<&InfraItem> = self
+            .items
+            .iter()
+            .filter(|item| item.vendor == vendor)
+            .collect();
+        let outdated = vendor_items
+            .iter()
+            .filter(|item| {
+                staleness::age_in_days(&item.updated_at).is_some_and(|days| {
+                    days > self.staleness.threshold_for(&item.vendor, &item.category)
+                })
+            })
+            .count();
+        let oldest = vendor_items.iter().map(|item| &item.updated_at).min();
+
+        let mut open = true;
+        let mut save = false;
+        let mut show_items = false;
+        egui::Window::new("Fornecedor")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.heading(&vendor);
+                egui::Grid::new("vendor_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Itens cadastrados:");
+                        ui.label(vendor_items.len().to_string());
+                        ui.end_row();
+                        ui.label("Desatualizados:");
+                        ui.label(outdated.to_string());
+                        ui.end_row();
+                        ui.label("Preço mais antigo:");
+                        ui.label(oldest.map_or("-", String::as_str));
+                        ui.end_row();
+                        ui.label("Validade dos preços:");
+                        ui.label(format!(
+                            "{} dias",
+                            self.staleness.threshold_for(&vendor, "")
+                        ));
+                        ui.end_row();
+                    });
+                ui.separator();
+                ui.label("Contato (e-mail, telefone, vendedor...):");
+                ui.add(
+                    TextEdit::multiline(&mut self.vendor_view.contact)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.horizontal(|ui| {
+                    save = ui.button("Salvar contato").clicked();
+                    show_items = ui.button("Mostrar só este fornecedor").clicked();
+                });
+            });
+
+        if save {
+            let contacts = std::collections::BTreeMap::from([(
+                vendor.clone(),
+                self.vendor_view.contact.clone(),
+            )]);
+            self.status_message = Some(match export::save_contacts(&mut self.conn, &contacts) {
+                Ok(()) => "Contato salvo.".into(),
+                Err(e) => format!("Erro ao salvar o contato: {}", e),
+            });
+            self.status_message_timer = None;
+        }
+        if show_items {
+            self.filter.clear();
+            self.filter.vendors = vec![vendor];
+            self.clear_selection();
+        }
+        if !open {
+            self.vendor_view = vendors::VendorView::default();
+        }
+    }
+
+    fn add_to_budget(&mut self, ids: &[i32]) {
+        let result = budget::add_items(&mut self.conn, ids).and_then(|()| {
+            self.budget.lines = budget::load_lines(&self.conn)?;
+            Ok(())
+        });
+        self.status_message = Some(match result {
+            Ok(()) => format!(
+                "{} item(ns) incluído(s) no orçamento ({} no total).",
+                ids.len(),
+                self.budget.lines.len()
+            ),
+            Err(e) => format!("Erro ao incluir no orçamento: {}", e),
+        });
+        self.status_message_timer = None;
+    }
+
+    fn search_duplicates(&mut self) {
+        let view = &mut self.duplicates;
+        view.clusters = duplicates::find_clusters(&self.items, view.scope, view.threshold);
+        view.selected = None;
+        view.kept = None;
+    }
+
+    fn show_duplicates_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut search = false;
+        let mut merge = false;
+        egui::Window::new("Itens duplicados")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(750.0)
+            .show(ctx, |ui| {
+                let view = &mut self.duplicates;
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("duplicates_scope")
+                        .selected_text(view.scope.label())
+                        .show_ui(ui, |ui| {
+                            for scope in duplicates::Scope::ALL {
+                                ui.selectable_value(&mut view.scope, scope, scope.label());
+                            }
+                        });
+                    ui.label("Semelhança mínima:");
+                    ui.add(
+                        egui::Slider::new(&mut view.threshold, 0.5..=1.0)
+                            .custom_formatter(|value, _| format!("{:.0}%", value * 100.0)),
+                    );
+                    search = ui.button("Procurar").clicked();
+                });
+                ui.label(
+                    "Compara as palavras das descrições, sem acentos, maiúsculas nem a \
+                     ordem (\"20A\" e \"20 A\" contam como iguais).",
+                );
+                ui.separator();
+
+                if view.clusters.is_empty() {
+                    ui.label("Nenhum grupo de itens parecidos.");
+                    return;
+                }
+                ui.columns(2, |columns| {
+                    egui::ScrollArea::vertical()
+                        .id_salt("duplicates_clusters")
+                        .max_height(400.0)
+                        .show(&mut columns[0], |ui| {
+                            for (index, cluster) in view.clusters.iter().enumerate() {
+                                let label = format!(
+                                    "{:.0}% · {} itens · {}",
+                                    cluster.score * 100.0,
+                                    cluster.items.len(),
+                                    cluster.items[0].description
+                                );
+                                if ui
+                                    .selectable_label(view.selected == Some(index), label)
+                                    .clicked()
+                                {
+                                    view.selected = Some(index);
+                                    // Sugere manter o de preço mais recente
+                                    view.kept = cluster
+                                        .items
+                                        .iter()
+                                        .max_by(|a, b| a.updated_at.cmp(&b.updated_at))
+                                        .map(|item| item.id);
+                                }
+                            }
+                        });
+
+                    let ui = &mut columns[1];
+                    let Some(cluster) = view.selected.and_then(|i| view.clusters.get(i)) else {
+                        ui.label("Escolha um grupo à esquerda.");
+                        return;
+                    };
+                    ui.label("Item que fica:");
+                    egui::Grid::new("duplicates_items")
+                        .striped(true)
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for item in &cluster.items {
+                                ui.radio_value(&mut view.kept, Some(item.id), "");
+                                ui.label(format!(
+                                    "{} [{}] - {} - R$ {} ({})",
+                                    item.description,
+                                    item.brand,
+                                    item.vendor,
+                                    format_money(item.price),
+                                    item.updated_at
+                                ));
+                                ui.end_row();
+                            }
+                        });
+                    ui.checkbox(
+                        &mut view.options.combine_history,
+                        "Juntar o histórico de preços no item que fica",
+                    );
+                    ui.checkbox(
+                        &mut view.options.repoint_budget,
+                        "Passar as linhas do orçamento para o item que fica",
+                    );
+                    merge = ui
+                        .add_enabled(
+                            view.kept.is_some(),
+                            Button::new(format!(
+                                "Mesclar {} itens no escolhido",
+                                cluster.items.len() - 1
+                            )),
+                        )
+                        .on_hover_text("Os outros itens vão para a lixeira, com as tags juntadas")
+                        .clicked();
+                });
+            });
+
+        if search {
+            self.search_duplicates();
+        }
+        if merge {
+            self.merge_selected_cluster();
+        }
+        if !open {
+            self.duplicates.open = false;
+        }
+    }
+
+    fn merge_selected_cluster(&mut self) {
+        let view = &self.duplicates;
+        let (Some(cluster), Some(kept)) =
+            (view.selected.and_then(|i| view.clusters.get(i)), view.kept)
+        else {
+            return;
+        };
+        let ids: Vec<i32> = cluster.items.iter().map(|item| item.id).collect();
+        let others: Vec<i32> = ids.iter().copied().filter(|id| *id != kept).collect();
+        let options = view.options;
+        let result = self.change(
+            &ids,
+            |conn| duplicates::merge(conn, kept, &others, options, &current_user()),
+            |()| Some((format!("Mesclar {} item(ns)", others.len()), vec![])),
+        );
+        match result {
+            Ok(()) => {
+                self.status_message = Some(format!(
+                    "{} item(ns) mesclado(s); os repetidos foram para a lixeira.",
+                    others.len()
+                ));
+                self.clear_selection();
+                self.load_items();
+                self.search_duplicates();
+            }
+            Err(e) => self.status_message = Some(format!("Erro ao mesclar: {}", e)),
+        }
+        self.status_message_timer = None;
+    }
+
+    fn open_budget_window(&mut self) {
+        match budget::load_lines(&self.conn) {
+            Ok(lines) => {
+                self.budget.lines = lines;
+                self.budget.open = true;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao abrir o orçamento: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_budget_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut changed_quantity = None;
+        let mut to_remove = None;
+        let mut copy = false;
+        let mut clear = false;
+        egui::Window::new("Orçamento")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                if self.budget.lines.is_empty() {
+                    ui.label("O orçamento está vazio. Inclua itens com o botão direito na lista.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("budget_grid")
+                            .striped(true)
+                            .num_columns(7)
+                            .show(ui, |ui| {
+                                ui.strong("Item");
+                                ui.strong("Fornecedor");
+                                ui.strong("Unid.");
+                                ui.strong("Preço");
+                                ui.strong("Quantidade");
+                                ui.strong("Subtotal");
+                                ui.end_row();
+                                for line in &mut self.budget.lines {
+                                    let item = &line.item;
+                                    let name = format!("{} {}", item.description, item.brand);
+                                    if line.deleted {
+                                        ui.label(
+                                            egui::RichText::new(format!("{} (excluído)", name))
+                                                .color(ui.visuals().warn_fg_color),
+                                        )
+                                        .on_hover_text("O item está na lixeira");
+                                    } else {
+                                        ui.label(name);
+                                    }
+                                    ui.label(&item.vendor);
+                                    ui.label(&item.unit);
+                                    ui.label(format!("R$ {}", format_money(item.price)));
+                                    let response = ui.add(
+                                        egui::DragValue::new(&mut line.quantity)
+                                            .range(0.0..=1_000_000.0)
+                                            .speed(1.0),
+                                    );
+                                    if response.changed() {
+                                        changed_quantity = Some((item.id, line.quantity));
+                                    }
+                                    ui.label(format!(
+                                        "R$ {}",
+                                        format_money(line.subtotal() as f32)
+                                    ));
+                                    if ui.button("Remover").clicked() {
+                                        to_remove = Some(item.id);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+                ui.strong(format!(
+                    "Total: R$ {}",
+                    format_money(budget::total(&self.budget.lines) as f32)
+                ));
+                ui.horizontal(|ui| {
+                    copy = ui.button("Copiar").clicked();
+                    clear = ui.button("Limpar orçamento").clicked();
+                });
+            });
+
+        let result = if let Some((id, quantity)) = changed_quantity {
+            budget::set_quantity(&self.conn, id, quantity)
+        } else if let Some(id) = to_remove {
+            budget::remove_item(&self.conn, id)
+        } else if clear {
+            budget::clear(&self.conn)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            self.status_message = Some(format!("Erro ao gravar o orçamento: {}", e));
+            self.status_message_timer = None;
+        }
+        if to_remove.is_some() || clear {
+            self.budget.lines = budget::load_lines(&self.conn).unwrap_or_default();
+        }
+        if copy {
+            let copied = clipboard::from_lines(budget::render_lines(&self.budget.lines));
+            self.clipboard.copy(ctx, copied);
+            self.status_message = Some("Orçamento copiado para a área de transferência".into());
+            self.status_message_timer = None;
+        }
+        if !open {
+            self.budget.open = false;
+        }
+    }
+
+    fn copy_selected_items(&mut self, ctx: &egui::Context) {
+        let items = self.selected_items();
+        self.copy_items(ctx, &items, None);
+    }
+
+    /// Copia os itens com o modelo `template_name` (ou o padrão), em texto e HTML.
+    fn copy_items(
+        &mut self,
+        ctx: &egui::Context,
+        items: &[InfraItem],
+        template_name: Option<&str>,
+    ) {
+        let name = template_name.unwrap_or(&self.default_copy_template);
+        let Some(template) = self
+            .copy_templates
+            .iter()
+            .find(|t| t.name == name)
+            .or(self.copy_templates.first())
+        else {
+            return;
+        };
+        let copied = clipboard::render_items(template, items);
+        self.clipboard.copy(ctx, copied);
+        self.status_message = Some(if items.len() == 1 {
+            "Copiado para a área de transferência".to_string()
+        } else {
+            format!(
+                "{} item(ns) copiado(s) para a área de transferência",
+                items.len()
+            )
+        });
+        self.status_message_timer = None;
+    }
+
+    fn load_copy_templates(&mut self) {
+        match clipboard::load_templates(&self.conn) {
+            Ok(templates) => self.copy_templates = templates,
+            Err(e) => log::error!("Falha ao carregar os modelos de cópia: {}", e),
+        }
+        self.default_copy_template = clipboard::default_template_name(&self.conn);
+    }
+
+    fn open_templates_window(&mut self) {
+        self.templates_view.templates = self.copy_templates.clone();
+        self.templates_view.default_name = self.default_copy_template.clone();
+        self.templates_view.open = true;
+    }
+
+    fn show_templates_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        let preview_item = self
+            .selected_items()
+            .into_iter()
+            .next()
+            .or_else(|| self.visible_items.first().cloned());
+        egui::Window::new("Modelos de cópia")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(650.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "O modelo padrão é usado em \"Copiar\" e \"Copiar selecionados\"; os demais \
+                     aparecem em \"Copiar como\", no clique com o botão direito. Cada item vira \
+                     uma linha; ao colar no Word ou no Excel, as tabulações viram colunas.",
+                );
+                egui::CollapsingHeader::new("Campos disponíveis").show(ui, |ui| {
+                    egui::Grid::new("copy_placeholders").show(ui, |ui| {
+                        for (placeholder, description) in clipboard::PLACEHOLDERS {
+                            ui.monospace(placeholder);
+                            ui.label(description);
+                            ui.end_row();
+                        }
+                    });
+                });
+                ui.separator();
+
+                let view = &mut self.templates_view;
+                let mut remove = None;
+                egui::Grid::new("copy_templates")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Padrão");
+                        ui.strong("Nome");
+                        ui.strong("Modelo");
+                        ui.end_row();
+                        for (index, template) in view.templates.iter_mut().enumerate() {
+                            ui.radio_value(&mut view.default_name, template.name.clone(), "");
+                            let was_default = view.default_name == template.name;
+                            if ui
+                                .add(TextEdit::singleline(&mut template.name).desired_width(150.0))
+                                .changed()
+                                && was_default
+                            {
+                                view.default_name = template.name.clone();
+                            }
+                            ui.add(
+                                TextEdit::singleline(&mut template.template)
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(350.0),
+                            );
+                            if ui.button("Excluir").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(index) = remove {
+                    view.templates.remove(index);
+                }
+                if ui.button("Novo modelo").clicked() {
+                    view.templates.push(clipboard::CopyTemplate {
+                        name: format!("Modelo {}", view.templates.len() + 1),
+                        template: r"{description}\t{brand}\t{vendor}\t{price}".to_string(),
+                    });
+                }
+
+                if let Some(item) = &preview_item
+                    && let Some(template) =
+                        view.templates.iter().find(|t| t.name == view.default_name)
+                {
+                    ui.separator();
+                    ui.label("Exemplo com o modelo padrão:");
+                    ui.monospace(template.render(item).replace('\t', " ⇥ "));
+                }
+
+                ui.separator();
+                let mut names: Vec<&str> = view.templates.iter().map(|t| t.name.trim()).collect();
+                let count = names.len();
+                names.sort();
+                names.dedup();
+                let error = if view.templates.is_empty() {
+                    Some("Crie pelo menos um modelo.")
+                } else if names.contains(&"") {
+                    Some("Dê um nome a cada modelo.")
+                } else if names.len() != count {
+                    Some("Há modelos com o mesmo nome.")
+                } else if !names.contains(&view.default_name.trim()) {
+                    Some("Escolha o modelo padrão.")
+                } else {
+                    None
+                };
+                if let Some(error) = error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                save = ui
+                    .add_enabled(error.is_none(), Button::new("Salvar"))
+                    .clicked();
+            });
+
+        if save {
+            let view = &self.templates_view;
+            match clipboard::save_templates(
+                &mut self.conn,
+                &view.templates,
+                view.default_name.trim(),
+            ) {
+                Ok(()) => {
+                    self.load_copy_templates();
+                    self.templates_view.open = false;
+                    self.status_message = Some("Modelos de cópia salvos.".into());
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Erro ao salvar os modelos: {}", e));
+                }
+            }
+            self.status_message_timer = None;
+        } else if !open {
+            self.templates_view.open = false;
+        }
+    }
+
+    fn show_batch_edit_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut apply = false;
+        egui::Window::new("Editar itens selecionados")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{} itens selecionados.", self.selected_ids.len()));
+                egui::Grid::new("frm_edicao_lote")
+                    .spacing([10.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.checkbox(&mut self.batch_edit.change_vendor, "Fornecedor:");
+                        ui.add_enabled(
+                            self.batch_edit.change_vendor,
+                            TextEdit::singleline(&mut self.batch_edit.vendor),
+                        );
+                        ui.end_row();
+
+                        ui.checkbox(&mut self.batch_edit.change_brand, "Marca:");
+                        ui.add_enabled(
+                            self.batch_edit.change_brand,
+                            TextEdit::singleline(&mut self.batch_edit.brand).hint_text("Sem Marca"),
+                        );
+                        ui.end_row();
+
+                        ui.checkbox(&mut self.batch_edit.change_category, "Categoria:");
+                        ui.add_enabled(
+                            self.batch_edit.change_category,
+                            TextEdit::singleline(&mut self.batch_edit.category)
+                                .hint_text("Sem categoria"),
+                        );
+                        ui.end_row();
+
+                        ui.checkbox(&mut self.batch_edit.change_unit, "Unidade:");
+                        ui.add_enabled(
+                            self.batch_edit.change_unit,
+                            TextEdit::singleline(&mut self.batch_edit.unit)
+                                .hint_text("Sem unidade"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Adicionar tags:");
+                        ui.add(
+                            TextEdit::singleline(&mut self.batch_edit.add_tags)
+                                .hint_text("Separadas por vírgula"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Remover tags:");
+                        ui.add(
+                            TextEdit::singleline(&mut self.batch_edit.remove_tags)
+                                .hint_text("Separadas por vírgula"),
+                        );
+                        ui.end_row();
+                    });
+
+                let vendor_missing =
+                    self.batch_edit.change_vendor && self.batch_edit.vendor.trim().is_empty();
+                if vendor_missing {
+                    ui.colored_label(egui::Color32::RED, "Informe o fornecedor.");
+                }
+                if ui
+                    .add_enabled(
+                        self.batch_edit.has_changes() && !vendor_missing,
+                        Button::new("Aplicar"),
+                    )
+                    .clicked()
+                {
+                    apply = true;
+                }
+            });
+
+        if apply {
+            let items = self.selected_items();
+            let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+            let edit = std::mem::take(&mut self.batch_edit);
+            let result = self.change(
+                &ids,
+                |conn| batch::apply_batch_edit(conn, &items, &edit),
+                |outcome| match outcome {
+                    batch::BatchOutcome::Updated(count) => {
+                        Some((format!("Editar {} itens em lote", count), vec![]))
+                    }
+                    _ => None,
+                },
+            );
+            match result {
+                Ok(batch::BatchOutcome::Updated(count)) => {
+                    self.status_message = Some(format!("{} itens atualizados.", count));
+                    self.load_items();
+                }
+                Ok(batch::BatchOutcome::Conflict(items)) => {
+                    self.batch_edit = edit;
+                    self.status_message = Some(format!(
+                        "Nenhum item foi alterado: {} foram alterados ou excluídos por outra \
+                         pessoa. Confira a lista atualizada e aplique de novo.",
+                        items.join(", ")
+                    ));
+                    self.load_items();
+                }
+                Ok(batch::BatchOutcome::Duplicate(description)) => {
+                    self.batch_edit = edit;
+                    self.status_message = Some(format!(
+                        "Nenhum item foi alterado: \"{}\" ficaria com a mesma descrição, marca \
+                         e fornecedor de outro item já cadastrado (ou na lixeira).",
+                        description
+                    ));
+                }
+                Err(e) => {
+                    self.batch_edit = edit;
+                    self.status_message = Some(format!(
+                        "Erro ao editar em lote (nenhum item alterado):\n{}",
+                        e
+                    ));
+                }
+            }
+            self.status_message_timer = None;
+        } else if !open {
+            self.batch_edit = batch::BatchEdit::default();
+        }
+    }
+
+    pub fn import_csv_to_db(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        let parsed = import::parse_rows(BufReader::new(file), b';', true)?;
+
+        self.backup_before_import()?;
+        let file_name = std::path::Path::new(path)
+            .file_name()
+            .map_or(path.to_string(), |n| n.to_string_lossy().to_string());
+        self.change_all(
+            |conn| import::apply_rows(conn, &parsed.rows, "importação"),
+            |_| format!("Importar {}", file_name),
+        )?;
+
+        self.load_items();
+        self.status_message = Some(if parsed.errors.is_empty() {
+            "CSV importado com sucesso.".to_string()
+        } else {
+            format!(
+                "CSV importado: {} linha(s) gravada(s), {} ignorada(s):\n{}",
+                parsed.rows.len(),
+                parsed.errors.len(),
+                parsed.errors.join("\n")
+            )
+        });
+        self.status_message_timer = None;
+
+        Ok(())
+    }
+
+    fn open_paste_window(&mut self) {
+        self.paste = import::PasteView {
+            open: true,
+            text: self.clipboard.paste_text().unwrap_or_default(),
+            ..Default::default()
+        };
+        self.refresh_paste_preview();
+    }
+
+    fn refresh_paste_preview(&mut self) {
+        let paste = &mut self.paste;
+        match import::parse_clipboard(&paste.text) {
+            Ok(parsed) => paste.parsed = parsed,
+            Err(e) => {
+                paste.parsed = import::ParsedRows {
+                    rows: vec![],
+                    errors: vec![format!("Não foi possível ler o texto colado: {}", e)],
+                }
+            }
+        }
+        paste.current_prices = paste
+            .parsed
+            .rows
+            .iter()
+            .map(|row| import::current_price(&self.conn, row).unwrap_or(None))
+            .collect();
+    }
+
+    fn apply_paste(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let rows = self.paste.parsed.rows.clone();
+        let count = self.change_all(
+            |conn| import::apply_rows(conn, &rows, "colagem"),
+            |count| format!("Colar {} item(ns) do Excel", count),
+        )?;
+        self.load_items();
+        Ok(count)
+    }
+
+    fn show_paste_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut reload = false;
+        let mut apply = false;
+        egui::Window::new("Colar do Excel")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Copie as linhas na planilha e cole abaixo (Ctrl+V). Colunas, na ordem: \
+                     descrição, marca, fornecedor, preço e data (opcional; em branco, hoje). \
+                     Itens com a mesma descrição, marca e fornecedor têm o preço atualizado.",
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Ler da área de transferência").clicked() {
+                        self.paste.text = self.clipboard.paste_text().unwrap_or_default();
+                        reload = true;
+                    }
+                    if ui.button("Limpar").clicked() {
+                        self.paste.text.clear();
+                        reload = true;
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .id_salt("paste_text")
+                    .max_height(120.0)
+                    .show(ui, |ui| {
+                        reload |= ui
+                            .add(
+                                TextEdit::multiline(&mut self.paste.text)
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(f32::INFINITY)
+                                    .desired_rows(4),
+                            )
+                            .changed();
+                    });
+
+                let paste = &self.paste;
+                if !paste.parsed.rows.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .id_salt("paste_preview")
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("paste_preview_grid")
+                                .striped(true)
+                                .num_columns(6)
+                                .show(ui, |ui| {
+                                    ui.strong("Linha");
+                                    ui.strong("Descrição");
+                                    ui.strong("Marca");
+                                    ui.strong("Fornecedor");
+                                    ui.strong("Preço");
+                                    ui.strong("Situação");
+                                    ui.end_row();
+                                    for (row, current) in
+                                        paste.parsed.rows.iter().zip(&paste.current_prices)
+                                    {
+                                        ui.label(row.line.to_string());
+                                        ui.label(&row.description);
+                                        ui.label(&row.brand);
+                                        ui.label(&row.vendor);
+                                        ui.label(format!("R$ {}", format_money(row.price)));
+                                        ui.label(match current {
+                                            None => "Novo".to_string(),
+                                            Some(price) if (price - row.price).abs() < 0.005 => {
+                                                "Mesmo preço".to_string()
+                                            }
+                                            Some(price) => {
+                                                format!(
+                                                    "Atualiza (era R$ {})",
+                                                    format_money(*price)
+                                                )
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+                if !paste.parsed.errors.is_empty() {
+                    ui.separator();
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!("{} linha(s) ignorada(s):", paste.parsed.errors.len()),
+                    );
+                    for error in paste.parsed.errors.iter().take(20) {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                }
+
+                ui.separator();
+                apply = ui
+                    .add_enabled(
+                        !paste.parsed.rows.is_empty(),
+                        Button::new(format!("Gravar {} item(ns)", paste.parsed.rows.len())),
+                    )
+                    .clicked();
+            });
+
+        if reload {
+            self.refresh_paste_preview();
+        }
+        if apply {
+            self.status_message = Some(match self.apply_paste() {
+                Ok(count) => {
+                    self.paste = import::PasteView::default();
+                    format!("{} item(ns) colado(s) do Excel.", count)
+                }
+                Err(e) => format!("Erro ao gravar (nenhum item alterado):\n{}", e),
+            });
+            self.status_message_timer = None;
+        } else if !open {
+            self.paste = import::PasteView::default();
+        }
+    }
+
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    pub fn export_to_csv(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(path)?;
+        file.write_all(b"\xEF\xBB\xBF")?;
+        let mut wtr = csv::WriterBuilder::new().delimiter(b';').from_writer(file);
+
+        // Cabeçalho
+        wtr.write_record(&[
+            "descrição",
+            "marca",
+            "fornecedor",
+            "preço",
+            "última atualização",
+        ])?;
+
+        // Escreve os itens
+        for item in &self.items {
+            let preco = format!("{:.2}", item.price).replace(".", ","); // BR style
+            wtr.write_record(&[
+                &item.description,
+                &item.brand,
+                &item.vendor,
+                &preco,
+                &item.updated_at,
+            ])?;
+        }
+
+        wtr.flush()?;
+        self.status_message = Some("Exportado com sucesso.".into());
+        self.status_message_timer = None;
+        Ok(())
+    }
+
+    /// Substitui o catálogo pelo conteúdo de um arquivo JSON ou JSON Lines.
+    fn import_json(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.backup_before_import()?;
+        let file_name = path.file_name().map_or(path.display().to_string(), |n| {
+            n.to_string_lossy().to_string()
+        });
+        let summary = self.change_all(
+            |conn| catalog_json::import_file(conn, path),
+            |_| format!("Importar {}", file_name),
+        )?;
+        self.clear_selection();
+        self.load_items();
+        self.saved_filters = tags::load_saved_filters(&self.conn)?;
+        self.staleness = staleness::load_config(&self.conn)?;
+        self.status_message = Some(format!(
+            "Catálogo importado: {} itens e {} preços no histórico.",
+            summary.items, summary.prices
+        ));
+        self.status_message_timer = None;
+        Ok(())
+    }
+
+    fn show_json_import_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.confirm_json_import.clone() else {
+            return;
+        };
+        egui::Window::new("Confirmar importação")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Substituir todo o catálogo (itens, lixeira, tags, histórico de preços e \
+                     prazos) pelo conteúdo de \"{}\"?",
+                    path.display()
+                ));
+                ui.label("Um backup do estado atual é feito antes da importação.");
+                ui.horizontal(|ui| {
+                    if ui.button("Cancelar").clicked() {
+                        self.confirm_json_import = None;
+                    }
+                    if ui.button("Substituir").clicked() {
+                        self.confirm_json_import = None;
+                        if let Err(e) = self.import_json(&path) {
+                            self.status_message =
+                                Some(format!("Erro ao importar (nada foi alterado):\n{}", e));
+                            self.status_message_timer = None;
+                        }
+                    }
+                });
+            });
+    }
+
+    fn open_export_window(&mut self) {
+        self.export.scope = if self.selected_ids.len() > 1 {
+            export::ExportScope::Selection
+        } else {
+            export::ExportScope::Visible
+        };
+        match export::load_presets(&self.conn).and_then(|presets| {
+            let mut contacts = export::load_contacts(&self.conn)?;
+            for vendor in &self.all_vendors {
+                contacts.entry(vendor.clone()).or_default();
+            }
+            Ok((presets, contacts))
+        }) {
+            Ok((presets, contacts)) => {
+                self.export.presets = presets;
+                self.export.contacts = contacts;
+                self.export.open = true;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao abrir a exportação: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_export_window(&mut self, ctx: &egui::Context) {
+        let local = self.remote.is_none();
+        let mut open = true;
+        let mut export_now = false;
+        let mut save_preset = false;
+        let mut delete_preset: Option<String> = None;
+        let mut save_contacts = false;
+        let selected_count = self.selected_ids.len();
+        egui::Window::new("Exportar visíveis")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(450.0)
+            .show(ctx, |ui| {
+                let view = &mut self.export;
+                ui.horizontal(|ui| {
+                    ui.radio_value(
+                        &mut view.scope,
+                        export::ExportScope::Visible,
+                        format!("Itens visíveis ({})", self.visible_items.len()),
+                    );
+                    ui.add_enabled_ui(selected_count > 0, |ui| {
+                        ui.radio_value(
+                            &mut view.scope,
+                            export::ExportScope::Selection,
+                            format!("Selecionados ({})", selected_count),
+                        );
+                    });
+                });
+
+                if local {
+                    ui.horizontal(|ui| {
+                        ui.label("Modelo:");
+                        egui::ComboBox::from_id_salt("export_preset")
+                            .selected_text("Escolher...")
+                            .show_ui(ui, |ui| {
+                                for preset in &view.presets {
+                                    if ui.selectable_label(false, &preset.name).clicked() {
+                                        view.options = preset.options.clone();
+                                        view.new_preset_name = preset.name.clone();
+                                    }
+                                }
+                            });
+                        if !view.new_preset_name.is_empty()
+                            && view.presets.iter().any(|p| p.name == view.new_preset_name)
+                            && ui.button("Excluir modelo").clicked()
+                        {
+                            delete_preset = Some(view.new_preset_name.clone());
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label("Colunas:");
+                egui::Grid::new("export_columns")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (index, column) in export::ExportColumn::ALL.iter().enumerate() {
+                            let mut checked = view.options.columns.contains(column);
+                            if ui.checkbox(&mut checked, column.label()).changed() {
+                                if checked {
+                                    view.options.columns.push(*column);
+                                    view.options.columns.sort_by_key(|c| {
+                                        export::ExportColumn::ALL.iter().position(|a| a == c)
+                                    });
+                                } else {
+                                    view.options.columns.retain(|c| c != column);
+                                }
+                            }
+                            if index % 2 == 1 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    ui.label("Separador de colunas:");
+                    egui::ComboBox::from_id_salt("export_delimiter")
+                        .selected_text(view.options.delimiter.label())
+                        .show_ui(ui, |ui| {
+                            for delimiter in export::Delimiter::ALL {
+                                ui.selectable_value(
+                                    &mut view.options.delimiter,
+                                    delimiter,
+                                    delimiter.label(),
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Separador decimal:");
+                    ui.radio_value(&mut view.options.decimal_comma, true, "Vírgula (1234,50)");
+                    ui.radio_value(&mut view.options.decimal_comma, false, "Ponto (1234.50)");
+                });
+
+                if local {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TextEdit::singleline(&mut view.new_preset_name)
+                                .hint_text("Nome do modelo")
+                                .desired_width(180.0),
+                        );
+                        save_preset = ui
+                            .add_enabled(
+                                !view.new_preset_name.trim().is_empty(),
+                                Button::new("Salvar como modelo"),
+                            )
+                            .clicked();
+                    });
+
+                    egui::CollapsingHeader::new("Contatos dos fornecedores").show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("vendor_contacts")
+                                    .num_columns(2)
+                                    .show(ui, |ui| {
+                                        for (vendor, contact) in &mut view.contacts {
+                                            ui.label(vendor);
+                                            ui.add(
+                                                TextEdit::singleline(contact)
+                                                    .hint_text("E-mail, telefone...")
+                                                    .desired_width(220.0),
+                                            );
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                        save_contacts = ui.button("Salvar contatos").clicked();
+                    });
+                }
+
+                ui.separator();
+                export_now = ui
+                    .add_enabled(!view.options.columns.is_empty(), Button::new("Exportar..."))
+                    .clicked();
+            });
+
+        if save_preset {
+            let preset = export::ExportPreset {
+                name: self.export.new_preset_name.trim().to_string(),
+                options: self.export.options.clone(),
+            };
+            self.status_message = Some(
+                match export::save_preset(&self.conn, &preset)
+                    .and_then(|()| Ok(export::load_presets(&self.conn)?))
+                {
+                    Ok(presets) => {
+                        self.export.presets = presets;
+                        format!("Modelo \"{}\" salvo.", preset.name)
+                    }
+                    Err(e) => format!("Erro ao salvar o modelo: {}", e),
+                },
+            );
+            self.status_message_timer = None;
+        }
+        if let Some(name) = delete_preset {
+            if let Err(e) = export::delete_preset(&self.conn, &name) {
+                log::error!("Falha ao excluir o modelo \"{}\": {}", name, e);
+            }
+            self.export.presets.retain(|preset| preset.name != name);
+            self.export.new_preset_name.clear();
+        }
+        if save_contacts {
+            self.status_message = Some(
+                match export::save_contacts(&mut self.conn, &self.export.contacts) {
+                    Ok(()) => "Contatos salvos.".into(),
+                    Err(e) => format!("Erro ao salvar os contatos: {}", e),
+                },
+            );
+            self.status_message_timer = None;
+        }
+
+        if export_now
+            && let Some(path) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .add_filter("Texto", &["txt"])
+                .set_file_name("catalogo.csv")
+                .save_file()
+        {
+            let items = match self.export.scope {
+                export::ExportScope::Visible => self.visible_items.clone(),
+                export::ExportScope::Selection => self.selected_items(),
+            };
+            // Contatos gravados, não os que ainda estão sendo digitados
+            let contacts = export::load_contacts(&self.conn).unwrap_or_default();
+            self.status_message = Some(
+                match export::write_csv(&path, &items, &self.export.options, &contacts) {
+                    Ok(()) => format!("{} item(ns) exportado(s).", items.len()),
+                    Err(e) => format!("Falha ao exportar: {}", e),
+                },
+            );
+            self.status_message_timer = None;
+        }
+        if !open {
+            self.export.open = false;
+        }
+    }
+
+    fn show_staleness_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Prazos de validade dos preços")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Prazo padrão:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.staleness.default_days)
+                            .range(1..=3650)
+                            .suffix(" dias"),
+                    );
+                });
+
+                ui.separator();
+                ui.label("Prazos por fornecedor:");
+                Self::stale_days_list(
+                    ui,
+                    "prazo_fornecedor",
+                    "Fornecedor...",
+                    &self.all_vendors,
+                    &mut self.staleness.vendor_days,
+                    &mut self.new_vendor_stale_days,
+                    self.staleness.default_days,
+                );
+
+                ui.separator();
+                ui.label("Prazos por categoria (o do fornecedor prevalece):");
+                Self::stale_days_list(
+                    ui,
+                    "prazo_categoria",
+                    "Categoria...",
+                    &self.all_categories,
+                    &mut self.staleness.category_days,
+                    &mut self.new_category_stale_days,
+                    self.staleness.default_days,
+                );
+
+                ui.separator();
+                if ui.button("Salvar").clicked() {
+                    save = true;
+                }
+            });
+
+        if save {
+            match staleness::save_config(&mut self.conn, &self.staleness) {
+                Ok(()) => {
+                    self.status_message = Some("Prazos de validade salvos.".into());
+                    self.show_staleness_settings = false;
+                    self.refresh_visible_items();
+                }
+                Err(e) => self.status_message = Some(format!("Erro ao salvar prazos: {}", e)),
+            }
+            self.status_message_timer = None;
+        } else if !open {
+            // Descarta alterações não salvas
+            self.staleness = staleness::load_config(&self.conn).unwrap_or_default();
+            self.show_staleness_settings = false;
+        }
+    }
+
+    /// Lista de prazos próprios (por fornecedor ou categoria) com a linha
+    /// para incluir um novo.
+    fn stale_days_list(
+        ui: &mut egui::Ui,
+        id_salt: &str,
+        placeholder: &str,
+        names: &[String],
+        days: &mut Vec<(String, i64)>,
+        new: &mut (String, i64),
+        default_days: i64,
+    ) {
+        let mut remove = None;
+        egui::Grid::new(format!("grid_{}", id_salt))
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                for (index, (name, days)) in days.iter_mut().enumerate() {
+                    ui.label(name.as_str());
+                    ui.add(egui::DragValue::new(days).range(1..=3650).suffix(" dias"));
+                    if ui.button("Remover").clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = remove {
+            days.remove(index);
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt(format!("{}_novo", id_salt))
+                .selected_text(if new.0.is_empty() {
+                    placeholder
+                } else {
+                    &new.0
+                })
+                .show_ui(ui, |ui| {
+                    for name in names {
+                        ui.selectable_value(&mut new.0, name.clone(), name);
+                    }
+                });
+            ui.add(
+                egui::DragValue::new(&mut new.1)
+                    .range(1..=3650)
+                    .suffix(" dias"),
+            );
+            if ui.button("Adicionar").clicked() && !new.0.is_empty() {
+                let (name, value) = std::mem::take(new);
+                days.retain(|(n, _)| *n != name);
+                days.push((name, value));
+                new.1 = default_days;
+            }
+        });
+    }
+
+    fn open_requote_window(&mut self) {
+        match requote::stale_items_by_vendor(&self.conn) {
+            Ok(groups) => {
+                self.requote_groups = groups;
+                self.show_requote = true;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao buscar itens desatualizados: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_requote_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("Cotação de itens desatualizados")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                if self.requote_groups.is_empty() {
+                    ui.label("Nenhum item desatualizado.");
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for quote in &self.requote_groups {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{} ({} itens)", quote.vendor, quote.items.len()));
+                                if ui.button("Copiar mensagem").clicked() {
+                                    ctx.copy_text(requote::quote_message(quote));
+                                    self.status_message = Some(
+                                        "Mensagem copiada para a área de transferência".into(),
+                                    );
+                                    self.status_message_timer = None;
+                                }
+                                if ui.button("Salvar planilha").clicked()
+                                    && let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("CSV", &["csv"])
+                                        .set_file_name(requote::template_file_name(&quote.vendor))
+                                        .save_file()
+                                {
+                                    self.status_message =
+                                        Some(match requote::write_quote_template(&path, quote) {
+                                            Ok(()) => "Planilha de cotação salva.".into(),
+                                            Err(e) => format!("Falha ao salvar planilha: {}", e),
+                                        });
+                                    self.status_message_timer = None;
+                                }
+                            });
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if !self.requote_groups.is_empty()
+                        && ui.button("Salvar todas as planilhas").clicked()
+                        && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                    {
+                        let result = self.requote_groups.iter().try_for_each(|quote| {
+                            requote::write_quote_template(
+                                &folder.join(requote::template_file_name(&quote.vendor)),
+                                quote,
+                            )
+                        });
+                        self.status_message = Some(match result {
+                            Ok(()) => format!(
+                                "{} planilha(s) salva(s) em {}",
+                                self.requote_groups.len(),
+                                folder.display()
+                            ),
+                            Err(e) => format!("Falha ao salvar planilhas: {}", e),
+                        });
+                        self.status_message_timer = None;
+                    }
+
+                    if ui.button("Importar planilha preenchida").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("CSV", &["csv"])
+                            .pick_file()
+                    {
+                        let result = self.backup_before_import().and_then(|()| {
+                            self.change_all(
+                                |conn| requote::import_quote_template(conn, &path),
+                                |_| "Importar cotação".to_string(),
+                            )
+                        });
+                        match result {
+                            Ok(summary) => {
+                                self.status_message = Some(summary.message());
+                                self.load_items();
+                                self.requote_groups =
+                                    requote::stale_items_by_vendor(&self.conn).unwrap_or_default();
+                            }
+                            Err(e) => {
+                                self.status_message =
+                                    Some(format!("Erro ao importar cotação: {}", e));
+                            }
+                        }
+                        self.status_message_timer = None;
+                    }
+                });
+            });
+        if !open {
+            self.show_requote = false;
+        }
+    }
+
+    fn backup_before_import(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = self.backup_dir();
+        backup::create_backup(&self.conn, &dir, backup::BackupKind::BeforeImport)
+            .map_err(|e| format!("não foi possível fazer o backup antes da importação: {}", e))?;
+        backup::rotate(&dir, &backup::load_config(&self.conn))?;
+        Ok(())
+    }
+
+    fn open_backup_window(&mut self) {
+        let dir = self.backup_dir();
+        match backup::list_backups(&dir) {
+            Ok(mut backups) => {
+                for backup in &mut backups {
+                    backup.item_count = backup::count_items(&backup.path).ok();
+                }
+                self.backups.backups = backups;
+                self.backups.config = backup::load_config(&self.conn);
+                self.backups.confirm_restore = None;
+                self.backups.open = true;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao listar backups: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_backup_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut restore: Option<usize> = None;
+        let mut backup_now = false;
+        egui::Window::new("Restaurar backup")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Backups na pasta \"{}\" (o diário e o semanal são feitos ao abrir o programa).",
+                    self.backup_dir().display()
+                ));
+                if self.backups.backups.is_empty() {
+                    ui.label("Nenhum backup encontrado.");
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(350.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("backup_grid")
+                            .striped(true)
+                            .num_columns(5)
+                            .show(ui, |ui| {
+                                ui.strong("Data");
+                                ui.strong("Tipo");
+                                ui.strong("Itens");
+                                ui.strong("Tamanho");
+                                ui.end_row();
+                                for (index, backup) in self.backups.backups.iter().enumerate() {
+                                    ui.label(backup.created_at.format("%d/%m/%Y %H:%M").to_string());
+                                    ui.label(backup.kind.label());
+                                    ui.label(match backup.item_count {
+                                        Some(count) => count.to_string(),
+                                        None => "ilegível".to_string(),
+                                    });
+                                    ui.label(format!("{} KB", backup.size / 1024));
+                                    if ui.button("Restaurar").clicked() {
+                                        self.backups.confirm_restore = Some(index);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Manter:");
+                    let config = &mut self.backups.config;
+                    ui.add(egui::DragValue::new(&mut config.keep_daily).range(1..=365).suffix(" diários"));
+                    ui.add(egui::DragValue::new(&mut config.keep_weekly).range(1..=520).suffix(" semanais"));
+                    ui.add(egui::DragValue::new(&mut config.keep_other).range(1..=100).suffix(" dos demais"));
+                    if ui.button("Salvar").clicked() {
+                        self.status_message = Some(
+                            match backup::save_config(&self.conn, &self.backups.config) {
+                                Ok(()) => "Retenção de backups salva.".into(),
+                                Err(e) => format!("Erro ao salvar retenção de backups: {}", e),
+                            },
+                        );
+                        self.status_message_timer = None;
+                    }
+                });
+                if ui.button("Fazer backup agora").clicked() {
+                    backup_now = true;
+                }
+            });
+
+        if let Some(index) = self.backups.confirm_restore
+            && let Some(backup) = self.backups.backups.get(index)
+        {
+            egui::Window::new("Confirmar restauração")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Substituir o catálogo atual pelo backup de {}?",
+                        backup.created_at.format("%d/%m/%Y %H:%M")
+                    ));
+                    ui.label("Um backup do estado atual é feito antes da restauração.");
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancelar").clicked() {
+                            self.backups.confirm_restore = None;
+                        }
+                        if ui.button("Sim, restaurar").clicked() {
+                            restore = Some(index);
+                        }
+                    });
+                });
+        }
+
+        if backup_now {
+            let dir = self.backup_dir();
+            self.status_message = Some(
+                match backup::create_backup(&self.conn, &dir, backup::BackupKind::Manual).and_then(
+                    |path| {
+                        backup::rotate(&dir, &backup::load_config(&self.conn))?;
+                        Ok(path)
+                    },
+                ) {
+                    Ok(path) => format!("Backup salvo em {}", path.display()),
+                    Err(e) => format!("Erro ao fazer backup: {}", e),
+                },
+            );
+            self.status_message_timer = None;
+            self.open_backup_window();
+        }
+
+        if let Some(index) = restore {
+            let path = self.backups.backups[index].path.clone();
+            self.backups.confirm_restore = None;
+            match self.restore_backup(&path) {
+                Ok(()) => {
+                    self.status_message = Some("Backup restaurado.".into());
+                    self.open_backup_window();
+                }
+                Err(e) => self.status_message = Some(format!("Erro ao restaurar backup: {}", e)),
+            }
+            self.status_message_timer = None;
+        }
+
+        if !open {
+            self.backups.open = false;
+        }
+    }
+
+    /// Verifica a integridade do backup, guarda o estado atual e restaura.
+    fn restore_backup(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let problems = backup::integrity_check(path)?;
+        if !problems.is_empty() {
+            return Err(format!("o backup está corrompido: {}", problems.join("; ")).into());
+        }
+        backup::create_backup(
+            &self.conn,
+            &self.backup_dir(),
+            backup::BackupKind::BeforeRestore,
+        )?;
+        backup::restore_backup(&mut self.conn, path)?;
+        // Backups antigos podem precisar das migrações atuais
+        init_schema(&self.conn)?;
+        self.conn
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+        // As entradas de desfazer se referem ao catálogo anterior
+        self.undo_stack = undo::UndoStack::default();
+        self.clear_selection();
+        self.clear_form();
+        self.staleness = staleness::load_config(&self.conn).unwrap_or_default();
+        self.saved_filters = tags::load_saved_filters(&self.conn).unwrap_or_default();
+        self.load_items();
+        Ok(())
+    }
+
+    /// Abre o outro catálogo e calcula o que muda de cada lado.
+    fn open_sync_file(&mut self, path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let same_file =
+            std::fs::canonicalize(&path).ok() == std::fs::canonicalize(&self.db_path).ok();
+        if same_file {
+            return Err("o arquivo escolhido é o próprio catálogo aberto".into());
+        }
+        let other = init_db(&path)?;
+        self.sync.plan = Some(sync::plan(&self.conn, &other)?);
+        self.sync.other = Some(other);
+        self.sync.other_path = Some(path);
+        Ok(())
+    }
+
+    fn apply_sync(&mut self) -> Result<sync::SyncSummary, Box<dyn std::error::Error>> {
+        // Fora de `self` enquanto grava, para a gravação poder usar `self`
+        let mut view = std::mem::take(&mut self.sync);
+        let result = self.sync_with(&mut view);
+        self.sync = view;
+        result
+    }
+
+    fn sync_with(
+        &mut self,
+        view: &mut sync::SyncView,
+    ) -> Result<sync::SyncSummary, Box<dyn std::error::Error>> {
+        let (Some(other), Some(other_path), Some(plan)) = (
+            view.other.as_mut(),
+            view.other_path.as_ref(),
+            view.plan.as_ref(),
+        ) else {
+            return Err("nenhum catálogo escolhido".into());
+        };
+        for (conn, path) in [(&self.conn, &self.db_path), (&*other, other_path)] {
+            let dir = backup::backup_dir(path);
+            backup::create_backup(conn, &dir, backup::BackupKind::BeforeSync).map_err(|e| {
+                format!(
+                    "não foi possível fazer o backup antes da sincronização: {}",
+                    e
+                )
+            })?;
+            backup::rotate(&dir, &backup::load_config(conn))?;
+        }
+
+        let file_name = |path: &Path| {
+            path.file_name().map_or(path.display().to_string(), |n| {
+                n.to_string_lossy().to_string()
+            })
+        };
+        let other_before = undo::capture_all(other)?;
+        let label = format!("Sincronizar com {}", file_name(other_path));
+        let summary = self.change_all(
+            |conn| sync::apply(conn, other, plan, &current_user()),
+            |_| label,
+        )?;
+
+        // O outro catálogo também registra na auditoria o que recebeu
+        let (other_before, other_after) = undo::diff(other_before, undo::capture_all(other)?);
+        let origin = format!("Sincronizar com {}", file_name(&self.db_path));
+        if let Err(e) = audit::record_changes(other, &origin, &other_before, &other_after) {
+            log::error!("Falha ao registrar auditoria de \"{}\": {}", origin, e);
+        }
+        self.load_items();
+        Ok(summary)
+    }
+
+    fn show_sync_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut pick_file = false;
+        let mut apply = false;
+        egui::Window::new("Sincronizar catálogos")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Troca as alterações com outra cópia do catálogo (por exemplo, a do \
+                     notebook levado para a obra). Cada lado recebe o que foi alterado só \
+                     no outro; o que foi alterado nos dois aparece como conflito.",
+                );
+                ui.horizontal(|ui| {
+                    pick_file = ui.button("Escolher catálogo...").clicked();
+                    if let Some(path) = &self.sync.other_path {
+                        ui.label(path.display().to_string());
+                    }
+                });
+
+                let Some(plan) = self.sync.plan.as_mut() else {
+                    return;
+                };
+                ui.separator();
+                if plan.changes.is_empty() && plan.conflicts.is_empty() {
+                    ui.label("Os dois catálogos já estão sincronizados.");
+                    return;
+                }
+                ui.label(format!(
+                    "{} alteração(ões) deste catálogo serão enviadas e {} do outro serão recebidas.",
+                    plan.count_from(sync::Side::Local),
+                    plan.count_from(sync::Side::Other)
+                ));
+                egui::CollapsingHeader::new("Alterações sem conflito").show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_salt("sync_changes")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("sync_changes_grid")
+                                .striped(true)
+                                .num_columns(3)
+                                .show(ui, |ui| {
+                                    for change in &plan.changes {
+                                        let (description, brand, vendor) = &change.key;
+                                        ui.label(format!(
+                                            "[{}] {} {}",
+                                            vendor, description, brand
+                                        ));
+                                        ui.label(format!("De: {}", change.from.label()));
+                                        ui.label(change.state.as_ref().map_or(
+                                            "Excluído".to_string(),
+                                            sync::RowState::summary,
+                                        ));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                });
+
+                if !plan.conflicts.is_empty() {
+                    ui.separator();
+                    ui.label(format!(
+                        "{} item(ns) alterado(s) nos dois catálogos. Escolha a versão que vale:",
+                        plan.conflicts.len()
+                    ));
+                    ui.horizontal(|ui| {
+                        for side in [sync::Side::Local, sync::Side::Other] {
+                            if ui.button(format!("Todos: {}", side.label())).clicked() {
+                                for conflict in &mut plan.conflicts {
+                                    conflict.choice = Some(side);
+                                }
+                            }
+                        }
+                    });
+                    egui::ScrollArea::vertical()
+                        .id_salt("sync_conflicts")
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("sync_conflicts_grid")
+                                .striped(true)
+                                .num_columns(3)
+                                .show(ui, |ui| {
+                                    ui.strong("Item");
+                                    ui.strong(sync::Side::Local.label());
+                                    ui.strong(sync::Side::Other.label());
+                                    ui.end_row();
+                                    for conflict in &mut plan.conflicts {
+                                        let (description, brand, vendor) = &conflict.key;
+                                        ui.label(format!(
+                                            "[{}] {} {}",
+                                            vendor, description, brand
+                                        ));
+                                        for (side, state) in [
+                                            (sync::Side::Local, &conflict.local),
+                                            (sync::Side::Other, &conflict.other),
+                                        ] {
+                                            let text = state.as_ref().map_or(
+                                                "Excluído".to_string(),
+                                                sync::RowState::summary,
+                                            );
+                                            ui.radio_value(&mut conflict.choice, Some(side), text);
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                ui.separator();
+                let resolved = plan.is_resolved();
+                if !resolved {
+                    ui.colored_label(egui::Color32::RED, "Resolva todos os conflitos.");
+                }
+                apply = ui
+                    .add_enabled(resolved, Button::new("Sincronizar"))
+                    .clicked();
+            });
+
+        if pick_file
+            && let Some(path) = rfd::FileDialog::new()
+                .add_filter("Catálogo", &["db"])
+                .pick_file()
+            && let Err(e) = self.open_sync_file(path)
+        {
+            self.status_message = Some(format!("Erro ao abrir o catálogo: {}", e));
+            self.status_message_timer = None;
+        }
+
+        if apply {
+            self.status_message = Some(match self.apply_sync() {
+                Ok(summary) => {
+                    self.sync = sync::SyncView::default();
+                    format!(
+                        "Sincronização concluída: {} item(ns) enviado(s), {} recebido(s).",
+                        summary.sent, summary.received
+                    )
+                }
+                Err(e) => format!("Erro ao sincronizar (nada foi alterado):\n{}", e),
+            });
+            self.status_message_timer = None;
+        } else if !open {
+            self.sync = sync::SyncView::default();
+        }
+    }
+
+    /// (Re)inicia a API local conforme a configuração salva no catálogo.
+    fn restart_api(&mut self) {
+        self.api_server = None;
+        let config = api::load_config(&self.conn);
+        if !config.enabled {
+            return;
+        }
+        match api::start(&self.db_path, &config) {
+            Ok(server) => self.api_server = Some(server),
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao iniciar a API local: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_api_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("API local")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Permite que o ERP e macros de planilha consultem e gravem itens enquanto \
+                     o aplicativo estiver aberto. Só atende este computador (127.0.0.1) e \
+                     exige o token em \"Authorization: Bearer <token>\".",
+                );
+                ui.separator();
+                let config = &mut self.api.config;
+                ui.checkbox(&mut config.enabled, "Ligar a API local");
+                ui.horizontal(|ui| {
+                    ui.label("Porta:");
+                    ui.add(egui::DragValue::new(&mut config.port).range(1024..=65535));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Token:");
+                    if config.token.is_empty() {
+                        ui.weak("(nenhum)");
+                    } else {
+                        ui.monospace(&config.token);
+                        if ui.button("Copiar").clicked() {
+                            ctx.copy_text(config.token.clone());
+                        }
+                    }
+                    if ui.button("Gerar novo token").clicked() {
+                        match api::new_token(&self.conn) {
+                            Ok(token) => config.token = token,
+                            Err(e) => log::error!("Falha ao gerar token: {}", e),
+                        }
+                    }
+                });
+                ui.separator();
+                match &self.api_server {
+                    Some(server) => {
+                        ui.label(format!("Atendendo em http://{}", server.addr()));
+                        ui.label(format!(
+                            "Descrição das rotas: http://{}/api/openapi.json",
+                            server.addr()
+                        ));
+                    }
+                    None => {
+                        ui.label("Desligada.");
+                    }
+                }
+                save = ui.button("Salvar").clicked();
+            });
+
+        if save {
+            if let Err(e) = api::save_config(&self.conn, &self.api.config) {
+                self.status_message = Some(format!("Erro ao salvar a API local: {}", e));
+                self.status_message_timer = None;
+                return;
+            }
+            self.status_message = Some("Configuração da API local salva.".to_string());
+            self.restart_api();
+        }
+        if !open {
+            self.api.open = false;
+        }
+    }
+
+    fn open_trash_window(&mut self) {
+        match trash::load_trash(&self.conn) {
+            Ok(items) => {
+                self.trash.items = items;
+                self.trash.retention_days = trash::retention_days(&self.conn);
+                self.trash.open = true;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao abrir a lixeira: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_trash_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut to_restore: Vec<i32> = vec![];
+        let mut to_purge: Vec<i32> = vec![];
+        egui::Window::new("Lixeira")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                if self.trash.items.is_empty() {
+                    ui.label("A lixeira está vazia.");
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("trash_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                for trashed in &self.trash.items {
+                                    let item = &trashed.item;
+                                    ui.label(format!(
+                                        "{} {} - {} - R$ {}",
+                                        item.description,
+                                        item.brand,
+                                        item.vendor,
+                                        format_money(item.price)
+                                    ));
+                                    ui.label(format!(
+                                        "Excluído em {} por {}",
+                                        trashed.deleted_at, trashed.deleted_by
+                                    ));
+                                    if ui.button("Restaurar").clicked() {
+                                        to_restore.push(item.id);
+                                    }
+                                    if ui.button("Excluir definitivamente").clicked() {
+                                        to_purge.push(item.id);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if !self.trash.items.is_empty() {
+                        if ui.button("Restaurar tudo").clicked() {
+                            to_restore = self.trash.items.iter().map(|t| t.item.id).collect();
+                        }
+                        if ui.button("Esvaziar lixeira").clicked() {
+                            to_purge = self.trash.items.iter().map(|t| t.item.id).collect();
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Excluir definitivamente após");
+                    ui.add(
+                        egui::DragValue::new(&mut self.trash.retention_days)
+                            .range(1..=3650)
+                            .suffix(" dias"),
+                    );
+                    if ui.button("Salvar").clicked() {
+                        self.status_message = Some(
+                            match trash::set_retention_days(&self.conn, self.trash.retention_days) {
+                                Ok(()) => "Prazo da lixeira salvo.".into(),
+                                Err(e) => format!("Erro ao salvar prazo da lixeira: {}", e),
+                            },
+                        );
+                        self.status_message_timer = None;
+                    }
+                });
+            });
+
+        if !to_restore.is_empty() {
+            let result = self.change(
+                &to_restore,
+                |conn| trash::restore(conn, &to_restore),
+                |affected| Some((format!("Restaurar {} item(ns)", affected), vec![])),
+            );
+            match result {
+                Ok(affected) => {
+                    self.status_message = Some(format!("{} item(ns) restaurado(s).", affected));
+                }
+                Err(e) => self.status_message = Some(format!("Erro ao restaurar: {}", e)),
+            }
+            self.status_message_timer = None;
+            self.load_items();
+            self.trash.items = trash::load_trash(&self.conn).unwrap_or_default();
+        }
+
+        if !to_purge.is_empty() {
+            let result = self.change(
+                &to_purge,
+                |conn| trash::purge(conn, &to_purge),
+                |affected| {
+                    Some((
+                        format!("Excluir definitivamente {} item(ns)", affected),
+                        vec![],
+                    ))
+                },
+            );
+            match result {
+                Ok(affected) => {
+                    let mut message = format!("{} item(ns) excluído(s) definitivamente.", affected);
+                    if affected < to_purge.len() {
+                        message.push_str(&format!(
+                            " {} item(ns) ficaram na lixeira por estarem no orçamento.",
+                            to_purge.len() - affected
+                        ));
+                    }
+                    self.status_message = Some(message);
+                }
+                Err(e) => self.status_message = Some(format!("Erro ao excluir: {}", e)),
+            }
+            self.status_message_timer = None;
+            self.trash.items = trash::load_trash(&self.conn).unwrap_or_default();
+        }
+
+        if !open {
+            self.trash.open = false;
+        }
+    }
+
+    fn reload_audit_entries(&mut self) {
+        match audit::load_entries(&self.conn, &self.audit.query, Some(AUDIT_ROWS_SHOWN)) {
+            Ok(entries) => self.audit.entries = entries,
+            Err(e) => {
+                self.status_message = Some(format!("Erro ao carregar auditoria: {}", e));
+                self.status_message_timer = None;
+            }
+        }
+    }
+
+    fn show_audit_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut reload = false;
+        egui::Window::new("Auditoria")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(800.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Buscar:");
+                    reload |= ui
+                        .add(
+                            TextEdit::singleline(&mut self.audit.query)
+                                .hint_text("Usuário, máquina, ação ou valor")
+                                .min_size(vec2(300.0, 0.0)),
+                        )
+                        .changed();
+
+                    if ui.button("Exportar CSV").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("CSV", &["csv"])
+                            .set_file_name("auditoria.csv")
+                            .save_file()
+                    {
+                        let result = audit::load_entries(&self.conn, &self.audit.query, None)
+                            .map_err(|e| e.into())
+                            .and_then(|entries| audit::export_csv(&path, &entries));
+                        self.status_message = Some(match result {
+                            Ok(()) => "Auditoria exportada.".into(),
+                            Err(e) => format!("Falha ao exportar auditoria: {}", e),
+                        });
+                        self.status_message_timer = None;
+                    }
+                });
+
+                if self.audit.entries.len() == AUDIT_ROWS_SHOWN {
+                    ui.label(format!(
+                        "Exibindo os {} registros mais recentes. Use a busca ou exporte o CSV para ver todos.",
+                        AUDIT_ROWS_SHOWN
+                    ));
+                }
+                ui.separator();
+
+                egui::ScrollArea::both().max_height(450.0).show(ui, |ui| {
+                    egui::Grid::new("audit_grid")
+                        .striped(true)
+                        .num_columns(5)
+                        .show(ui, |ui| {
+                            ui.strong("Data");
+                            ui.strong("Usuário");
+                            ui.strong("Ação");
+                            ui.strong("Origem");
+                            ui.strong("Alterações");
+                            ui.end_row();
+                            for entry in &self.audit.entries {
+                                ui.label(&entry.recorded_at);
+                                ui.label(format!("{} ({})", entry.user, entry.machine));
+                                ui.label(&entry.action);
+                                ui.label(&entry.origin);
+                                ui.label(entry.summary());
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        if reload {
+            self.reload_audit_entries();
+        }
+        if !open {
+            self.audit.open = false;
+        }
+    }
+
+    fn show_bulk_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("Reajuste em lote")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(550.0)
+            .show(ctx, |ui| {
+                let (scope, items) = if self.selected_ids.is_empty() {
+                    (bulk::Scope::Visible, self.visible_items.clone())
+                } else {
+                    (bulk::Scope::Selection, self.selected_items())
+                };
+                ui.label(format!("Aplica-se aos {}.", scope.describe(items.len())));
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(
+                        &mut self.bulk.mode,
+                        bulk::AdjustmentMode::Percent,
+                        "Percentual",
+                    );
+                    ui.radio_value(
+                        &mut self.bulk.mode,
+                        bulk::AdjustmentMode::Fixed,
+                        "Valor fixo",
+                    );
+                    ui.radio_value(&mut self.bulk.mode, bulk::AdjustmentMode::Index, "Índice");
+                });
+
+                match self.bulk.mode {
+                    bulk::AdjustmentMode::Percent | bulk::AdjustmentMode::Fixed => {
+                        ui.horizontal(|ui| {
+                            ui.label(if self.bulk.mode == bulk::AdjustmentMode::Percent {
+                                "Percentual (%):"
+                            } else {
+                                "Valor (R$):"
+                            });
+                            ui.add(
+                                TextEdit::singleline(&mut self.bulk.value)
+                                    .hint_text("ex.: 8 ou -2,5")
+                                    .desired_width(100.0),
+                            );
+                        });
+                    }
+                    bulk::AdjustmentMode::Index => {
+                        ui.horizontal(|ui| {
+                            if ui.button("Carregar arquivo de índice").clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("CSV", &["csv", "txt"])
+                                    .pick_file()
+                            {
+                                match bulk::load_index_file(&path) {
+                                    Ok(series) => {
+                                        self.bulk.index_from = series.months[0].0.clone();
+                                        self.bulk.index_to =
+                                            series.months[series.months.len() - 1].0.clone();
+                                        self.bulk.index = Some(series);
+                                    }
+                                    Err(e) => {
+                                        self.status_message =
+                                            Some(format!("Erro ao ler índice: {}", e));
+                                        self.status_message_timer = None;
+                                    }
+                                }
+                            }
+                            if let Some(series) = &self.bulk.index {
+                                ui.label(format!(
+                                    "{} ({} meses)",
+                                    series.name,
+                                    series.months.len()
+                                ));
+                            }
+                        });
+                        if let Some(series) = &self.bulk.index {
+                            ui.horizontal(|ui| {
+                                ui.label("De:");
+                                egui::ComboBox::from_id_salt("indice_de")
+                                    .selected_text(&self.bulk.index_from)
+                                    .show_ui(ui, |ui| {
+                                        for (month, _) in &series.months {
+                                            ui.selectable_value(
+                                                &mut self.bulk.index_from,
+                                                month.clone(),
+                                                month,
+                                            );
+                                        }
+                                    });
+                                ui.label("Até:");
+                                egui::ComboBox::from_id_salt("indice_ate")
+                                    .selected_text(&self.bulk.index_to)
+                                    .show_ui(ui, |ui| {
+                                        for (month, _) in &series.months {
+                                            ui.selectable_value(
+                                                &mut self.bulk.index_to,
+                                                month.clone(),
+                                                month,
+                                            );
+                                        }
+                                    });
+                                match series.factor(&self.bulk.index_from, &self.bulk.index_to) {
+                                    Ok(factor) => {
+                                        ui.label(format!(
+                                            "Acumulado: {:.2}%",
+                                            (factor - 1.0) * 100.0
+                                        ));
+                                    }
+                                    Err(e) => {
+                                        ui.colored_label(ui.visuals().error_fg_color, e);
+                                    }
+                                }
+                            });
+                        }
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Arredondamento:");
+                    egui::ComboBox::from_id_salt("arredondamento")
+                        .selected_text(self.bulk.rounding.label())
+                        .show_ui(ui, |ui| {
+                            for rounding in bulk::Rounding::ALL {
+                                ui.selectable_value(
+                                    &mut self.bulk.rounding,
+                                    rounding,
+                                    rounding.label(),
+                                );
+                            }
+                        });
+                });
+
+                if ui.button("Pré-visualizar").clicked() {
+                    match self.bulk.adjustment_value() {
+                        Ok(value) => {
+                            self.bulk.preview =
+                                bulk::preview(&items, self.bulk.mode, value, self.bulk.rounding);
+                            self.bulk.preview_scope = scope;
+                            self.bulk.error = None;
+                        }
+                        Err(e) => {
+                            self.bulk.preview.clear();
+                            self.bulk.error = Some(e);
+                        }
+                    }
+                }
+                if let Some(error) = &self.bulk.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                if !self.bulk.preview.is_empty() {
+                    ui.separator();
+                    ui.strong(format!(
+                        "Pré-visualização dos {}",
+                        self.bulk
+                            .preview_scope
+                            .describe(self.bulk.preview.len())
+                    ));
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("grid_reajuste")
+                                .striped(true)
+                                .spacing([10.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.strong("Item");
+                                    ui.strong("Fornecedor");
+                                    ui.strong("Preço atual");
+                                    ui.strong("Novo preço");
+                                    ui.end_row();
+                                    for change in &self.bulk.preview {
+                                        ui.label(&change.description);
+                                        ui.label(&change.vendor);
+                                        ui.label(format!("R$ {}", format_money(change.old_price)));
+                                        let new_price =
+                                            format!("R$ {}", format_money(change.new_price));
+                                        if change.is_valid() {
+                                            ui.label(new_price);
+                                        } else {
+                                            ui.colored_label(
+                                                egui::Color32::DARK_RED,
+                                                format!("{} (inválido)", new_price),
+                                            );
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+
+                    let invalid = self
+                        .bulk
+                        .preview
+                        .iter()
+                        .filter(|change| !change.is_valid())
+                        .count();
+                    if invalid > 0 {
+                        ui.colored_label(
+                            egui::Color32::DARK_RED,
+                            format!(
+                                "{} itens ficariam com preço zero ou negativo. Ajuste o valor ou a seleção.",
+                                invalid
+                            ),
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(
+                                invalid == 0,
+                                Button::new(format!("Aplicar a {} itens", self.bulk.preview.len())),
+                            )
+                            .clicked()
+                        {
+                            let changes = std::mem::take(&mut self.bulk.preview);
+                            let ids: Vec<i32> = changes.iter().map(|c| c.item_id).collect();
+                            let result = self.change(
+                                &ids,
+                                |conn| bulk::apply_changes(conn, &changes, "reajuste"),
+                                |skipped| {
+                                    Some((
+                                        format!(
+                                            "Reajuste de {} itens",
+                                            changes.len() - skipped.len()
+                                        ),
+                                        vec![],
+                                    ))
+                                },
+                            );
+                            match result {
+                                Ok(skipped) => {
+                                    let applied = changes.len() - skipped.len();
+                                    let undo = match self.shortcuts.text(Command::Undo) {
+                                        shortcut if shortcut.is_empty() => {
+                                            "o botão Desfazer".to_string()
+                                        }
+                                        shortcut => shortcut,
+                                    };
+                                    let mut message = format!(
+                                        "Reajuste aplicado a {} itens. Use {} para desfazer.",
+                                        applied, undo
+                                    );
+                                    if !skipped.is_empty() {
+                                        message.push_str(&format!(
+                                            "\n{} itens foram alterados por outra pessoa e não \
+                                             foram reajustados: {}",
+                                            skipped.len(),
+                                            skipped.join(", ")
+                                        ));
+                                    }
+                                    self.status_message = Some(message);
+                                    self.load_items();
+                                }
+                                Err(e) => {
+                                    self.status_message =
+                                        Some(format!("Erro ao aplicar reajuste: {}", e));
+                                }
+                            }
+                            self.status_message_timer = None;
+                        }
+                        if ui.button("Cancelar").clicked() {
+                            self.bulk.preview.clear();
+                        }
+                    });
+                }
+            });
+        if !open {
+            self.bulk.open = false;
+            self.bulk.preview.clear();
+        }
+    }
+
+    fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
+        // A janela de atalhos espera a tecla nova e a paleta trata as próprias teclas
+        if self.shortcuts_view.capturing.is_some() || self.palette.open || self.confirm_delete {
+            return;
+        }
+
+        let typing = ctx.wants_keyboard_input();
+        let form_focused = ctx
+            .memory(|memory| memory.focused())
+            .is_some_and(|focused| FORM_FIELDS.iter().any(|field| form_field(field) == focused));
+        let completing = form_focused && self.completion.is_open();
+        let bindings = self.shortcuts.by_specificity();
+        // Ctrl+Shift+Z também refaz, se não estiver em outra ação
+        let redo_alias = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::Z,
+        );
+        let alias_free = !bindings.iter().any(|(_, shortcut)| *shortcut == redo_alias);
+        let triggered: Vec<Command> = ctx.input_mut(|input| {
+            let mut triggered = vec![];
+            if !typing && alias_free && input.consume_shortcut(&redo_alias) {
+                triggered.push(Command::Redo);
+            }
+            for (command, shortcut) in &bindings {
+                let applies = match command {
+                    // Com as sugestões abertas, Enter, Esc e setas ficam com elas
+                    _ if completing && shortcut.modifiers.is_none() => {
+                        *command == Command::Submit && !self.completion.is_choosing()
+                    }
+                    Command::Submit => form_focused,
+                    _ => !typing || command.applies_while_typing(shortcut),
+                };
+                if applies && input.consume_shortcut(shortcut) {
+                    triggered.push(*command);
+                }
+            }
+            triggered
+        });
+        for command in triggered {
+            self.run_command(ctx, command);
+        }
+    }
+
+    /// Executa uma ação, vinda de um atalho, da paleta ou de um botão.
+    fn run_command(&mut self, ctx: &egui::Context, command: Command) {
+        if command.local_only() && self.remote.is_some() {
+            return;
+        }
+        match command {
+            Command::Submit => self.submit_form(),
+            Command::NewItem => {
+                self.clear_selection();
+                self.clear_form();
+                self.focus_request = Some(form_field("description"));
+            }
+            Command::Duplicate => match self.selected_items()[..] {
+                [ref item] => self.duplicate_into_form(&item.clone()),
+                _ => {
+                    self.status_message = Some("Selecione um item para duplicar.".into());
+                    self.status_message_timer = None;
+                }
+            },
+            Command::Delete => {
+                if !self.selected_ids.is_empty() {
+                    self.confirm_delete = true;
+                }
+            }
+            Command::ClearSelection => {
+                if !self.selected_ids.is_empty() {
+                    self.clear_selection();
+                    self.clear_form();
+                }
+            }
+            Command::SelectPrevious => self.select_adjacent(false),
+            Command::SelectNext => self.select_adjacent(true),
+            Command::FocusSearch => self.focus_request = Some(egui::Id::new(SEARCH_FIELD)),
+            Command::CopySelected => {
+                if !self.selected_ids.is_empty() {
+                    self.copy_selected_items(ctx);
+                }
+            }
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::Palette => {
+                self.palette = shortcuts::PaletteView {
+                    open: true,
+                    ..Default::default()
+                }
+            }
+            Command::ExportVisible => self.open_export_window(),
+            Command::ExportCsv => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("catalogo.csv")
+                    .save_file()
+                    && let Err(e) = self.export_to_csv(&path.to_string_lossy())
+                {
+                    self.status_message = Some(format!("Falha ao exportar: {}", e));
+                    self.status_message_timer = None;
+                }
+            }
+            Command::ImportCsv => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV files", &["csv"])
+                    .pick_file()
+                    && let Err(e) = self.import_csv_to_db(&path.to_string_lossy())
+                {
+                    self.status_message = Some(format!("Erro ao importar: {}", e));
+                    self.status_message_timer = None;
+                }
+            }
+            Command::PasteFromSpreadsheet => self.open_paste_window(),
+            Command::ImportJson => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json", "jsonl", "ndjson"])
+                    .pick_file()
+                {
+                    self.confirm_json_import = Some(path);
+                }
+            }
+            Command::ExportJson => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .add_filter("JSON Lines (catálogos grandes)", &["jsonl"])
+                    .set_file_name("catalogo.json")
+                    .save_file()
+                {
+                    self.status_message =
+                        Some(match catalog_json::export_file(&self.conn, &path) {
+                            Ok(()) => "Catálogo exportado.".into(),
+                            Err(e) => format!("Falha ao exportar: {}", e),
+                        });
+                    self.status_message_timer = None;
+                }
+            }
+            Command::CopyTemplates => self.open_templates_window(),
+            Command::Staleness => self.show_staleness_settings = true,
+            Command::Requote => self.open_requote_window(),
+            Command::BulkAdjust => {
+                self.bulk.open = true;
+                self.bulk.preview.clear();
+            }
+            Command::Budget => self.open_budget_window(),
+            Command::Trash => self.open_trash_window(),
+            Command::Backups => self.open_backup_window(),
+            Command::Sync => self.sync.open = true,
+            Command::LocalApi => {
+                self.api.config = api::load_config(&self.conn);
+                self.api.open = true;
+            }
+            Command::Audit => {
+                self.audit.open = true;
+                self.reload_audit_entries();
+            }
+            Command::Duplicates => {
+                self.duplicates.open = true;
+                self.search_duplicates();
+            }
+            Command::Shortcuts => {
+                self.shortcuts_view = shortcuts::ShortcutsView {
+                    open: true,
+                    shortcuts: self.shortcuts.clone(),
+                    capturing: None,
+                }
+            }
+        }
+    }
+
+    /// Setas na lista: seleciona o item acima ou abaixo do último clicado.
+    fn select_adjacent(&mut self, forward: bool) {
+        let len = self.visible_items.len();
+        if len == 0 {
+            return;
+        }
+        let current = self.selection_anchor.or_else(|| {
+            let id = self.selected_ids.last()?;
+            self.visible_items.iter().position(|item| item.id == *id)
+        });
+        let row = match current {
+            Some(row) if forward => (row + 1).min(len - 1),
+            Some(row) => row.saturating_sub(1),
+            None if forward => 0,
+            None => len - 1,
+        };
+        self.select_only(row);
+        self.scroll_to_row = Some(row);
+    }
+
+    fn show_palette_window(&mut self, ctx: &egui::Context) {
+        let commands = shortcuts::search(&self.palette.query, self.remote.is_some());
+        // Lidas antes do campo de busca, que perderia o foco com Enter e Esc
+        let (up, down, enter, escape) = ctx.input_mut(|input| {
+            (
+                input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                input.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                input.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        let palette = &mut self.palette;
+        if down && palette.highlighted + 1 < commands.len() {
+            palette.highlighted += 1;
+        }
+        if up {
+            palette.highlighted = palette.highlighted.saturating_sub(1);
+        }
+        let mut chosen = if enter {
+            commands.get(palette.highlighted).copied()
+        } else {
+            None
+        };
+
+        let mut open = !escape;
+        egui::Window::new("Paleta de comandos")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    TextEdit::singleline(&mut palette.query)
+                        .hint_text("Digite o nome da ação")
+                        .desired_width(400.0),
+                );
+                response.request_focus();
+                if response.changed() {
+                    palette.highlighted = 0;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("palette_grid")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for (index, command) in commands.iter().enumerate() {
+                                    let highlighted = index == palette.highlighted;
+                                    let response =
+                                        ui.selectable_label(highlighted, command.label());
+                                    if highlighted && (up || down) {
+                                        response.scroll_to_me(None);
+                                    }
+                                    if response.clicked() {
+                                        chosen = Some(*command);
+                                    }
+                                    ui.weak(self.shortcuts.text(*command));
+                                    ui.end_row();
+                                }
+                            });
+                        if commands.is_empty() {
+                            ui.label("Nenhuma ação encontrada.");
+                        }
+                    });
+            });
+
+        if let Some(command) = chosen {
+            self.palette = shortcuts::PaletteView::default();
+            self.run_command(ctx, command);
+        } else if !open {
+            self.palette = shortcuts::PaletteView::default();
+        }
+    }
+
+    fn show_shortcuts_window(&mut self, ctx: &egui::Context) {
+        if let Some(command) = self.shortcuts_view.capturing {
+            let pressed = ctx.input_mut(|input| {
+                let index = input
+                    .events
+                    .iter()
+                    .position(|event| matches!(event, egui::Event::Key { pressed: true, .. }))?;
+                match input.events.remove(index) {
+                    egui::Event::Key { key, modifiers, .. } => Some((key, modifiers)),
+                    _ => None,
+                }
+            });
+            match pressed {
+                Some((egui::Key::Escape, modifiers)) if modifiers.is_none() => {
+                    self.shortcuts_view.capturing = None;
+                }
+                Some((key, modifiers)) => {
+                    let shortcut = shortcuts::from_key(key, modifiers);
+                    self.shortcuts_view.capturing = None;
+                    if let Some(previous) =
+                        self.shortcuts_view.shortcuts.set(command, Some(shortcut))
+                    {
                         self.status_message = Some(format!(
-                            "Preço inválido na linha {}: '{}'",
-                            index + 2,
-                            price_str
+                            "{} estava em \"{}\", que ficou sem atalho.",
+                            shortcuts::format_shortcut(&shortcut),
+                            previous.label()
                         ));
                         self.status_message_timer = None;
-                        continue;
                     }
-                };
-
-                stmt.execute(rusqlite::params![
-                    description,
-                    brand,
-                    vendor,
-                    price,
-                    updated_at
-                ])?;
+                }
+                None => {}
             }
-        } // <- Aqui stmt é dropado, e o compilador libera a referência a tx
-
-        tx.commit()?; // <- Agora pode consumir `tx`
-
-        self.load_items(); // <- Agora pode usar `self` de novo
-        self.status_message = Some("CSV importado com sucesso.".to_string());
-        self.status_message_timer = None;
-
-        Ok(())
-    }
-
-    pub fn export_to_csv(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut file = File::create(path)?;
-        file.write_all(b"\xEF\xBB\xBF")?;
-        let mut wtr = csv::WriterBuilder::new().delimiter(b';').from_writer(file);
+        }
 
-        // Cabeçalho
-        wtr.write_record(&[
-            "descrição",
-            "marca",
-            "fornecedor",
-            "preço",
-            "última atualização",
-        ])?;
+        let mut open = true;
+        let mut save = false;
+        let remote = self.remote.is_some();
+        egui::Window::new("Atalhos do teclado")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                let view = &mut self.shortcuts_view;
+                ui.label(
+                    "Com um campo de texto em foco, só valem os atalhos com Ctrl ou Alt \
+                     (Ctrl+Z e Ctrl+Y ficam com o campo) e o Enter do formulário.",
+                );
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("shortcuts_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                for command in Command::ALL {
+                                    if remote && command.local_only() {
+                                        continue;
+                                    }
+                                    ui.label(command.label());
+                                    if view.capturing == Some(command) {
+                                        ui.strong("Pressione o atalho (Esc cancela)");
+                                    } else {
+                                        let text = view.shortcuts.text(command);
+                                        ui.monospace(if text.is_empty() { "—" } else { &text });
+                                    }
+                                    if ui.button("Alterar").clicked() {
+                                        view.capturing = Some(command);
+                                    }
+                                    if ui
+                                        .add_enabled(
+                                            view.shortcuts.get(command).is_some(),
+                                            Button::new("Remover"),
+                                        )
+                                        .clicked()
+                                    {
+                                        view.shortcuts.set(command, None);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Restaurar padrões").clicked() {
+                        view.shortcuts = shortcuts::Shortcuts::default();
+                        view.capturing = None;
+                    }
+                    save = ui.button("Salvar").clicked();
+                });
+            });
 
-        // Escreve os itens
-        for item in &self.items {
-            let preco = format!("{:.2}", item.price).replace(".", ","); // BR style
-            wtr.write_record(&[
-                &item.description,
-                &item.brand,
-                &item.vendor,
-                &preco,
-                &item.updated_at,
-            ])?;
+        if save {
+            self.shortcuts = self.shortcuts_view.shortcuts.clone();
+            self.status_message = Some(match shortcuts::save(&self.conn, &self.shortcuts) {
+                Ok(()) => "Atalhos salvos.".into(),
+                Err(e) => format!("Erro ao salvar os atalhos: {}", e),
+            });
+            self.status_message_timer = None;
+            self.shortcuts_view = shortcuts::ShortcutsView::default();
+        } else if !open {
+            self.shortcuts_view = shortcuts::ShortcutsView::default();
         }
-
-        wtr.flush()?;
-        self.status_message = Some("Exportado com sucesso.".into());
-        self.status_message_timer = None;
-        Ok(())
     }
+}
 
-    fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
-        ctx.input(|input| {
-            if input.key_pressed(egui::Key::Escape) && self.selected_item_id.is_some() {
-                self.selected_item_id = None;
-                self.new_description.clear();
-                self.new_brand.clear();
-                self.new_vendor.clear();
-                self.new_price.clear();
-            }
-
-            if input.key_pressed(egui::Key::Delete) {
-                if self.selected_item_id.is_some() {
-                    self.confirm_delete = true;
-                }
-            }
-        });
-    }
+/// Opções do menu do botão direito numa linha da lista.
+enum RowAction {
+    /// Copia com o modelo indicado, ou o padrão.
+    Copy(Option<String>),
+    CopyTemplates,
+    Edit,
+    Duplicate,
+    Delete,
+    PriceHistory,
+    OtherVendors,
+    AddToBudget,
+    VendorDetails,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 struct InfraItem {
     id: i32,
     description: String,
@@ -382,6 +4234,12 @@ struct InfraItem {
     vendor: String,
     price: f32,
     updated_at: String,
+    tags: Vec<String>,
+    /// Incrementada a cada gravação; usada para detectar edições simultâneas.
+    version: i64,
+    category: String,
+    /// Unidade de venda ("m", "pç", "cx com 100"...).
+    unit: String,
 }
 
 impl eframe::App for MyApp {
@@ -398,11 +4256,11 @@ impl eframe::App for MyApp {
                 .show(ctx, |ui| {
                     ui.label(status_label);
                 });
-            if let Some(t) = self.status_message_timer {
-                if t.elapsed().as_secs_f32() > 3.0 {
-                    self.status_message = None;
-                    self.status_message_timer = None;
-                }
+            if let Some(t) = self.status_message_timer
+                && t.elapsed().as_secs_f32() > 3.0
+            {
+                self.status_message = None;
+                self.status_message_timer = None;
             }
         }
 
@@ -412,7 +4270,26 @@ impl eframe::App for MyApp {
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
                 .show(ctx, |ui| {
-                    ui.label("Tem certeza que deseja excluir este item?");
+                    if self.selected_ids.len() > 1 {
+                        ui.label(format!(
+                            "Tem certeza que deseja excluir estes {} itens?",
+                            self.selected_ids.len()
+                        ));
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                for item in self.selected_items() {
+                                    ui.label(format!(
+                                        "[{}] {} R$ {}",
+                                        item.vendor,
+                                        item.description,
+                                        format_money(item.price)
+                                    ));
+                                }
+                            });
+                    } else {
+                        ui.label("Tem certeza que deseja excluir este item?");
+                    }
 
                     ui.horizontal(|ui| {
                         if ui.button("Cancelar").clicked() {
@@ -420,132 +4297,360 @@ impl eframe::App for MyApp {
                         }
 
                         if ui.button("Sim, excluir").clicked() {
-                            self.delete_selected_item();
-                            self.selected_item_id = None;
-                            self.status_message = Some("Item excluído.".into());
-                            self.status_message_timer = None;
+                            self.delete_selected_items();
                             self.confirm_delete = false;
                         }
                     });
                 });
         }
 
+        if self.confirm_json_import.is_some() {
+            self.show_json_import_confirmation(ctx);
+        }
+
+        if self.show_staleness_settings {
+            self.show_staleness_window(ctx);
+        }
+
+        if self.show_requote {
+            self.show_requote_window(ctx);
+        }
+
+        if self.bulk.open {
+            self.show_bulk_window(ctx);
+        }
+
+        if self.batch_edit.open {
+            self.show_batch_edit_window(ctx);
+        }
+
+        if self.trash.open {
+            self.show_trash_window(ctx);
+        }
+
+        if self.audit.open {
+            self.show_audit_window(ctx);
+        }
+
+        if self.backups.open {
+            self.show_backup_window(ctx);
+        }
+
+        if self.sync.open {
+            self.show_sync_window(ctx);
+        }
+
+        if self.api.open {
+            self.show_api_window(ctx);
+        }
+
+        if self.export.open {
+            self.show_export_window(ctx);
+        }
+
+        if self.templates_view.open {
+            self.show_templates_window(ctx);
+        }
+
+        if self.paste.open {
+            self.show_paste_window(ctx);
+        }
+
+        self.show_history_window(ctx);
+        self.show_other_vendors_window(ctx);
+        self.show_vendor_window(ctx);
+
+        if self.budget.open {
+            self.show_budget_window(ctx);
+        }
+
+        if self.duplicates.open {
+            self.show_duplicates_window(ctx);
+        }
+
+        if self.shortcuts_view.open {
+            self.show_shortcuts_window(ctx);
+        }
+
+        if self.palette.open {
+            self.show_palette_window(ctx);
+        }
+
+        if self.edit_conflict.is_some() {
+            self.show_edit_conflict_window(ctx);
+        }
+
+        if self.key_conflict.is_some() {
+            self.show_key_conflict_window(ctx);
+        }
+
+        self.check_external_changes(ctx);
+
         self.handle_keyboard_shortcuts(ctx);
+        if let Some(id) = self.focus_request.take() {
+            ctx.memory_mut(|memory| memory.request_focus(id));
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.add_enabled_ui(!self.confirm_delete, |ui| {
                 ui.heading("Cadastro de Materiais Elétricos");
+                if let Some(remote) = &self.remote {
+                    ui.label(format!(
+                        "Catálogo no servidor {}",
+                        remote.catalog.base_url()
+                    ));
+                }
+                // Janelas que trabalham direto no arquivo do catálogo
+                let local = self.remote.is_none();
 
+                let mut open_similar = None;
                 egui::Grid::new("frm_cadastro")
                     .spacing([10.0, 6.0])
                     .show(ui, |ui| {
                         let desired_text_with = 400.0;
                         ui.label("Descrição:");
-                        ui.add(
-                            TextEdit::singleline(&mut self.new_description)
-                                .min_size(vec2(desired_text_with, 0.0)),
-                        );
+                        ui.horizontal(|ui| {
+                            let response = ui.add(
+                                TextEdit::singleline(&mut self.new_description)
+                                    .id(form_field("description"))
+                                    .min_size(vec2(desired_text_with, 0.0)),
+                            );
+                            if response.changed() {
+                                self.form_errors.description = None;
+                            }
+                            validation::show_error(ui, &self.form_errors.description);
+                        });
                         ui.end_row();
 
+                        // Avisa, antes de incluir, que o material já está cadastrado
+                        if self.selected_item_id.is_none() {
+                            let similar = autocomplete::similar_items(
+                                &self.items,
+                                &self.new_description,
+                                SIMILAR_ITEMS_SHOWN,
+                            );
+                            if !similar.is_empty() {
+                                ui.label("");
+                                ui.vertical(|ui| {
+                                    for item in similar {
+                                        let brand = if item.brand.is_empty() {
+                                            String::new()
+                                        } else {
+                                            format!(" [{}]", item.brand)
+                                        };
+                                        let warning = format!(
+                                            "Já existe \"{}\"{} em {} por R$ {}",
+                                            item.description,
+                                            brand,
+                                            item.vendor,
+                                            format_money(item.price)
+                                        );
+                                        if ui
+                                            .link(
+                                                egui::RichText::new(warning)
+                                                    .color(ui.visuals().warn_fg_color),
+                                            )
+                                            .on_hover_text("Abrir este item para edição")
+                                            .clicked()
+                                        {
+                                            open_similar = Some(item.id);
+                                        }
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        }
+
                         ui.label("Marca:");
+                        let suggestions =
+                            autocomplete::suggestions(&self.ranked_brands, &self.new_brand);
+                        autocomplete::completing_field(
+                            ui,
+                            form_field("brand"),
+                            &mut self.new_brand,
+                            "Sem Marca",
+                            desired_text_with,
+                            &suggestions,
+                            &mut self.completion,
+                        );
+                        ui.end_row();
+
+                        ui.label("Fornecedor:");
+                        let suggestions =
+                            autocomplete::suggestions(&self.ranked_vendors, &self.new_vendor);
+                        ui.horizontal(|ui| {
+                            let response = autocomplete::completing_field(
+                                ui,
+                                form_field("vendor"),
+                                &mut self.new_vendor,
+                                "",
+                                desired_text_with,
+                                &suggestions,
+                                &mut self.completion,
+                            );
+                            if response.changed() {
+                                self.form_errors.vendor = None;
+                            }
+                            validation::show_error(ui, &self.form_errors.vendor);
+                        });
+                        ui.end_row();
+
+                        ui.label("Preço (R$):");
+                        money::price_field(
+                            ui,
+                            form_field("price"),
+                            &mut self.new_price,
+                            &mut self.form_errors.price,
+                            desired_text_with,
+                        );
+                        ui.end_row();
+
+                        ui.label("Tags:");
                         ui.add(
-                            TextEdit::singleline(&mut self.new_brand)
-                                .hint_text("Sem Marca")
+                            TextEdit::singleline(&mut self.new_tags)
+                                .id(form_field("tags"))
+                                .hint_text("Separadas por vírgula")
                                 .min_size(vec2(desired_text_with, 0.0)),
                         );
                         ui.end_row();
 
-                        ui.label("Fornecedor:");
+                        ui.label("Categoria:");
                         ui.add(
-                            TextEdit::singleline(&mut self.new_vendor)
+                            TextEdit::singleline(&mut self.new_category)
+                                .id(form_field("category"))
+                                .hint_text("Ex.: Proteção")
                                 .min_size(vec2(desired_text_with, 0.0)),
                         );
                         ui.end_row();
 
-                        ui.label("Preço (R$):");
+                        ui.label("Unidade:");
                         ui.add(
-                            TextEdit::singleline(&mut self.new_price)
+                            TextEdit::singleline(&mut self.new_unit)
+                                .id(form_field("unit"))
+                                .hint_text("Ex.: m, pç, cx")
                                 .min_size(vec2(desired_text_with, 0.0)),
                         );
                         ui.end_row();
                     });
 
+                if let Some(id) = open_similar {
+                    self.select_item_by_id(id);
+                }
+
                 ui.horizontal(|ui| {
                     if ui.button("Adicionar").clicked() {
-                        if let Ok(price) = self.new_price.replace(",", ".").parse::<f32>() {
-                            if !self.new_description.is_empty() && !self.new_vendor.is_empty() {
-                                self.insert_item(
-                                    &self.new_description.clone(),
-                                    &self.new_brand.clone(),
-                                    &self.new_vendor.clone(),
-                                    price,
-                                );
-                                self.new_description.clear();
-                                self.new_brand.clear();
-                                self.new_vendor.clear();
-                                self.new_price.clear();
-                            } else {
-                                self.status_message =
-                                    Some("Campo de descrição ou fabricante está vazio".into());
-                                self.status_message_timer = None;
-                            }
-                        }
+                        self.add_from_form();
                     }
 
-                    if self.selected_item_id.is_some() {
-                        if ui.button("Atualizar").clicked() {
-                            if !self.new_description.is_empty() && !self.new_vendor.is_empty() {
-                                self.update_item();
-                            } else {
-                                self.status_message =
-                                    Some("Campo de descrição ou fabricante está vazio".into());
-                                self.status_message_timer = None;
-                            }
-                        }
+                    if self.selected_item_id.is_some() && ui.button("Atualizar").clicked() {
+                        self.update_item();
                     }
 
-                    if self.selected_item_id.is_some() {
-                        if ui.button("Excluir").clicked() {
-                            // self.delete_selected_item();
-                            self.confirm_delete = true;
-                        }
+                    let undo_button = ui
+                        .add_enabled(
+                            self.undo_stack.undo_label().is_some(),
+                            Button::new("Desfazer"),
+                        )
+                        .on_hover_text(format!(
+                            "{}: {}",
+                            self.shortcuts.text(Command::Undo),
+                            self.undo_stack.undo_label().unwrap_or("nada a desfazer")
+                        ));
+                    if undo_button.clicked() {
+                        self.undo();
+                    }
+                    let redo_button = ui
+                        .add_enabled(
+                            self.undo_stack.redo_label().is_some(),
+                            Button::new("Refazer"),
+                        )
+                        .on_hover_text(format!(
+                            "{}: {}",
+                            self.shortcuts.text(Command::Redo),
+                            self.undo_stack.redo_label().unwrap_or("nada a refazer")
+                        ));
+                    if redo_button.clicked() {
+                        self.redo();
                     }
 
-                    if ui.button("Importar CSV").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("CSV files", &["csv"])
-                            .pick_file()
-                        {
-                            if let Err(e) = self.import_csv_to_db(&path.to_string_lossy()) {
-                                self.status_message = Some(format!("Erro ao importar: {}", e));
-                                self.status_message_timer = None;
-                            }
-                        }
+                    if !self.selected_ids.is_empty() && ui.button("Excluir").clicked() {
+                        // self.delete_selected_item();
+                        self.confirm_delete = true;
+                    }
+
+                    if local
+                        && self.selected_ids.len() > 1
+                        && ui.button("Editar selecionados").clicked()
+                    {
+                        self.batch_edit.open = true;
+                    }
+
+                    if !self.selected_ids.is_empty() && ui.button("Copiar selecionados").clicked() {
+                        self.copy_selected_items(ctx);
+                    }
+
+                    if local && ui.button("Importar CSV").clicked() {
+                        self.run_command(ctx, Command::ImportCsv);
+                    }
+
+                    if local && ui.button("Colar do Excel").clicked() {
+                        self.run_command(ctx, Command::PasteFromSpreadsheet);
                     }
 
                     if ui.button("Exportar CSV").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("CSV", &["csv"])
-                            .set_file_name("catalogo.csv")
-                            .save_file()
-                        {
-                            if let Err(e) = self.export_to_csv(&path.to_string_lossy()) {
-                                self.status_message = Some(format!("Falha ao exportar: {}", e));
-                                self.status_message_timer = None;
-                            }
-                        }
+                        self.run_command(ctx, Command::ExportCsv);
+                    }
+
+                    if ui.button("Exportar visíveis").clicked() {
+                        self.run_command(ctx, Command::ExportVisible);
+                    }
+
+                    if local && ui.button("Importar JSON").clicked() {
+                        self.run_command(ctx, Command::ImportJson);
+                    }
+
+                    if local && ui.button("Exportar JSON").clicked() {
+                        self.run_command(ctx, Command::ExportJson);
+                    }
+
+                    if ui
+                        .checkbox(&mut self.filter.outdated_only, "Exibir desatualizados")
+                        .clicked()
+                    {
+                        self.clear_selection();
                     }
 
                     if ui
-                        .checkbox(&mut self.show_outdated, "Exibir desatualizados")
+                        .button("Comandos")
+                        .on_hover_text(self.shortcuts.text(Command::Palette))
                         .clicked()
                     {
-                        if self.show_outdated {
-                            self.load_outdated_items();
-                        } else {
-                            self.load_items();
+                        self.run_command(ctx, Command::Palette);
+                    }
+
+                    if ui.button("Atalhos").clicked() {
+                        self.run_command(ctx, Command::Shortcuts);
+                    }
+
+                    if !local {
+                        return;
+                    }
+
+                    for command in [
+                        Command::Staleness,
+                        Command::Requote,
+                        Command::BulkAdjust,
+                        Command::Trash,
+                        Command::Backups,
+                        Command::Budget,
+                        Command::Sync,
+                        Command::LocalApi,
+                        Command::Audit,
+                        Command::Duplicates,
+                    ] {
+                        if ui.button(command.label()).clicked() {
+                            self.run_command(ctx, command);
                         }
-                        self.selected_item_id = None;
                     }
                 });
 
@@ -553,154 +4658,329 @@ impl eframe::App for MyApp {
                 ui.horizontal(|ui| {
                     ui.label("Buscar:");
                     ui.add(
-                        TextEdit::singleline(&mut self.search_query)
+                        TextEdit::singleline(&mut self.filter.query)
+                            .id(egui::Id::new(SEARCH_FIELD))
                             .hint_text("Item, fornecedor ou marca")
                             .min_size(vec2(300.0, 0.0)),
                     );
                     if ui.button("Limpar Pesquisa").clicked() {
-                        self.search_query.clear();
+                        self.filter.clear();
                     }
                 });
 
-                ui.label("Itens Cadastrados:");
+                egui::CollapsingHeader::new("Filtros avançados").show(ui, |ui| {
+                    egui::Grid::new("frm_filtros")
+                        .spacing([10.0, 6.0])
+                        .show(ui, |ui| {
+                            ui.label("Fornecedores:");
+                            filters::multi_select_menu(
+                                ui,
+                                &self.all_vendors,
+                                &mut self.filter.vendors,
+                            );
+                            ui.end_row();
 
-                if self.search_query != self.last_search_query {
-                    self.last_search_query = self.search_query.clone();
+                            ui.label("Marcas:");
+                            filters::multi_select_menu(
+                                ui,
+                                &self.all_brands,
+                                &mut self.filter.brands,
+                            );
+                            ui.end_row();
 
-                    let search = self.search_query.to_lowercase();
+                            ui.label("Tags:");
+                            filters::multi_select_menu(ui, &self.all_tags, &mut self.filter.tags);
+                            ui.end_row();
 
-                    self.visible_items = if search.is_empty() {
-                        self.items.clone()
-                    } else {
-                        self.items
-                            .iter()
-                            .filter(|item| {
-                                item.description.to_lowercase().contains(&search)
-                                    || item.vendor.to_lowercase().contains(&search)
-                                    || item.brand.to_lowercase().contains(&search)
-                            })
-                            .cloned()
-                            .collect()
-                    };
+                            ui.label("Preço (R$):");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    TextEdit::singleline(&mut self.filter.min_price)
+                                        .hint_text("mín.")
+                                        .desired_width(80.0),
+                                );
+                                ui.label("a");
+                                ui.add(
+                                    TextEdit::singleline(&mut self.filter.max_price)
+                                        .hint_text("máx.")
+                                        .desired_width(80.0),
+                                );
+                            });
+                            ui.end_row();
+
+                            ui.label("Atualizado entre:");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    TextEdit::singleline(&mut self.filter.updated_from)
+                                        .hint_text("AAAA-MM-DD")
+                                        .desired_width(90.0),
+                                );
+                                ui.label("e");
+                                ui.add(
+                                    TextEdit::singleline(&mut self.filter.updated_to)
+                                        .hint_text("AAAA-MM-DD")
+                                        .desired_width(90.0),
+                                );
+                            });
+                            ui.end_row();
+
+                            ui.label("Idade do preço:");
+                            egui::ComboBox::from_id_salt("filtro_idade")
+                                .selected_text(
+                                    self.filter
+                                        .age
+                                        .map_or("Qualquer", filters::AgeBucket::label),
+                                )
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.filter.age, None, "Qualquer");
+                                    for bucket in filters::AgeBucket::ALL {
+                                        ui.selectable_value(
+                                            &mut self.filter.age,
+                                            Some(bucket),
+                                            bucket.label(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                        });
+
+                    for error in self.filter.errors() {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                });
+
+                if local {
+                    ui.horizontal(|ui| {
+                        ui.label("Filtros salvos:");
+                        let mut chosen = None;
+                        egui::ComboBox::from_id_salt("filtros_salvos")
+                            .selected_text("Selecionar...")
+                            .show_ui(ui, |ui| {
+                                for filter in &self.saved_filters {
+                                    if ui.selectable_label(false, &filter.name).clicked() {
+                                        chosen = Some(filter.clone());
+                                    }
+                                }
+                            });
+                        if let Some(filter) = chosen {
+                            self.apply_saved_filter(&filter);
+                        }
+                        ui.add(
+                            TextEdit::singleline(&mut self.new_filter_name)
+                                .hint_text("Nome do filtro")
+                                .min_size(vec2(150.0, 0.0)),
+                        );
+                        if ui.button("Salvar filtro").clicked() {
+                            self.save_current_filter();
+                        }
+                        if ui.button("Excluir filtro").clicked() {
+                            self.delete_current_filter();
+                        }
+                    });
+                }
+
+                if self.last_filter.as_ref() != Some(&self.filter) {
+                    self.refresh_visible_items();
                 }
 
+                ui.label(format!(
+                    "Itens Cadastrados: {} de {}",
+                    self.visible_items.len(),
+                    self.items.len()
+                ));
+
                 let row_height = 24.0;
                 let total_rows = self.visible_items.len();
+                let mut clicked_row = None;
+                let mut row_action: Option<(usize, RowAction)> = None;
 
-                egui::ScrollArea::vertical()
-                    .auto_shrink([false; 2])
-                    .show_rows(ui, row_height, total_rows, |ui, row_range| {
-                        ui.set_width(ui.available_width());
-                        for row in row_range {
-                            if let Some(item) = self.visible_items.get(row) {
-                                let is_selected = Some(item.id) == self.selected_item_id;
-                                let price_str = format_money(item.price);
-                                let brand_str = if !item.brand.is_empty() {
-                                    format!(" [{}]", item.brand)
+                let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false; 2]);
+                // Linha fora da tela: rola até ela para que seja desenhada
+                if let Some(row) = self.scroll_to_row
+                    && !self.visible_rows.contains(&row)
+                {
+                    let spacing = ui.spacing().item_spacing.y;
+                    scroll_area =
+                        scroll_area.vertical_scroll_offset(row as f32 * (row_height + spacing));
+                }
+                scroll_area.show_rows(ui, row_height, total_rows, |ui, row_range| {
+                    ui.set_width(ui.available_width());
+                    self.visible_rows = row_range.clone();
+                    for row in row_range {
+                        if let Some(item) = self.visible_items.get(row) {
+                            let is_selected = self.selected_ids.contains(&item.id);
+                            let price_str = format_money(item.price);
+                            let brand_str = if !item.brand.is_empty() {
+                                format!(" [{}]", item.brand)
+                            } else {
+                                "".to_string()
+                            };
+                            let tags_str: String =
+                                item.tags.iter().map(|t| format!(" #{}", t)).collect();
+                            let age = staleness::age_in_days(&item.updated_at);
+                            let age_str = age
+                                .map(|days| format!(" ({})", staleness::format_age(days)))
+                                .unwrap_or_default();
+                            let label = format!(
+                                "[{}]{} {} R$ {} {}{}{}",
+                                item.vendor,
+                                brand_str,
+                                item.description,
+                                price_str,
+                                item.updated_at,
+                                age_str,
+                                tags_str
+                            );
+                            let mut label = egui::RichText::new(label);
+                            if let Some(days) = age {
+                                let threshold =
+                                    self.staleness.threshold_for(&item.vendor, &item.category);
+                                label = label
+                                    .color(staleness::Freshness::classify(days, threshold).color());
+                            }
+
+                            let selectable_label_response = ui.add(
+                                Button::new(label)
+                                    .selected(is_selected)
+                                    .min_size(vec2(row_height, 0.0)),
+                            );
+                            // ui.selectable_label(is_selected, &label);
+                            if selectable_label_response.clicked_by(egui::PointerButton::Primary) {
+                                clicked_row = Some(row);
+                            }
+                            if self.scroll_to_row == Some(row) {
+                                selectable_label_response.scroll_to_me(None);
+                                self.scroll_to_row = None;
+                            }
+
+                            selectable_label_response.context_menu(|ui| {
+                                let in_selection = is_selected && self.selected_ids.len() > 1;
+                                let mut action = None;
+                                let copy_label = if in_selection {
+                                    format!("Copiar {} selecionados", self.selected_ids.len())
                                 } else {
-                                    "".to_string()
+                                    "Copiar".to_string()
                                 };
-                                let label = format!(
-                                    "[{}]{} {} R$ {} {}",
-                                    item.vendor,
-                                    brand_str,
-                                    item.description,
-                                    price_str,
-                                    item.updated_at
-                                );
-
-                                let selectable_label_response = ui.add(
-                                    Button::new(&label)
-                                        .selected(is_selected)
-                                        .min_size(vec2(row_height, 0.0)),
-                                );
-                                // ui.selectable_label(is_selected, &label);
-                                if selectable_label_response
-                                    .clicked_by(egui::PointerButton::Primary)
-                                {
-                                    if self.selected_item_id == Some(item.id) {
-                                        self.selected_item_id = None; // unselect if clicked again
-                                        self.new_description.clear();
-                                        self.new_brand.clear();
-                                        self.new_vendor.clear();
-                                        self.new_price.clear();
+                                if ui.button(copy_label).clicked() {
+                                    action = Some(RowAction::Copy(None));
+                                }
+                                ui.menu_button("Copiar como", |ui| {
+                                    for template in &self.copy_templates {
+                                        if ui.button(&template.name).clicked() {
+                                            action =
+                                                Some(RowAction::Copy(Some(template.name.clone())));
+                                        }
+                                    }
+                                    ui.separator();
+                                    if ui.button("Modelos de cópia...").clicked() {
+                                        action = Some(RowAction::CopyTemplates);
+                                    }
+                                });
+                                ui.separator();
+                                if ui.button("Editar").clicked() {
+                                    action = Some(RowAction::Edit);
+                                }
+                                if ui.button("Duplicar como novo item").clicked() {
+                                    action = Some(RowAction::Duplicate);
+                                }
+                                let delete_label = if in_selection {
+                                    format!("Excluir {} selecionados", self.selected_ids.len())
+                                } else {
+                                    "Excluir".to_string()
+                                };
+                                if ui.button(delete_label).clicked() {
+                                    action = Some(RowAction::Delete);
+                                }
+                                ui.separator();
+                                if ui.button("Histórico de preços").clicked() {
+                                    action = Some(RowAction::PriceHistory);
+                                }
+                                if ui.button("Outros fornecedores").clicked() {
+                                    action = Some(RowAction::OtherVendors);
+                                }
+                                if local {
+                                    let budget_label = if in_selection {
+                                        format!(
+                                            "Incluir {} selecionados no orçamento",
+                                            self.selected_ids.len()
+                                        )
                                     } else {
-                                        self.selected_item_id = Some(item.id); // select item
-                                        self.new_description = item.description.clone();
-                                        self.new_brand = item.brand.clone();
-                                        self.new_vendor = item.vendor.clone();
-                                        self.new_price = price_str.clone().replace(".", "");
+                                        "Incluir no orçamento".to_string()
+                                    };
+                                    if ui.button(budget_label).clicked() {
+                                        action = Some(RowAction::AddToBudget);
+                                    }
+                                    if ui.button("Dados do fornecedor").clicked() {
+                                        action = Some(RowAction::VendorDetails);
                                     }
                                 }
-
-                                if selectable_label_response
-                                    .clicked_by(egui::PointerButton::Secondary)
-                                {
-                                    let label_to_copy = format!(
-                                        "{} {}\t\t\t\t{}\t{}",
-                                        item.description, item.brand, item.vendor, price_str
-                                    );
-                                    ctx.copy_text(label_to_copy);
-                                    self.status_message =
-                                        Some("Copiado para a área de transferência".into());
-                                    self.status_message_timer = None;
-                                    // self.copied_feedback_timer = Some(std::time::Instant::now());
+                                if let Some(action) = action {
+                                    row_action = Some((row, action));
+                                    ui.close_menu();
                                 }
-                            }
+                            });
                         }
-                        // for item in self.items.iter().filter(|item| {
-                        //     item.description.to_lowercase().contains(&search)
-                        //         || item.vendor.to_lowercase().contains(&search)
-                        //         || item.brand.to_lowercase().contains(&search)
-                        // }) {
-                        //     let is_selected = Some(item.id) == self.selected_item_id;
-                        //     let price_str = format_money(item.price);
-                        //     let brand_str = if !item.brand.is_empty() {
-                        //         format!(" [{}]", item.brand)
-                        //     } else {
-                        //         "".to_string()
-                        //     };
-                        //     let label = format!(
-                        //         "[{}]{} {} R$ {} {}",
-                        //         item.vendor,
-                        //         brand_str,
-                        //         item.description,
-                        //         price_str,
-                        //         item.updated_at
-                        //     );
-
-                        //     let selectable_label_response =
-                        //         ui.selectable_label(is_selected, &label);
-                        //     if selectable_label_response.clicked_by(egui::PointerButton::Primary) {
-                        //         if self.selected_item_id == Some(item.id) {
-                        //             self.selected_item_id = None; // unselect if clicked again
-                        //             self.new_description.clear();
-                        //             self.new_brand.clear();
-                        //             self.new_vendor.clear();
-                        //             self.new_price.clear();
-                        //         } else {
-                        //             self.selected_item_id = Some(item.id); // select item
-                        //             self.new_description = item.description.clone();
-                        //             self.new_brand = item.brand.clone();
-                        //             self.new_vendor = item.vendor.clone();
-                        //             self.new_price = price_str.clone().replace(".", "");
-                        //         }
-                        //     }
-
-                        //     if selectable_label_response.clicked_by(egui::PointerButton::Secondary)
-                        //     {
-                        //         let label_to_copy = format!(
-                        //             "{} {}\t\t\t\t{}\t{}",
-                        //             item.description, item.brand, item.vendor, price_str
-                        //         );
-                        //         ctx.copy_text(label_to_copy);
-                        //         self.status_message =
-                        //             Some("Copiado para a área de transferência".into());
-                        //         self.status_message_timer = None;
-                        //         // self.copied_feedback_timer = Some(std::time::Instant::now());
-                        //     }
-                        // }
-                    });
+                    }
+                    // for item in self.items.iter().filter(|item| {
+                    //     item.description.to_lowercase().contains(&search)
+                    //         || item.vendor.to_lowercase().contains(&search)
+                    //         || item.brand.to_lowercase().contains(&search)
+                    // }) {
+                    //     let is_selected = Some(item.id) == self.selected_item_id;
+                    //     let price_str = format_money(item.price);
+                    //     let brand_str = if !item.brand.is_empty() {
+                    //         format!(" [{}]", item.brand)
+                    //     } else {
+                    //         "".to_string()
+                    //     };
+                    //     let label = format!(
+                    //         "[{}]{} {} R$ {} {}",
+                    //         item.vendor,
+                    //         brand_str,
+                    //         item.description,
+                    //         price_str,
+                    //         item.updated_at
+                    //     );
+
+                    //     let selectable_label_response =
+                    //         ui.selectable_label(is_selected, &label);
+                    //     if selectable_label_response.clicked_by(egui::PointerButton::Primary) {
+                    //         if self.selected_item_id == Some(item.id) {
+                    //             self.selected_item_id = None; // unselect if clicked again
+                    //             self.new_description.clear();
+                    //             self.new_brand.clear();
+                    //             self.new_vendor.clear();
+                    //             self.new_price.clear();
+                    //         } else {
+                    //             self.selected_item_id = Some(item.id); // select item
+                    //             self.new_description = item.description.clone();
+                    //             self.new_brand = item.brand.clone();
+                    //             self.new_vendor = item.vendor.clone();
+                    //             self.new_price = price_str.clone().replace(".", "");
+                    //         }
+                    //     }
+
+                    //     if selectable_label_response.clicked_by(egui::PointerButton::Secondary)
+                    //     {
+                    //         let label_to_copy = format!(
+                    //             "{} {}\t\t\t\t{}\t{}",
+                    //             item.description, item.brand, item.vendor, price_str
+                    //         );
+                    //         ctx.copy_text(label_to_copy);
+                    //         self.status_message =
+                    //             Some("Copiado para a área de transferência".into());
+                    //         self.status_message_timer = None;
+                    //         // self.copied_feedback_timer = Some(std::time::Instant::now());
+                    //     }
+                    // }
+                });
+                if let Some(row) = clicked_row {
+                    let modifiers = ui.input(|i| i.modifiers);
+                    self.click_row(row, modifiers);
+                }
+                if let Some((row, action)) = row_action {
+                    self.apply_row_action(ctx, row, action);
+                }
                 // if let Some(t) = self.copied_feedback_timer {
                 //     if t.elapsed().as_secs_f32() < 0.2 {
                 //         if let Some(pos) = ctx.input(|i| i.pointer.hover_pos()) {
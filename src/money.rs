@@ -0,0 +1,215 @@
+use std::fmt;
+
+use eframe::egui::{Id, Response, TextEdit, Ui, vec2};
+use num_format::{Locale, ToFormattedString};
+
+/// Valor no formato brasileiro, com separador de milhar: 1.234,50.
+pub fn format_money(valor: f32) -> String {
+    // Arredonda para centavos antes de separar, para 1,999 virar 2,00
+    let centavos_total = (valor as f64 * 100.0).round() as i64;
+    let sinal = if centavos_total < 0 { "-" } else { "" };
+    let centavos_total = centavos_total.unsigned_abs();
+    format!(
+        "{}{},{:02}",
+        sinal,
+        (centavos_total / 100).to_formatted_string(&Locale::de),
+        centavos_total % 100
+    )
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MoneyError {
+    Empty,
+    Invalid,
+    TooManyDecimals,
+    Negative,
+    Zero,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MoneyError::Empty => "Informe o preço",
+            MoneyError::Invalid => "Valor inválido; use o formato 1.234,56",
+            MoneyError::TooManyDecimals => "Use no máximo duas casas decimais",
+            MoneyError::Negative => "O preço não pode ser negativo",
+            MoneyError::Zero => "O preço deve ser maior que zero",
+        })
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// Lê um valor digitado como "1.234,56", "1234,56", "R$ 10", "10.5" ou
+/// "1.234". Com vírgula, ela é a separação dos centavos e os pontos são de
+/// milhar; sem vírgula, um ponto seguido de um ou dois dígitos é a dos
+/// centavos. Aceita um sinal, antes ou depois do "R$".
+pub fn parse_amount(text: &str) -> Result<f64, MoneyError> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\u{a0}')
+        .collect();
+    if text.is_empty() {
+        return Err(MoneyError::Empty);
+    }
+    // Um "R$" e um sinal, em qualquer ordem
+    fn currency(text: &str) -> Option<&str> {
+        text.strip_prefix("R$").or_else(|| text.strip_prefix("r$"))
+    }
+    let mut rest = text.as_str();
+    let has_currency = currency(rest).is_some();
+    rest = currency(rest).unwrap_or(rest);
+    let negative = rest.starts_with('-');
+    rest = rest.strip_prefix(['-', '+']).unwrap_or(rest);
+    if !has_currency {
+        rest = currency(rest).unwrap_or(rest);
+    }
+    let text = rest;
+    if text.is_empty()
+        || !text
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    {
+        return Err(MoneyError::Invalid);
+    }
+
+    let (integer, decimals) = match text.split_once(',') {
+        Some((integer, decimals)) => (integer, decimals),
+        None => match text.split_once('.') {
+            Some((integer, decimals)) if !decimals.contains('.') && decimals.len() != 3 => {
+                (integer, decimals)
+            }
+            _ => (text, ""),
+        },
+    };
+    if decimals.contains([',', '.']) {
+        return Err(MoneyError::Invalid);
+    }
+    if decimals.len() > 2 {
+        return Err(MoneyError::TooManyDecimals);
+    }
+
+    // Pontos de milhar: o primeiro grupo com 1 a 3 dígitos e os outros com 3
+    let groups: Vec<&str> = integer.split('.').collect();
+    let grouped = groups.len() > 1;
+    let valid_groups = groups.iter().enumerate().all(|(i, group)| match i {
+        0 if grouped => (1..=3).contains(&group.len()),
+        0 => true,
+        _ => group.len() == 3,
+    });
+    if !valid_groups || (integer.is_empty() && decimals.is_empty()) {
+        return Err(MoneyError::Invalid);
+    }
+
+    let digits = format!("{}.{}", groups.concat(), decimals);
+    let value: f64 = format!("0{}0", digits)
+        .parse()
+        .map_err(|_| MoneyError::Invalid)?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Preço de um item: como `parse_amount`, mas só valores maiores que zero.
+pub fn parse_price(text: &str) -> Result<f32, MoneyError> {
+    let value = parse_amount(text)?;
+    if value < 0.0 {
+        return Err(MoneyError::Negative);
+    }
+    if value == 0.0 {
+        return Err(MoneyError::Zero);
+    }
+    Ok(value as f32)
+}
+
+/// Campo de preço: aceita só dígitos, vírgula, ponto, sinal e "R$", mostra o
+/// erro ao lado enquanto o valor é inválido e, ao sair do campo, reescreve o
+/// valor como 1.234,56. `error`, vindo da validação do formulário, aparece
+/// no lugar do erro do valor até o campo ser alterado.
+pub fn price_field(
+    ui: &mut Ui,
+    id: Id,
+    text: &mut String,
+    error: &mut Option<String>,
+    width: f32,
+) -> Response {
+    ui.horizontal(|ui| {
+        let response = ui.add(
+            TextEdit::singleline(text)
+                .id(id)
+                .hint_text("0,00")
+                .min_size(vec2(width, 0.0)),
+        );
+        if response.changed() {
+            *error = None;
+            text.retain(|c| c.is_ascii_digit() || matches!(c, ',' | '.' | '-' | 'R' | '$' | ' '));
+        }
+        if response.lost_focus()
+            && let Ok(price) = parse_price(text)
+        {
+            *text = format_money(price);
+        }
+        if let Some(error) = error {
+            ui.colored_label(ui.visuals().error_fg_color, error.as_str());
+        } else if !text.trim().is_empty()
+            && let Err(e) = parse_price(text)
+        {
+            ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+        }
+        response
+    })
+    .inner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MoneyError, format_money, parse_amount, parse_price};
+
+    #[test]
+    fn formats_with_thousands_and_rounding() {
+        assert_eq!(format_money(1234.5), "1.234,50");
+        assert_eq!(format_money(0.0), "0,00");
+        assert_eq!(format_money(1.999), "2,00");
+        assert_eq!(format_money(1234567.0), "1.234.567,00");
+        assert_eq!(format_money(-3.5), "-3,50");
+    }
+
+    #[test]
+    fn parses_brazilian_formats() {
+        assert_eq!(parse_amount("1.234,56"), Ok(1234.56));
+        assert_eq!(parse_amount("1234,56"), Ok(1234.56));
+        assert_eq!(parse_amount("R$ 10"), Ok(10.0));
+        assert_eq!(parse_amount("r$1.234"), Ok(1234.0));
+        assert_eq!(parse_amount("10.5"), Ok(10.5));
+        assert_eq!(parse_amount("1234.56"), Ok(1234.56));
+        assert_eq!(parse_amount("1.234.567,8"), Ok(1234567.8));
+        assert_eq!(parse_amount(",50"), Ok(0.5));
+        assert_eq!(parse_amount(" 1 234,00 "), Ok(1234.0));
+        assert_eq!(parse_amount("-R$ 3,50"), Ok(-3.5));
+        assert_eq!(parse_amount("R$ -3,50"), Ok(-3.5));
+        assert_eq!(parse_amount("+8"), Ok(8.0));
+    }
+
+    #[test]
+    fn round_trips_formatted_values() {
+        for value in [0.01f32, 3.45, 999.99, 1234.5, 98765.43] {
+            assert_eq!(parse_price(&format_money(value)), Ok(value));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(parse_amount(""), Err(MoneyError::Empty));
+        assert_eq!(parse_amount("abc"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("12,34,5"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("1.23.4"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("12.34,5.6"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("1234.567,00"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("R$"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("--10"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("-+10"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("-R$ -10"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("R$ R$ 10"), Err(MoneyError::Invalid));
+        assert_eq!(parse_amount("3,455"), Err(MoneyError::TooManyDecimals));
+        assert_eq!(parse_price("-10"), Err(MoneyError::Negative));
+        assert_eq!(parse_price("0,00"), Err(MoneyError::Zero));
+    }
+}
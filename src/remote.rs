@@ -0,0 +1,245 @@
+use std::{
+    error::Error,
+    sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use eframe::egui;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{
+    InfraItem, audit, filters,
+    history::PriceRecord,
+    items::{ItemFields, ItemWithHistory, UpdateOutcome},
+    server::{self, Facets},
+    staleness,
+};
+
+/// Tempo máximo de uma requisição. Maior que a espera de `/api/changes`.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(40);
+
+/// Espera antes de tentar de novo quando o servidor não responde.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Catálogo acessado pelo servidor (`catalogo serve`) em vez de um arquivo.
+#[derive(Clone)]
+pub struct RemoteCatalog {
+    base_url: String,
+    agent: ureq::Agent,
+    user: String,
+    machine: String,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Revision {
+    revision: i64,
+}
+
+#[derive(Deserialize)]
+struct Deleted {
+    deleted: usize,
+}
+
+impl RemoteCatalog {
+    pub fn new(base_url: &str) -> Self {
+        RemoteCatalog {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            user: server::encode_header_value(&crate::current_user()),
+            machine: server::encode_header_value(&audit::machine_name()),
+            token: None,
+        }
+    }
+
+    /// Envia o token exigido pelo servidor iniciado com `--token`.
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token.filter(|token| !token.is_empty());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self
+            .agent
+            .request(method, &format!("{}{}", self.base_url, path))
+            .set(server::USER_HEADER, &self.user)
+            .set(server::MACHINE_HEADER, &self.machine);
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Box<dyn Error>> {
+        Ok(self
+            .request("GET", path)
+            .call()
+            .map_err(describe)?
+            .into_json()?)
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Value,
+    ) -> Result<T, Box<dyn Error>> {
+        Ok(self
+            .request(method, path)
+            .send_json(body)
+            .map_err(describe)?
+            .into_json()?)
+    }
+
+    pub fn load_items(&self) -> Result<Vec<InfraItem>, Box<dyn Error>> {
+        self.get("/api/items")
+    }
+
+    pub fn query_items(
+        &self,
+        filter: &filters::ItemFilter,
+    ) -> Result<Vec<InfraItem>, Box<dyn Error>> {
+        self.send("POST", "/api/items/query", json!(filter))
+    }
+
+    pub fn load_facets(&self) -> Result<Facets, Box<dyn Error>> {
+        self.get("/api/facets")
+    }
+
+    pub fn load_staleness(&self) -> Result<staleness::StalenessConfig, Box<dyn Error>> {
+        self.get("/api/staleness")
+    }
+
+    pub fn insert_item(&self, fields: &ItemFields) -> Result<InfraItem, Box<dyn Error>> {
+        self.send("POST", "/api/items", json!(fields))
+    }
+
+    pub fn update_item(
+        &self,
+        id: i32,
+        version: i64,
+        fields: &ItemFields,
+    ) -> Result<UpdateOutcome, Box<dyn Error>> {
+        let mut body = json!(fields);
+        body["version"] = json!(version);
+        let response = self
+            .request("PUT", &format!("/api/items/{}", id))
+            .send_json(body);
+        match response {
+            Ok(_) => Ok(UpdateOutcome::Updated),
+            Err(ureq::Error::Status(404, _)) => Ok(UpdateOutcome::Deleted),
+            Err(ureq::Error::Status(409, response)) => {
+                let mut body: Value = response.into_json()?;
+                Ok(UpdateOutcome::Conflict(serde_json::from_value(
+                    body["current"].take(),
+                )?))
+            }
+            Err(e) => Err(describe(e).into()),
+        }
+    }
+
+    pub fn load_history(&self, id: i32) -> Result<Vec<PriceRecord>, Box<dyn Error>> {
+        let item: ItemWithHistory = self.get(&format!("/api/items/{}/history", id))?;
+        Ok(item.history)
+    }
+
+    /// Move os itens para a lixeira. Devolve quantos foram movidos.
+    pub fn delete_items(&self, ids: &[i32]) -> Result<usize, Box<dyn Error>> {
+        let deleted: Deleted = self.send("POST", "/api/items/delete", json!({ "ids": ids }))?;
+        Ok(deleted.deleted)
+    }
+
+    /// Espera o servidor avisar que o catálogo mudou desde a revisão `since`
+    /// (ou o tempo de espera acabar) e devolve a revisão atual.
+    pub fn wait_for_change(&self, since: i64) -> Result<i64, Box<dyn Error>> {
+        let revision: Revision = self.get(&format!("/api/changes?since={}", since))?;
+        Ok(revision.revision)
+    }
+}
+
+/// Catálogo remoto aberto no aplicativo, com a revisão mais recente avisada
+/// pelo servidor.
+pub struct RemoteSession {
+    pub catalog: RemoteCatalog,
+    revision: Arc<AtomicI64>,
+}
+
+impl RemoteSession {
+    /// Confere se o servidor responde e passa a acompanhar as alterações em
+    /// outra thread, redesenhando a janela assim que alguém grava.
+    pub fn connect(base_url: &str, ctx: &egui::Context) -> Result<Self, Box<dyn Error>> {
+        let catalog =
+            RemoteCatalog::new(base_url).with_token(std::env::var(server::TOKEN_ENV).ok());
+        let revision = Arc::new(AtomicI64::new(catalog.wait_for_change(-1)?));
+        let watched = Arc::clone(&revision);
+        let watcher = catalog.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            // Termina quando o aplicativo descarta o catálogo
+            while Arc::strong_count(&watched) > 1 {
+                match watcher.wait_for_change(watched.load(Ordering::Relaxed)) {
+                    Ok(current) => {
+                        if watched.swap(current, Ordering::Relaxed) != current {
+                            ctx.request_repaint();
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Sem resposta do servidor {}: {}", watcher.base_url, e);
+                        thread::sleep(RETRY_INTERVAL);
+                    }
+                }
+            }
+        });
+        Ok(RemoteSession { catalog, revision })
+    }
+
+    pub fn revision(&self) -> i64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+}
+
+/// Mensagem de erro com o texto devolvido pelo servidor, quando houver.
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let message = response
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            format!("o servidor respondeu {}: {}", status, message)
+        }
+        ureq::Error::Transport(transport) => {
+            format!("não foi possível falar com o servidor: {}", transport)
+        }
+    }
+}
+
+/// Tela exibida quando o servidor não responde ao abrir o aplicativo.
+/// Devolve `true` quando o usuário pede para tentar de novo.
+pub fn show_connection_error(ctx: &egui::Context, base_url: &str, details: &str) -> bool {
+    let mut retry = false;
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("Não foi possível conectar ao servidor do catálogo");
+        ui.add_space(8.0);
+        ui.label(format!("Servidor: {}", base_url));
+        ui.label(
+            "Verifique se o servidor está rodando (catalogo serve) e se este computador \
+             alcança o endereço informado.",
+        );
+        ui.add_space(4.0);
+        ui.label(egui::RichText::new(details).monospace().weak());
+        ui.add_space(12.0);
+        retry = ui.button("Tentar novamente").clicked();
+    });
+    retry
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs::File,
+    io::{BufReader, Write},
+    path::Path,
+};
+
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{InfraItem, filters, find_item_id, format_money, history, money};
+
+const TEMPLATE_HEADER: [&str; 7] = [
+    "id",
+    "descrição",
+    "marca",
+    "fornecedor",
+    "preço atual",
+    "última atualização",
+    "novo preço",
+];
+
+pub struct VendorQuote {
+    pub vendor: String,
+    pub items: Vec<InfraItem>,
+}
+
+/// Itens desatualizados agrupados por fornecedor.
+pub fn stale_items_by_vendor(conn: &Connection) -> rusqlite::Result<Vec<VendorQuote>> {
+    let filter = filters::ItemFilter {
+        outdated_only: true,
+        ..Default::default()
+    };
+    let mut items = filters::query_items(conn, &filter)?;
+    items.sort_by(|a, b| {
+        a.vendor
+            .to_lowercase()
+            .cmp(&b.vendor.to_lowercase())
+            .then_with(|| a.description.cmp(&b.description))
+    });
+
+    let mut groups: Vec<VendorQuote> = vec![];
+    for item in items {
+        match groups.last_mut() {
+            Some(group) if group.vendor == item.vendor => group.items.push(item),
+            _ => groups.push(VendorQuote {
+                vendor: item.vendor.clone(),
+                items: vec![item],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+/// Texto de pedido de cotação para enviar por e-mail ou WhatsApp.
+pub fn quote_message(quote: &VendorQuote) -> String {
+    let mut text = format!(
+        "Olá, {}!\n\nPoderiam nos enviar a cotação atualizada dos itens abaixo?\n\n",
+        quote.vendor
+    );
+    for (index, item) in quote.items.iter().enumerate() {
+        let brand = if item.brand.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", item.brand)
+        };
+        text.push_str(&format!(
+            "{}. {}{} - último preço R$ {}\n",
+            index + 1,
+            item.description,
+            brand,
+            format_money(item.price)
+        ));
+    }
+    text.push_str("\nObrigado!");
+    text
+}
+
+/// Planilha CSV com a lista de itens e a coluna "novo preço" em branco.
+pub fn write_quote_template(
+    path: &Path,
+    quote: &VendorQuote,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(b"\xEF\xBB\xBF")?;
+    let mut wtr = csv::WriterBuilder::new().delimiter(b';').from_writer(file);
+
+    wtr.write_record(TEMPLATE_HEADER)?;
+    for item in &quote.items {
+        let preco = format!("{:.2}", item.price).replace(".", ",");
+        wtr.write_record([
+            &item.id.to_string(),
+            &item.description,
+            &item.brand,
+            &item.vendor,
+            &preco,
+            &item.updated_at,
+            "",
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Nome de arquivo seguro para a planilha de um fornecedor.
+pub fn template_file_name(vendor: &str) -> String {
+    let name: String = vendor
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("cotacao_{}.csv", name)
+}
+
+#[derive(Default)]
+pub struct QuoteImportSummary {
+    pub updated: usize,
+    pub blank: usize,
+    pub not_found: Vec<usize>,
+    /// Linhas cujo id é de um item com outra descrição ou outro fornecedor,
+    /// como numa planilha feita a partir de outro catálogo.
+    pub mismatched: Vec<usize>,
+    pub invalid: Vec<usize>,
+}
+
+impl QuoteImportSummary {
+    pub fn message(&self) -> String {
+        let mut message = format!("{} preço(s) atualizado(s).", self.updated);
+        if self.blank > 0 {
+            message.push_str(&format!("\n{} linha(s) sem novo preço.", self.blank));
+        }
+        if !self.not_found.is_empty() {
+            message.push_str(&format!(
+                "\nItens não encontrados nas linhas: {}",
+                join_lines(&self.not_found)
+            ));
+        }
+        if !self.mismatched.is_empty() {
+            message.push_str(&format!(
+                "\nDescrição ou fornecedor diferentes do item com o mesmo id nas linhas: {}",
+                join_lines(&self.mismatched)
+            ));
+        }
+        if !self.invalid.is_empty() {
+            message.push_str(&format!(
+                "\nPreço inválido nas linhas: {}",
+                join_lines(&self.invalid)
+            ));
+        }
+        message
+    }
+}
+
+fn join_lines(lines: &[usize]) -> String {
+    lines
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Descrição e fornecedor do item, se ele existe e não está na lixeira.
+fn active_item(conn: &Connection, id: i32) -> rusqlite::Result<Option<(String, String)>> {
+    conn.query_row(
+        "SELECT description, vendor FROM infra_item WHERE id = ?1 AND deleted_at IS NULL",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Lê a planilha de cotação preenchida e atualiza preços e histórico.
+/// Cada linha é associada ao item pelo `id`, se a descrição e o fornecedor
+/// da linha forem os do item; senão, tenta pela combinação descrição +
+/// marca + fornecedor. Itens na lixeira não recebem preço de cotação.
+pub fn import_quote_template(
+    conn: &mut Connection,
+    path: &Path,
+) -> Result<QuoteImportSummary, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .has_headers(true)
+        .from_reader(BufReader::new(file));
+
+    let headers: Vec<String> = rdr
+        .headers()?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let id_col = column(TEMPLATE_HEADER[0]);
+    let description_col = column(TEMPLATE_HEADER[1]);
+    let brand_col = column(TEMPLATE_HEADER[2]);
+    let vendor_col = column(TEMPLATE_HEADER[3]);
+    let price_col = column(TEMPLATE_HEADER[6]).ok_or("Coluna \"novo preço\" não encontrada")?;
+
+    let now = Utc::now().format("%Y-%m-%d").to_string();
+    let mut summary = QuoteImportSummary::default();
+    let tx = conn.savepoint()?;
+
+    for (index, result) in rdr.records().enumerate() {
+        let record = result?;
+        let line = index + 2;
+        let field = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or("").trim();
+
+        let price_str = field(Some(price_col));
+        if price_str.is_empty() {
+            summary.blank += 1;
+            continue;
+        }
+        let price: f32 = match money::parse_price(price_str) {
+            Ok(p) => p,
+            Err(_) => {
+                summary.invalid.push(line);
+                continue;
+            }
+        };
+
+        // Colunas ausentes na planilha não são conferidas
+        let same = |col: Option<usize>, value: &str| col.is_none() || field(col) == value;
+        let by_id = match field(id_col).parse::<i32>() {
+            Ok(id) => active_item(&tx, id)?.map(|item| (id, item)),
+            Err(_) => None,
+        };
+        let id = match &by_id {
+            Some((id, (description, vendor)))
+                if same(description_col, description) && same(vendor_col, vendor) =>
+            {
+                Some(*id)
+            }
+            _ => match find_item_id(
+                &tx,
+                field(description_col),
+                field(brand_col),
+                field(vendor_col),
+            )? {
+                Some(id) if active_item(&tx, id)?.is_some() => Some(id),
+                _ => None,
+            },
+        };
+        let Some(id) = id else {
+            if by_id.is_some() {
+                summary.mismatched.push(line);
+            } else {
+                summary.not_found.push(line);
+            }
+            continue;
+        };
+
+        tx.execute(
+            "UPDATE infra_item SET price = ?1, updated_at = ?2 WHERE id = ?3",
+            (price, &now, id),
+        )?;
+        history::record_price(&tx, id, price, &now, "cotação")?;
+        summary.updated += 1;
+    }
+
+    tx.commit()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rusqlite::Connection;
+
+    use super::{import_quote_template, stale_items_by_vendor, write_quote_template};
+    use crate::{history, items};
+
+    fn catalog() -> (Connection, i32, i32) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let item = |description: &str| items::ItemFields {
+            description: description.to_string(),
+            brand: "Sil".to_string(),
+            vendor: "Eletro".to_string(),
+            price: 3.5,
+            tags: vec![],
+            category: String::new(),
+            unit: String::new(),
+        };
+        let cable = items::upsert_item(&conn, &item("Cabo 2,5mm"), "2020-01-01").unwrap();
+        let socket = items::upsert_item(&conn, &item("Tomada 10A"), "2020-01-01").unwrap();
+        (conn, cable, socket)
+    }
+
+    fn sheet(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "catalogo-cotacao-{}-{}.csv",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn price(conn: &Connection, id: i32) -> f32 {
+        items::load_item(conn, id).unwrap().unwrap().price
+    }
+
+    #[test]
+    fn exported_template_imports_back() {
+        let (mut conn, cable, socket) = catalog();
+        let groups = stale_items_by_vendor(&conn).unwrap();
+        assert_eq!(groups.len(), 1);
+        let path = sheet("modelo", "");
+        write_quote_template(&path, &groups[0]).unwrap();
+
+        // O fornecedor preenche só o preço do cabo
+        let text = std::fs::read_to_string(&path).unwrap();
+        let filled: Vec<String> = text
+            .lines()
+            .map(|line| {
+                if line.starts_with(&format!("{};", cable)) {
+                    format!("{}4,20", line)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        std::fs::write(&path, filled.join("\n")).unwrap();
+        let summary = import_quote_template(&mut conn, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((summary.updated, summary.blank), (1, 1));
+        assert_eq!(price(&conn, cable), 4.2);
+        assert_eq!(price(&conn, socket), 3.5);
+        let sources: Vec<String> = history::load_history(&conn, cable)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.source)
+            .collect();
+        assert_eq!(sources, ["cadastro", "cotação"]);
+    }
+
+    #[test]
+    fn rows_of_unknown_or_other_items_are_reported() {
+        let (mut conn, cable, socket) = catalog();
+        let path = sheet(
+            "outro",
+            &format!(
+                "id;descrição;marca;fornecedor;preço atual;última atualização;novo preço\n\
+                 999;Disjuntor 20A;Sil;Eletro;10,00;2020-01-01;12,00\n\
+                 {cable};Disjuntor 20A;Sil;Outro Fornecedor;10,00;2020-01-01;12,00\n\
+                 {socket};Tomada 10A;Sil;Eletro;3,50;2020-01-01;abc\n\
+                 {socket};Tomada 10A;Sil;Eletro;3,50;2020-01-01;-1\n\
+                 ;Tomada 10A;Sil;Eletro;3,50;2020-01-01;R$ 5,10\n"
+            ),
+        );
+        let summary = import_quote_template(&mut conn, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary.not_found, [2]);
+        assert_eq!(summary.mismatched, [3]);
+        assert_eq!(summary.invalid, [4, 5]);
+        // Sem id, o item é achado pela descrição, marca e fornecedor
+        assert_eq!(summary.updated, 1);
+        assert_eq!(price(&conn, cable), 3.5);
+        assert_eq!(price(&conn, socket), 5.1);
+        assert!(summary.message().contains("mesmo id nas linhas: 3"));
+    }
+}
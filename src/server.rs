@@ -0,0 +1,772 @@
+use std::{
+    error::Error,
+    io::Read,
+    net::SocketAddr,
+    sync::{Arc, Condvar, Mutex, Weak},
+    thread,
+    time::Duration,
+};
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::{
+    EXTERNAL_CHANGE_INTERVAL, audit, backup, concurrency, filters, items, staleness, tags, trash,
+    undo,
+};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+/// Variável de ambiente com o token exigido pelo servidor (`serve --token`),
+/// lida também pelo aplicativo no modo cliente.
+pub const TOKEN_ENV: &str = "CATALOGO_TOKEN";
+
+/// Descrição das rotas no formato OpenAPI, servida em `/api/openapi.json`.
+const OPENAPI: &str = include_str!("openapi.json");
+
+/// Quanto tempo `/api/changes` segura a resposta esperando uma alteração.
+const CHANGE_WAIT: Duration = Duration::from_secs(25);
+
+/// Maior corpo de requisição aceito; o lote e a importação de um catálogo
+/// grande ficam bem abaixo disso.
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Cabeçalhos com quem fez a alteração, gravados na auditoria.
+pub const USER_HEADER: &str = "X-Catalogo-Usuario";
+pub const MACHINE_HEADER: &str = "X-Catalogo-Maquina";
+
+/// Codifica o valor de um cabeçalho em `%XX` fora do ASCII visível, para
+/// nomes como "João" ou "Conceição".
+pub fn encode_header_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'%' => "%25".to_string(),
+            0x21..=0x7E => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Desfaz o `%XX` de cabeçalhos e de parâmetros da URL.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+struct Shared {
+    conn: Mutex<Connection>,
+    /// Quando definido, toda requisição precisa de `Authorization: Bearer <token>`.
+    token: Option<String>,
+    /// Número da última alteração do catálogo; os clientes esperam que mude.
+    revision: Mutex<i64>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn notify_change(&self) {
+        *self.revision.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Espera até a revisão ser diferente de `since` (ou o tempo acabar).
+    /// Compara por diferença, não por ordem, para que um cliente ainda com a
+    /// revisão de antes de o servidor reiniciar recarregue na hora.
+    fn wait_for_change(&self, since: i64) -> i64 {
+        let revision = self.revision.lock().unwrap();
+        let (revision, _) = self
+            .changed
+            .wait_timeout_while(revision, CHANGE_WAIT, |revision| *revision == since)
+            .unwrap();
+        *revision
+    }
+}
+
+/// Servidor do catálogo rodando em segundo plano. Para de aceitar conexões
+/// quando é descartado.
+pub struct CatalogServer {
+    http: Arc<tiny_http::Server>,
+    addr: SocketAddr,
+}
+
+impl CatalogServer {
+    /// Abre `addr` (porta 0 escolhe uma livre) e atende em outra thread.
+    pub fn start(
+        conn: Connection,
+        addr: &str,
+        token: Option<String>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let http = Arc::new(tiny_http::Server::http(addr)?);
+        let addr = http
+            .server_addr()
+            .to_ip()
+            .ok_or("endereço do servidor não é TCP")?;
+        let shared = Arc::new(Shared {
+            conn: Mutex::new(conn),
+            token,
+            revision: Mutex::new(0),
+            changed: Condvar::new(),
+        });
+        watch_external_changes(Arc::downgrade(&shared));
+        let listener = Arc::clone(&http);
+        thread::spawn(move || {
+            for request in listener.incoming_requests() {
+                let shared = Arc::clone(&shared);
+                // Uma thread por requisição: `/api/changes` fica parada esperando
+                thread::spawn(move || handle(&shared, request));
+            }
+        });
+        Ok(CatalogServer { http, addr })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for CatalogServer {
+    fn drop(&mut self) {
+        self.http.unblock();
+    }
+}
+
+/// `catalogo serve [--db ARQUIVO] [--addr ENDEREÇO:PORTA] [--token TOKEN]`:
+/// atende até o processo ser encerrado.
+pub fn serve(args: &[String]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut db_path = crate::DB_PATH.to_string();
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut token = std::env::var(TOKEN_ENV).unwrap_or_default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--db" => &mut db_path,
+            "--addr" => &mut addr,
+            "--token" => &mut token,
+            other => return Err(format!("opção desconhecida: {}", other).into()),
+        };
+        *value = args
+            .next()
+            .ok_or_else(|| format!("{} precisa de um valor", arg))?
+            .clone();
+    }
+
+    let path = std::path::Path::new(&db_path);
+    let mut conn = crate::init_db(path).map_err(|e| e.to_string())?;
+    // As mesmas rotinas que o aplicativo roda ao abrir o catálogo
+    if let Err(e) = backup::run_scheduled(&conn, &backup::backup_dir(path)) {
+        eprintln!("Erro ao fazer backup automático: {}", e);
+    }
+    let retention_days = trash::retention_days(&conn);
+    if let Err(e) = trash::purge_expired(&mut conn, retention_days) {
+        eprintln!("Erro ao esvaziar a lixeira: {}", e);
+    }
+    let token = Some(token).filter(|token| !token.is_empty());
+    let server = CatalogServer::start(conn, &addr, token)?;
+    eprintln!(
+        "Catálogo {} disponível em http://{}",
+        db_path,
+        server.addr()
+    );
+    loop {
+        thread::park();
+    }
+}
+
+/// Avisa os clientes também quando o arquivo é alterado por fora do servidor
+/// (uma instância do aplicativo aberta direto no banco, por exemplo).
+fn watch_external_changes(shared: Weak<Shared>) {
+    thread::spawn(move || {
+        let mut last = None;
+        while let Some(shared) = shared.upgrade() {
+            let version = concurrency::data_version(&shared.conn.lock().unwrap()).ok();
+            if last.is_some() && version != last {
+                shared.notify_change();
+            }
+            last = version;
+            drop(shared);
+            thread::sleep(EXTERNAL_CHANGE_INTERVAL);
+        }
+    });
+}
+
+struct ApiError {
+    status: u16,
+    body: Value,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(e: rusqlite::Error) -> Self {
+        ApiError::new(500, e.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct UpdateRequest {
+    version: i64,
+    #[serde(flatten)]
+    fields: items::ItemFields,
+}
+
+#[derive(Deserialize)]
+struct DeleteRequest {
+    ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Facets {
+    pub tags: Vec<String>,
+    pub vendors: Vec<String>,
+    pub categories: Vec<String>,
+    pub brands: Vec<String>,
+}
+
+/// Quem fez a alteração, pelos cabeçalhos da requisição.
+struct Author {
+    user: String,
+    machine: String,
+}
+
+impl Author {
+    fn from_request(request: &Request) -> Self {
+        let header = |name: &'static str| {
+            request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv(name))
+                .map(|h| percent_decode(h.value.as_str().trim()))
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| "desconhecido".to_string())
+        };
+        Author {
+            user: header(USER_HEADER),
+            machine: header(MACHINE_HEADER),
+        }
+    }
+}
+
+/// Confere o token; a descrição OpenAPI fica aberta.
+fn authorize(shared: &Shared, request: &Request) -> Result<(), ApiError> {
+    let Some(token) = &shared.token else {
+        return Ok(());
+    };
+    if request.url() == "/api/openapi.json" {
+        return Ok(());
+    }
+    let authorized = request.headers().iter().any(|h| {
+        h.field.equiv("Authorization")
+            && h.value
+                .as_str()
+                .trim()
+                .strip_prefix("Bearer ")
+                .is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes()))
+    });
+    if authorized {
+        Ok(())
+    } else {
+        Err(ApiError::new(401, "token ausente ou inválido"))
+    }
+}
+
+/// Compara sem parar no primeiro byte diferente, para que o tempo da
+/// resposta não revele quanto do token foi acertado.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Lê o corpo da requisição, recusando com 413 o que passar de
+/// `MAX_BODY_BYTES`, declarado no `Content-Length` ou não.
+fn read_body(request: &mut Request) -> Result<String, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            413,
+            format!("corpo da requisição maior que {} bytes", MAX_BODY_BYTES),
+        )
+    };
+    if request
+        .body_length()
+        .is_some_and(|length| length > MAX_BODY_BYTES)
+    {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| ApiError::new(400, e.to_string()))?;
+    if body.len() > MAX_BODY_BYTES {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn handle(shared: &Shared, mut request: Request) {
+    // O token é conferido antes de ler o corpo, que pode ser grande
+    let result = authorize(shared, &request)
+        .and_then(|()| read_body(&mut request))
+        .and_then(|body| route(shared, &request, &body));
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(e) => (e.status, e.body),
+    };
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes("Content-Type", "application/json; charset=utf-8").unwrap(),
+        );
+    if let Err(e) = request.respond(response) {
+        log::warn!("Falha ao responder ao cliente: {}", e);
+    }
+}
+
+fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|e| ApiError::new(400, format!("JSON inválido: {}", e)))
+}
+
+fn to_json(value: impl Serialize) -> Result<Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| ApiError::new(500, e.to_string()))
+}
+
+/// Valores do parâmetro `name` da query string, já decodificados.
+fn query_values<'a>(query: &'a str, name: &'a str) -> impl Iterator<Item = String> + 'a {
+    query.split('&').filter_map(move |pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| percent_decode(&value.replace('+', " ")))
+    })
+}
+
+/// Filtro de `GET /api/items?q=...&vendor=...&brand=...&tag=...&outdated=true`.
+fn search_filter(query: &str) -> filters::ItemFilter {
+    filters::ItemFilter {
+        query: query_values(query, "q").next().unwrap_or_default(),
+        tags: query_values(query, "tag").collect(),
+        vendors: query_values(query, "vendor").collect(),
+        brands: query_values(query, "brand").collect(),
+        outdated_only: query_values(query, "outdated").any(|value| value == "true"),
+        ..Default::default()
+    }
+}
+
+fn route(shared: &Shared, request: &Request, body: &str) -> Result<Value, ApiError> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let method = request.method();
+
+    if let (Method::Get, ["api", "changes"]) = (method, segments.as_slice()) {
+        let since = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("since="))
+            .and_then(|since| since.parse().ok())
+            .unwrap_or(-1);
+        // Não segura a conexão com o banco enquanto espera
+        return Ok(json!({ "revision": shared.wait_for_change(since) }));
+    }
+    if let (Method::Get, ["api", "openapi.json"]) = (method, segments.as_slice()) {
+        return serde_json::from_str(OPENAPI).map_err(|e| ApiError::new(500, e.to_string()));
+    }
+
+    let mut conn = shared.conn.lock().unwrap();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["api", "items"]) if query.is_empty() => to_json(items::load_items(&conn)?),
+        (Method::Get, ["api", "items"]) => {
+            to_json(filters::query_items(&conn, &search_filter(query))?)
+        }
+        (Method::Post, ["api", "items", "query"]) => {
+            let filter: filters::ItemFilter = parse_body(body)?;
+            to_json(filters::query_items(&conn, &filter)?)
+        }
+        (Method::Get, ["api", "items", id]) => {
+            let id = parse_id(id)?;
+            match items::load_item(&conn, id)? {
+                Some(item) => to_json(item),
+                None => Err(ApiError::new(404, "item não encontrado")),
+            }
+        }
+        (Method::Get, ["api", "items", id, "history"]) => {
+            let id = parse_id(id)?;
+            match items::load_item(&conn, id)? {
+                Some(item) => to_json(items::with_history(&conn, item)?),
+                None => Err(ApiError::new(404, "item não encontrado")),
+            }
+        }
+        (Method::Post, ["api", "items", "batch"]) => {
+            let list: Vec<items::ItemFields> = parse_body(body)?;
+            for fields in &list {
+                validate(fields)?;
+            }
+            let now = Utc::now().format("%Y-%m-%d").to_string();
+            let mut before = vec![];
+            for fields in &list {
+                let id =
+                    crate::find_item_id(&conn, &fields.description, &fields.brand, &fields.vendor)?;
+                if let Some(id) = id.filter(|id| !before.iter().any(|(seen, _)| seen == id)) {
+                    before.extend(undo::capture(&conn, &[id])?);
+                }
+            }
+            let tx = conn.transaction()?;
+            let mut ids = vec![];
+            for fields in &list {
+                ids.push(items::upsert_item(&tx, fields, &now)?);
+            }
+            tx.commit()?;
+            for id in &ids {
+                if !before.iter().any(|(existing, _)| existing == id) {
+                    before.push((*id, None));
+                }
+            }
+            let origin = format!("Inserir {} item(ns) (servidor)", list.len());
+            record_audit(&mut conn, &Author::from_request(request), &origin, &before);
+            let mut saved = vec![];
+            for id in ids {
+                saved.extend(items::load_item(&conn, id)?);
+            }
+            drop(conn);
+            shared.notify_change();
+            to_json(saved)
+        }
+        (Method::Post, ["api", "items"]) => {
+            let fields: items::ItemFields = parse_body(body)?;
+            validate(&fields)?;
+            let now = Utc::now().format("%Y-%m-%d").to_string();
+            let before =
+                crate::find_item_id(&conn, &fields.description, &fields.brand, &fields.vendor)?
+                    .map_or(Ok(vec![]), |id| undo::capture(&conn, &[id]))?;
+            let id = items::upsert_item(&conn, &fields, &now)?;
+            let before = if before.is_empty() {
+                vec![(id, None)]
+            } else {
+                before
+            };
+            let origin = format!("Inserir \"{}\" (servidor)", fields.description);
+            record_audit(&mut conn, &Author::from_request(request), &origin, &before);
+            let item = items::load_item(&conn, id)?;
+            drop(conn);
+            shared.notify_change();
+            to_json(item)
+        }
+        (Method::Put, ["api", "items", id]) => {
+            let id = parse_id(id)?;
+            let update: UpdateRequest = parse_body(body)?;
+            validate(&update.fields)?;
+            let now = Utc::now().format("%Y-%m-%d").to_string();
+            let before = undo::capture(&conn, &[id])?;
+            match items::update_item(&conn, id, update.version, &update.fields, &now)? {
+                items::UpdateOutcome::Updated => {
+                    let origin = format!("Editar \"{}\" (servidor)", update.fields.description);
+                    record_audit(&mut conn, &Author::from_request(request), &origin, &before);
+                    let item = items::load_item(&conn, id)?;
+                    drop(conn);
+                    shared.notify_change();
+                    to_json(item)
+                }
+                items::UpdateOutcome::Conflict(current) => Err(ApiError {
+                    status: 409,
+                    body: json!({
+                        "error": "o item foi alterado por outra pessoa",
+                        "current": to_json(current)?,
+                    }),
+                }),
+                items::UpdateOutcome::Deleted => Err(ApiError::new(404, "item não encontrado")),
+            }
+        }
+        (Method::Post, ["api", "items", "delete"]) => {
+            let DeleteRequest { ids } = parse_body(body)?;
+            let author = Author::from_request(request);
+            let before = undo::capture(&conn, &ids)?;
+            let deleted = trash::soft_delete(&mut conn, &ids, &author.user)?;
+            if deleted > 0 {
+                let origin = format!("Excluir {} item(ns) (servidor)", deleted);
+                record_audit(&mut conn, &author, &origin, &before);
+                drop(conn);
+                shared.notify_change();
+            }
+            Ok(json!({ "deleted": deleted }))
+        }
+        (Method::Get, ["api", "facets"]) => to_json(Facets {
+            tags: tags::load_tag_names(&conn)?,
+            vendors: filters::distinct_values(&conn, "vendor")?,
+            categories: filters::distinct_values(&conn, "category")?,
+            brands: filters::distinct_values(&conn, "brand")?,
+        }),
+        (Method::Get, ["api", "staleness"]) => to_json(staleness::load_config(&conn)?),
+        (Method::Get, ["api", "export"]) => {
+            let items = items::load_items(&conn)?
+                .into_iter()
+                .map(|item| items::with_history(&conn, item))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(json!({
+                "exported_at": Utc::now().to_rfc3339(),
+                "items": to_json(items)?,
+            }))
+        }
+        _ => Err(ApiError::new(404, "rota desconhecida")),
+    }
+}
+
+fn parse_id(id: &str) -> Result<i32, ApiError> {
+    id.parse()
+        .map_err(|_| ApiError::new(400, format!("id inválido: '{}'", id)))
+}
+
+fn validate(fields: &items::ItemFields) -> Result<(), ApiError> {
+    if fields.description.trim().is_empty() || fields.vendor.trim().is_empty() {
+        return Err(ApiError::new(
+            400,
+            "descrição e fornecedor são obrigatórios",
+        ));
+    }
+    Ok(())
+}
+
+/// Registra na auditoria a diferença entre `before` e o estado atual dos itens.
+fn record_audit(conn: &mut Connection, author: &Author, origin: &str, before: &undo::Snapshot) {
+    let ids: Vec<i32> = before.iter().map(|(id, _)| *id).collect();
+    let result = undo::capture(conn, &ids).and_then(|after| {
+        audit::record_changes_by(conn, &author.user, &author.machine, origin, before, &after)
+    });
+    if let Err(e) = result {
+        log::error!("Falha ao registrar auditoria de \"{}\": {}", origin, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use rusqlite::Connection;
+
+    use serde_json::{Value, json};
+
+    use super::{
+        CatalogServer, MAX_BODY_BYTES, constant_time_eq, encode_header_value, percent_decode,
+    };
+    use crate::{items::ItemFields, items::UpdateOutcome, remote::RemoteCatalog};
+
+    fn start() -> (CatalogServer, RemoteCatalog) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let server = CatalogServer::start(conn, "127.0.0.1:0", None).unwrap();
+        let client = RemoteCatalog::new(&format!("http://{}", server.addr()));
+        (server, client)
+    }
+
+    fn fields(description: &str, price: f32) -> ItemFields {
+        ItemFields {
+            description: description.to_string(),
+            brand: String::new(),
+            vendor: "Fornecedor".to_string(),
+            price,
+            tags: vec!["obra".to_string()],
+            category: String::new(),
+            unit: "pç".to_string(),
+        }
+    }
+
+    #[test]
+    fn header_values_round_trip() {
+        for value in ["maria", "João Conceição", "100% NBR"] {
+            let encoded = encode_header_value(value);
+            assert!(encoded.bytes().all(|b| (0x21..=0x7E).contains(&b)));
+            assert_eq!(percent_decode(&encoded), value);
+        }
+    }
+
+    #[test]
+    fn tokens_are_compared_whole() {
+        assert!(constant_time_eq(b"segredo", b"segredo"));
+        assert!(!constant_time_eq(b"segredo", b"segredO"));
+        assert!(!constant_time_eq(b"segred", b"segredo"));
+        assert!(!constant_time_eq(b"", b"segredo"));
+    }
+
+    #[test]
+    fn items_round_trip_through_the_api() {
+        let (_server, client) = start();
+        let item = client
+            .insert_item(&fields("Disjuntor DIN 20A", 10.0))
+            .unwrap();
+        assert_eq!(item.description, "Disjuntor DIN 20A");
+        assert_eq!(item.tags, ["obra"]);
+
+        let mut edited = fields("Disjuntor DIN 20A", 12.5);
+        edited.tags.clear();
+        assert!(matches!(
+            client.update_item(item.id, item.version, &edited).unwrap(),
+            UpdateOutcome::Updated
+        ));
+        let items = client.load_items().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].price, 12.5);
+        assert!(items[0].tags.is_empty());
+        assert_eq!(client.load_facets().unwrap().vendors, ["Fornecedor"]);
+
+        assert_eq!(client.delete_items(&[item.id]).unwrap(), 1);
+        assert!(client.load_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stale_version_is_reported_as_conflict() {
+        let (_server, client) = start();
+        let item = client.insert_item(&fields("Cabo 2,5mm", 3.0)).unwrap();
+        client
+            .update_item(item.id, item.version, &fields("Cabo 2,5mm", 3.5))
+            .unwrap();
+        match client
+            .update_item(item.id, item.version, &fields("Cabo 2,5mm", 4.0))
+            .unwrap()
+        {
+            UpdateOutcome::Conflict(current) => assert_eq!(current.price, 3.5),
+            _ => panic!("a edição com versão antiga deveria conflitar"),
+        }
+
+        client.delete_items(&[item.id]).unwrap();
+        assert!(matches!(
+            client
+                .update_item(item.id, item.version, &fields("Cabo 2,5mm", 4.0))
+                .unwrap(),
+            UpdateOutcome::Deleted
+        ));
+    }
+
+    #[test]
+    fn writes_wake_up_waiting_clients() {
+        let (_server, client) = start();
+        let revision = client.wait_for_change(-1).unwrap();
+        let writer = client.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            writer.insert_item(&fields("Eletroduto 3/4", 8.0)).unwrap();
+        });
+        // Sem a alteração, só voltaria depois de CHANGE_WAIT
+        let started = std::time::Instant::now();
+        assert_ne!(client.wait_for_change(revision).unwrap(), revision);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn token_is_required_when_configured() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let server = CatalogServer::start(conn, "127.0.0.1:0", Some("segredo".into())).unwrap();
+        let url = format!("http://{}", server.addr());
+
+        match RemoteCatalog::new(&url).load_items() {
+            Err(e) => assert!(e.to_string().contains("401"), "{}", e),
+            Ok(_) => panic!("sem token deveria ser recusado"),
+        }
+        let wrong = RemoteCatalog::new(&url).with_token(Some("outro".into()));
+        assert!(wrong.load_items().is_err());
+        let client = RemoteCatalog::new(&url).with_token(Some("segredo".into()));
+        assert!(client.load_items().unwrap().is_empty());
+        // A descrição das rotas fica aberta
+        let openapi: Value = ureq::get(&format!("{}/api/openapi.json", url))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert!(openapi["paths"]["/api/export"].is_object());
+    }
+
+    #[test]
+    fn oversized_body_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let server = CatalogServer::start(conn, "127.0.0.1:0", Some("segredo".into())).unwrap();
+        let url = format!("http://{}/api/items/batch", server.addr());
+        let body = " ".repeat(MAX_BODY_BYTES + 1);
+
+        let status = |result: Result<ureq::Response, ureq::Error>| match result {
+            Err(ureq::Error::Status(status, _)) => status,
+            Ok(response) => response.status(),
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(status(ureq::post(&url).send_string(&body)), 401);
+        let authorized = || ureq::post(&url).set("Authorization", "Bearer segredo");
+        assert_eq!(status(authorized().send_string(&body)), 413);
+        assert_eq!(status(authorized().send_string("[]")), 200);
+    }
+
+    #[test]
+    fn search_history_batch_and_export() {
+        let (server, client) = start();
+        let url = format!("http://{}", server.addr());
+        let item = client.insert_item(&fields("Tomada 10A", 9.0)).unwrap();
+        client
+            .update_item(item.id, item.version, &fields("Tomada 10A", 9.9))
+            .unwrap();
+
+        let saved: Vec<Value> = ureq::post(&format!("{}/api/items/batch", url))
+            .send_json(json!([
+                fields("Interruptor simples", 7.0),
+                fields("Tomada 20A", 11.0)
+            ]))
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(saved.len(), 2);
+
+        let found: Vec<Value> = ureq::get(&format!("{}/api/items?q=tomada&vendor=Fornecedor", url))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        let found: Vec<Value> = ureq::get(&format!("{}/api/items?q=Tomada+10A", url))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(found.len(), 1);
+
+        let detail: Value = ureq::get(&format!("{}/api/items/{}/history", url, item.id))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(detail["description"], "Tomada 10A");
+        let prices: Vec<f64> = detail["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["price"].as_f64().unwrap())
+            .collect();
+        assert_eq!(prices.len(), 2);
+        assert!((prices[1] - 9.9).abs() < 1e-4);
+
+        let export: Value = ureq::get(&format!("{}/api/export", url))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(export["items"].as_array().unwrap().len(), 3);
+    }
+}
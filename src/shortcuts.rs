@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+
+use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{filters, settings};
+
+const SHORTCUTS_KEY: &str = "keyboard_shortcuts";
+
+/// Ações do aplicativo que podem ter atalho e aparecem na paleta de comandos.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    Submit,
+    NewItem,
+    Duplicate,
+    Delete,
+    ClearSelection,
+    SelectPrevious,
+    SelectNext,
+    FocusSearch,
+    CopySelected,
+    Undo,
+    Redo,
+    Palette,
+    ExportVisible,
+    ExportCsv,
+    ImportCsv,
+    PasteFromSpreadsheet,
+    ImportJson,
+    ExportJson,
+    CopyTemplates,
+    Staleness,
+    Requote,
+    BulkAdjust,
+    Budget,
+    Trash,
+    Backups,
+    Sync,
+    LocalApi,
+    Audit,
+    Duplicates,
+    Shortcuts,
+}
+
+impl Command {
+    pub const ALL: [Command; 30] = [
+        Command::Submit,
+        Command::NewItem,
+        Command::Duplicate,
+        Command::Delete,
+        Command::ClearSelection,
+        Command::SelectPrevious,
+        Command::SelectNext,
+        Command::FocusSearch,
+        Command::CopySelected,
+        Command::Undo,
+        Command::Redo,
+        Command::Palette,
+        Command::ExportVisible,
+        Command::ExportCsv,
+        Command::ImportCsv,
+        Command::PasteFromSpreadsheet,
+        Command::ImportJson,
+        Command::ExportJson,
+        Command::CopyTemplates,
+        Command::Staleness,
+        Command::Requote,
+        Command::BulkAdjust,
+        Command::Budget,
+        Command::Trash,
+        Command::Backups,
+        Command::Sync,
+        Command::LocalApi,
+        Command::Audit,
+        Command::Duplicates,
+        Command::Shortcuts,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Command::Submit => "Gravar o formulário (Adicionar ou Atualizar)",
+            Command::NewItem => "Novo item",
+            Command::Duplicate => "Duplicar o item selecionado",
+            Command::Delete => "Excluir selecionados",
+            Command::ClearSelection => "Limpar seleção",
+            Command::SelectPrevious => "Selecionar o item anterior",
+            Command::SelectNext => "Selecionar o próximo item",
+            Command::FocusSearch => "Buscar",
+            Command::CopySelected => "Copiar selecionados",
+            Command::Undo => "Desfazer",
+            Command::Redo => "Refazer",
+            Command::Palette => "Paleta de comandos",
+            Command::ExportVisible => "Exportar visíveis",
+            Command::ExportCsv => "Exportar CSV",
+            Command::ImportCsv => "Importar CSV",
+            Command::PasteFromSpreadsheet => "Colar do Excel",
+            Command::ImportJson => "Importar JSON",
+            Command::ExportJson => "Exportar JSON",
+            Command::CopyTemplates => "Modelos de cópia",
+            Command::Staleness => "Prazos de validade",
+            Command::Requote => "Cotações",
+            Command::BulkAdjust => "Reajuste em lote",
+            Command::Budget => "Orçamento",
+            Command::Trash => "Lixeira",
+            Command::Backups => "Restaurar backup",
+            Command::Sync => "Sincronizar",
+            Command::LocalApi => "API local",
+            Command::Audit => "Auditoria",
+            Command::Duplicates => "Itens duplicados",
+            Command::Shortcuts => "Atalhos do teclado",
+        }
+    }
+
+    pub fn default_shortcut(self) -> Option<KeyboardShortcut> {
+        let ctrl = |key| KeyboardShortcut::new(Modifiers::COMMAND, key);
+        let plain = |key| KeyboardShortcut::new(Modifiers::NONE, key);
+        Some(match self {
+            Command::Submit => plain(Key::Enter),
+            Command::NewItem => ctrl(Key::N),
+            Command::Duplicate => ctrl(Key::D),
+            Command::Delete => plain(Key::Delete),
+            Command::ClearSelection => plain(Key::Escape),
+            Command::SelectPrevious => plain(Key::ArrowUp),
+            Command::SelectNext => plain(Key::ArrowDown),
+            Command::FocusSearch => ctrl(Key::F),
+            Command::CopySelected => {
+                KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::C)
+            }
+            Command::Undo => ctrl(Key::Z),
+            Command::Redo => ctrl(Key::Y),
+            Command::Palette => ctrl(Key::P),
+            Command::ExportVisible => ctrl(Key::E),
+            _ => return None,
+        })
+    }
+
+    /// Ações que trabalham direto no arquivo do catálogo, ocultas no modo cliente.
+    pub fn local_only(self) -> bool {
+        matches!(
+            self,
+            Command::ImportCsv
+                | Command::PasteFromSpreadsheet
+                | Command::ImportJson
+                | Command::ExportJson
+                | Command::Staleness
+                | Command::Requote
+                | Command::BulkAdjust
+                | Command::Budget
+                | Command::Trash
+                | Command::Backups
+                | Command::Sync
+                | Command::LocalApi
+                | Command::Audit
+                | Command::Duplicates
+        )
+    }
+
+    /// Com um campo de texto em foco, Ctrl+Z/Ctrl+Y e as teclas sem Ctrl ou
+    /// Alt ficam com o campo (Esc e as teclas de função continuam valendo).
+    /// O "Gravar" é tratado à parte: só vale com o foco no formulário.
+    pub fn applies_while_typing(self, shortcut: &KeyboardShortcut) -> bool {
+        if matches!(self, Command::Undo | Command::Redo) {
+            return false;
+        }
+        let modifiers = shortcut.modifiers;
+        modifiers.command
+            || modifiers.ctrl
+            || modifiers.alt
+            || matches!(
+                shortcut.logical_key,
+                Key::Escape
+                    | Key::F1
+                    | Key::F2
+                    | Key::F3
+                    | Key::F4
+                    | Key::F5
+                    | Key::F6
+                    | Key::F7
+                    | Key::F8
+                    | Key::F9
+                    | Key::F10
+                    | Key::F11
+                    | Key::F12
+            )
+    }
+}
+
+/// Texto do atalho, como "Ctrl+Shift+C"; é também o formato gravado.
+pub fn format_shortcut(shortcut: &KeyboardShortcut) -> String {
+    let modifiers = shortcut.modifiers;
+    let mut parts = vec![];
+    if modifiers.command || modifiers.ctrl {
+        parts.push("Ctrl");
+    }
+    if modifiers.alt {
+        parts.push("Alt");
+    }
+    if modifiers.shift {
+        parts.push("Shift");
+    }
+    parts.push(shortcut.logical_key.name());
+    parts.join("+")
+}
+
+/// Atalho de uma tecla pressionada, com Ctrl (ou Cmd, no Mac) como `COMMAND`.
+pub fn from_key(key: Key, modifiers: Modifiers) -> KeyboardShortcut {
+    let mut normalized = Modifiers::NONE;
+    if modifiers.command || modifiers.ctrl {
+        normalized |= Modifiers::COMMAND;
+    }
+    if modifiers.alt {
+        normalized |= Modifiers::ALT;
+    }
+    if modifiers.shift {
+        normalized |= Modifiers::SHIFT;
+    }
+    KeyboardShortcut::new(normalized, key)
+}
+
+pub fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = Key::from_name(parts.pop()?)?;
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        modifiers |= match part {
+            "Ctrl" => Modifiers::COMMAND,
+            "Alt" => Modifiers::ALT,
+            "Shift" => Modifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+/// Atalho de cada ação; `None` quando a ação não tem atalho.
+#[derive(Clone, PartialEq)]
+pub struct Shortcuts {
+    bindings: BTreeMap<Command, Option<KeyboardShortcut>>,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Shortcuts {
+            bindings: Command::ALL
+                .iter()
+                .map(|command| (*command, command.default_shortcut()))
+                .collect(),
+        }
+    }
+}
+
+impl Shortcuts {
+    pub fn get(&self, command: Command) -> Option<KeyboardShortcut> {
+        self.bindings.get(&command).copied().flatten()
+    }
+
+    /// Texto do atalho da ação, ou vazio.
+    pub fn text(&self, command: Command) -> String {
+        self.get(command)
+            .map(|shortcut| format_shortcut(&shortcut))
+            .unwrap_or_default()
+    }
+
+    /// Troca o atalho da ação. Se outra ação já usava o mesmo atalho, ela fica
+    /// sem atalho e é devolvida.
+    pub fn set(&mut self, command: Command, shortcut: Option<KeyboardShortcut>) -> Option<Command> {
+        let mut previous_owner = None;
+        if let Some(shortcut) = shortcut {
+            for (other, binding) in self.bindings.iter_mut() {
+                if *other != command && *binding == Some(shortcut) {
+                    *binding = None;
+                    previous_owner = Some(*other);
+                }
+            }
+        }
+        self.bindings.insert(command, shortcut);
+        previous_owner
+    }
+
+    /// Ações com atalho, das combinações com mais modificadores para as com
+    /// menos: o egui aceita Shift e Alt a mais ao comparar, então Ctrl+Shift+C
+    /// precisa ser testado antes de Ctrl+C.
+    pub fn by_specificity(&self) -> Vec<(Command, KeyboardShortcut)> {
+        let mut bound: Vec<(Command, KeyboardShortcut)> = self
+            .bindings
+            .iter()
+            .filter_map(|(command, shortcut)| Some((*command, (*shortcut)?)))
+            .collect();
+        bound.sort_by_key(|(_, shortcut)| {
+            let modifiers = shortcut.modifiers;
+            std::cmp::Reverse(
+                [modifiers.command, modifiers.alt, modifiers.shift]
+                    .iter()
+                    .filter(|on| **on)
+                    .count(),
+            )
+        });
+        bound
+    }
+}
+
+/// Atalhos gravados, sobre os padrões. Só as ações alteradas são gravadas, e
+/// uma ação sem atalho fica com o texto vazio.
+pub fn load(conn: &Connection) -> Shortcuts {
+    let mut shortcuts = Shortcuts::default();
+    let saved: BTreeMap<Command, String> = settings::get(conn, SHORTCUTS_KEY)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    for (command, text) in saved {
+        shortcuts.bindings.insert(command, parse_shortcut(&text));
+    }
+    shortcuts
+}
+
+pub fn save(conn: &Connection, shortcuts: &Shortcuts) -> Result<(), Box<dyn std::error::Error>> {
+    let changed: BTreeMap<Command, String> = Command::ALL
+        .iter()
+        .filter(|command| shortcuts.get(**command) != command.default_shortcut())
+        .map(|command| (*command, shortcuts.text(*command)))
+        .collect();
+    settings::set(conn, SHORTCUTS_KEY, &serde_json::to_string(&changed)?)?;
+    Ok(())
+}
+
+/// Ações que contêm o texto buscado (sem diferenciar acentos e maiúsculas).
+pub fn search(query: &str, remote: bool) -> Vec<Command> {
+    let query = filters::fold(query.trim());
+    Command::ALL
+        .into_iter()
+        .filter(|command| !(remote && command.local_only()))
+        .filter(|command| filters::fold(command.label()).contains(&query))
+        .collect()
+}
+
+/// Paleta de comandos (Ctrl+P): busca por nome e executa a ação.
+#[derive(Default)]
+pub struct PaletteView {
+    pub open: bool,
+    pub query: String,
+    /// Posição da ação destacada na lista filtrada.
+    pub highlighted: usize,
+}
+
+/// Janela "Atalhos do teclado".
+#[derive(Default)]
+pub struct ShortcutsView {
+    pub open: bool,
+    pub shortcuts: Shortcuts,
+    /// Ação esperando o novo atalho ser pressionado.
+    pub capturing: Option<Command>,
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+    use rusqlite::Connection;
+
+    use super::{Command, Shortcuts, format_shortcut, load, parse_shortcut, save, search};
+
+    #[test]
+    fn shortcut_text_round_trips() {
+        for command in Command::ALL {
+            if let Some(shortcut) = command.default_shortcut() {
+                assert_eq!(parse_shortcut(&format_shortcut(&shortcut)), Some(shortcut));
+            }
+        }
+        assert_eq!(
+            format_shortcut(&Command::CopySelected.default_shortcut().unwrap()),
+            "Ctrl+Shift+C"
+        );
+        assert_eq!(parse_shortcut("Hyper+K"), None);
+    }
+
+    #[test]
+    fn rebinding_moves_shortcut_and_persists() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let mut shortcuts = Shortcuts::default();
+        let ctrl_n = KeyboardShortcut::new(Modifiers::COMMAND, Key::N);
+        assert_eq!(
+            shortcuts.set(Command::Budget, Some(ctrl_n)),
+            Some(Command::NewItem)
+        );
+        shortcuts.set(Command::Delete, None);
+        save(&conn, &shortcuts).unwrap();
+
+        let loaded = load(&conn);
+        assert!(loaded == shortcuts);
+        assert_eq!(loaded.get(Command::NewItem), None);
+        assert_eq!(loaded.get(Command::Budget), Some(ctrl_n));
+        assert_eq!(loaded.by_specificity()[0].0, Command::CopySelected);
+    }
+
+    #[test]
+    fn palette_search_ignores_accents_and_hides_local_actions() {
+        assert!(search("orcamento", false).contains(&Command::Budget));
+        assert!(search("orcamento", true).is_empty());
+        assert_eq!(search("", false).len(), Command::ALL.len());
+    }
+}
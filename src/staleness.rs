@@ -0,0 +1,227 @@
+use chrono::{NaiveDate, Utc};
+use eframe::egui::Color32;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::settings;
+
+pub const STALE_DAYS_KEY: &str = "stale_days";
+pub const DEFAULT_STALE_DAYS: i64 = 30;
+
+/// Prazo de validade (em dias) aplicável a `infra_item`: o do fornecedor,
+/// se houver, senão o da categoria, senão o prazo global.
+pub fn stale_days_sql() -> String {
+    format!(
+        "COALESCE(
+        (SELECT vs.stale_days FROM vendor_staleness vs WHERE vs.vendor = infra_item.vendor),
+        (SELECT cs.stale_days FROM category_staleness cs
+            WHERE cs.category = infra_item.category AND infra_item.category <> ''),
+        (SELECT CAST(s.value AS INTEGER) FROM setting s WHERE s.key = '{STALE_DAYS_KEY}'),
+        {DEFAULT_STALE_DAYS})"
+    )
+}
+
+pub const AGE_DAYS_SQL: &str = "CAST(julianday('now') - julianday(updated_at) AS INTEGER)";
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS vendor_staleness (
+            vendor TEXT PRIMARY KEY,
+            stale_days INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS category_staleness (
+            category TEXT PRIMARY KEY,
+            stale_days INTEGER NOT NULL
+        );",
+    )
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StalenessConfig {
+    pub default_days: i64,
+    pub vendor_days: Vec<(String, i64)>,
+    pub category_days: Vec<(String, i64)>,
+}
+
+/// O prazo global padrão, para nada parecer desatualizado antes de a
+/// configuração ser lida.
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        StalenessConfig {
+            default_days: DEFAULT_STALE_DAYS,
+            vendor_days: vec![],
+            category_days: vec![],
+        }
+    }
+}
+
+impl StalenessConfig {
+    /// O prazo do fornecedor prevalece sobre o da categoria, que prevalece
+    /// sobre o padrão (mesma ordem de `stale_days_sql`).
+    pub fn threshold_for(&self, vendor: &str, category: &str) -> i64 {
+        let find = |days: &[(String, i64)], name: &str| {
+            days.iter().find(|(n, _)| n == name).map(|(_, days)| *days)
+        };
+        find(&self.vendor_days, vendor)
+            .or_else(|| (!category.is_empty()).then(|| find(&self.category_days, category))?)
+            .unwrap_or(self.default_days)
+    }
+}
+
+pub fn load_config(conn: &Connection) -> rusqlite::Result<StalenessConfig> {
+    let mut stmt =
+        conn.prepare("SELECT vendor, stale_days FROM vendor_staleness ORDER BY vendor")?;
+    let vendor_days = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut stmt =
+        conn.prepare("SELECT category, stale_days FROM category_staleness ORDER BY category")?;
+    let category_days = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(StalenessConfig {
+        default_days: settings::get_i64(conn, STALE_DAYS_KEY, DEFAULT_STALE_DAYS),
+        vendor_days,
+        category_days,
+    })
+}
+
+pub fn save_config(conn: &mut Connection, config: &StalenessConfig) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    settings::set(&tx, STALE_DAYS_KEY, &config.default_days.to_string())?;
+    tx.execute("DELETE FROM vendor_staleness", [])?;
+    for (vendor, days) in &config.vendor_days {
+        tx.execute(
+            "INSERT OR REPLACE INTO vendor_staleness (vendor, stale_days) VALUES (?1, ?2)",
+            (vendor, days),
+        )?;
+    }
+    tx.execute("DELETE FROM category_staleness", [])?;
+    for (category, days) in &config.category_days {
+        tx.execute(
+            "INSERT OR REPLACE INTO category_staleness (category, stale_days) VALUES (?1, ?2)",
+            (category, days),
+        )?;
+    }
+    tx.commit()
+}
+
+/// Dias desde `updated_at` (formato AAAA-MM-DD). `None` se a data não for reconhecida.
+pub fn age_in_days(updated_at: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(updated_at.get(..10)?, "%Y-%m-%d").ok()?;
+    Some((Utc::now().date_naive() - date).num_days())
+}
+
+pub fn format_age(days: i64) -> String {
+    match days {
+        i64::MIN..=0 => "hoje".to_string(),
+        1 => "há 1 dia".to_string(),
+        n => format!("há {} dias", n),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Freshness {
+    Fresh,
+    Aging,
+    Stale,
+}
+
+impl Freshness {
+    /// Preço "envelhecendo" a partir da metade do prazo, vencido depois dele.
+    pub fn classify(age_days: i64, threshold_days: i64) -> Freshness {
+        if age_days > threshold_days {
+            Freshness::Stale
+        } else if age_days * 2 >= threshold_days {
+            Freshness::Aging
+        } else {
+            Freshness::Fresh
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            Freshness::Fresh => Color32::from_rgb(30, 120, 40),
+            Freshness::Aging => Color32::from_rgb(190, 120, 0),
+            Freshness::Stale => Color32::from_rgb(200, 30, 30),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use rusqlite::Connection;
+
+    use super::{Freshness, StalenessConfig, load_config, save_config};
+    use crate::filters;
+
+    #[test]
+    fn classifies_by_half_and_full_threshold() {
+        assert!(Freshness::classify(0, 30) == Freshness::Fresh);
+        assert!(Freshness::classify(14, 30) == Freshness::Fresh);
+        assert!(Freshness::classify(15, 30) == Freshness::Aging);
+        assert!(Freshness::classify(30, 30) == Freshness::Aging);
+        assert!(Freshness::classify(31, 30) == Freshness::Stale);
+    }
+
+    #[test]
+    fn vendor_overrides_category_and_default() {
+        let config = StalenessConfig {
+            default_days: 30,
+            vendor_days: vec![("Elétrica Central".to_string(), 10)],
+            category_days: vec![("Cabos".to_string(), 90)],
+        };
+        assert_eq!(config.threshold_for("Elétrica Central", "Cabos"), 10);
+        assert_eq!(config.threshold_for("Outro", "Cabos"), 90);
+        assert_eq!(config.threshold_for("Outro", "Disjuntores"), 30);
+        assert_eq!(config.threshold_for("Outro", ""), 30);
+        assert_eq!(
+            StalenessConfig::default().threshold_for("Outro", "Cabos"),
+            super::DEFAULT_STALE_DAYS
+        );
+    }
+
+    #[test]
+    fn outdated_filter_uses_same_thresholds() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let updated_at = (Utc::now() - Duration::days(45))
+            .format("%Y-%m-%d")
+            .to_string();
+        for (description, vendor, category) in [
+            ("Cabo 2,5mm", "Outro", "Cabos"),
+            ("Disjuntor 20A", "Outro", "Disjuntores"),
+            ("Cabo 4mm", "Elétrica Central", "Cabos"),
+        ] {
+            conn.execute(
+                "INSERT INTO infra_item (description, brand, vendor, price, updated_at, category)
+                VALUES (?1, '', ?2, 10, ?3, ?4)",
+                (description, vendor, &updated_at, category),
+            )
+            .unwrap();
+        }
+        let config = StalenessConfig {
+            default_days: 30,
+            vendor_days: vec![("Elétrica Central".to_string(), 10)],
+            category_days: vec![("Cabos".to_string(), 90)],
+        };
+        save_config(&mut conn, &config).unwrap();
+        let config = load_config(&conn).unwrap();
+
+        let filter = filters::ItemFilter {
+            outdated_only: true,
+            ..Default::default()
+        };
+        let mut outdated: Vec<String> = filters::query_items(&conn, &filter)
+            .unwrap()
+            .into_iter()
+            .map(|item| {
+                assert!(config.threshold_for(&item.vendor, &item.category) < 45);
+                item.description
+            })
+            .collect();
+        outdated.sort();
+        assert_eq!(outdated, ["Cabo 4mm", "Disjuntor 20A"]);
+    }
+}
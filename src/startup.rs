@@ -0,0 +1,163 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use eframe::egui;
+use rusqlite::ErrorCode;
+
+use crate::backup;
+
+/// Tela exibida quando o catálogo não pode ser aberto, no lugar de encerrar
+/// o programa sem mensagem.
+pub struct StartupScreen {
+    db_path: PathBuf,
+    explanation: &'static str,
+    details: String,
+    backups: Vec<backup::BackupFile>,
+    message: Option<String>,
+}
+
+/// Explicação em linguagem simples para os erros mais comuns ao abrir o banco.
+fn explain(error: &(dyn Error + 'static)) -> &'static str {
+    let code = error
+        .downcast_ref::<rusqlite::Error>()
+        .and_then(|e| e.sqlite_error_code());
+    match code {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => {
+            "O catálogo está bloqueado por outro programa ou por outra pessoa. \
+             Feche as outras cópias do programa e tente novamente."
+        }
+        Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt) => {
+            "O arquivo do catálogo está corrompido ou não é um catálogo. \
+             Restaure um backup ou abra outro arquivo."
+        }
+        Some(ErrorCode::ReadOnly | ErrorCode::PermissionDenied) => {
+            "Não há permissão para gravar no catálogo. Verifique se o arquivo ou a \
+             pasta não estão somente leitura."
+        }
+        Some(ErrorCode::CannotOpen) => {
+            "O arquivo do catálogo não pôde ser aberto. Verifique se a pasta existe \
+             e se a unidade de rede está conectada."
+        }
+        Some(ErrorCode::DiskFull) => "O disco está cheio.",
+        _ => "Ocorreu um erro inesperado ao abrir o catálogo.",
+    }
+}
+
+impl StartupScreen {
+    pub fn new(db_path: PathBuf, error: &(dyn Error + 'static)) -> Self {
+        let mut backups = backup::list_backups(&backup::backup_dir(&db_path)).unwrap_or_default();
+        for backup in &mut backups {
+            backup.item_count = backup::count_items(&backup.path).ok();
+        }
+        StartupScreen {
+            explanation: explain(error),
+            details: error.to_string(),
+            db_path,
+            backups,
+            message: None,
+        }
+    }
+
+    /// Desenha a tela. Devolve o catálogo que deve ser aberto quando o
+    /// usuário escolhe uma das opções.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<PathBuf> {
+        let mut open = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Não foi possível abrir o catálogo");
+            ui.add_space(8.0);
+            ui.label(format!("Arquivo: {}", self.db_path.display()));
+            ui.label(self.explanation);
+            ui.add_space(4.0);
+            ui.label(egui::RichText::new(&self.details).monospace().weak());
+            ui.add_space(12.0);
+
+            ui.horizontal(|ui| {
+                if ui.button("Tentar novamente").clicked() {
+                    open = Some(self.db_path.clone());
+                }
+                if ui.button("Abrir outro catálogo...").clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Catálogo", &["db"])
+                        .pick_file()
+                {
+                    open = Some(path);
+                }
+                if ui.button("Criar novo catálogo...").clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Catálogo", &["db"])
+                        .set_file_name("infra_items.db")
+                        .save_file()
+                {
+                    open = Some(path);
+                }
+            });
+
+            if let Some(message) = &self.message {
+                ui.add_space(8.0);
+                ui.colored_label(egui::Color32::DARK_RED, message);
+            }
+
+            ui.separator();
+            ui.label("Restaurar backup:");
+            if self.backups.is_empty() {
+                ui.label("Nenhum backup encontrado.");
+            }
+            let mut restore = None;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("startup_backup_grid")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for backup in &self.backups {
+                            ui.label(backup.created_at.format("%d/%m/%Y %H:%M").to_string());
+                            ui.label(backup.kind.label());
+                            ui.label(match backup.item_count {
+                                Some(count) => format!("{} itens", count),
+                                None => "ilegível".to_string(),
+                            });
+                            if ui.button("Restaurar").clicked() {
+                                restore = Some(backup.path.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+            if let Some(path) = restore {
+                match self.restore(&path) {
+                    Ok(()) => open = Some(self.db_path.clone()),
+                    Err(e) => self.message = Some(format!("Erro ao restaurar backup: {}", e)),
+                }
+            }
+        });
+        open
+    }
+
+    /// Substitui o arquivo do catálogo pelo backup. O arquivo com problema é
+    /// renomeado, não apagado.
+    fn restore(&self, backup_path: &Path) -> Result<(), Box<dyn Error>> {
+        let problems = backup::integrity_check(backup_path)?;
+        if !problems.is_empty() {
+            return Err(format!("o backup está corrompido: {}", problems.join("; ")).into());
+        }
+        let stamp = Utc::now().format("%Y-%m-%d_%H%M%S").to_string();
+        // Os arquivos -wal e -shm pertencem ao banco com problema e não podem
+        // ser aplicados sobre o backup
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.db_path.clone().into_os_string();
+            path.push(suffix);
+            let path = PathBuf::from(path);
+            if path.exists() {
+                let mut aside = path.clone().into_os_string();
+                aside.push(format!(".erro-{}", stamp));
+                fs::rename(&path, aside)?;
+            }
+        }
+        fs::copy(backup_path, &self.db_path)?;
+        Ok(())
+    }
+}
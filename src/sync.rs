@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::{
+    ITEM_COLUMNS, InfraItem, find_item_id, format_money, history, item_from_row, settings, tags,
+};
+
+const CATALOG_ID_KEY: &str = "catalog_id";
+
+/// Chave natural de um item: descrição, marca e fornecedor.
+pub type ItemKey = (String, String, String);
+
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    // Versão do item nos dois catálogos na última sincronização com `peer`;
+    // NULL quando o item não existia daquele lado
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sync_base (
+            peer TEXT NOT NULL,
+            description TEXT NOT NULL,
+            brand TEXT NOT NULL,
+            vendor TEXT NOT NULL,
+            local_version INTEGER,
+            peer_version INTEGER,
+            PRIMARY KEY (peer, description, brand, vendor)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Identificador do catálogo, criado na primeira sincronização.
+fn catalog_id(conn: &Connection) -> rusqlite::Result<String> {
+    if let Some(id) = settings::get(conn, CATALOG_ID_KEY)? {
+        return Ok(id);
+    }
+    let id = new_catalog_id();
+    settings::set(conn, CATALOG_ID_KEY, &id)?;
+    Ok(id)
+}
+
+fn new_catalog_id() -> String {
+    let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    format!("{:x}-{:x}", nanos, std::process::id())
+}
+
+/// Estado de um item num dos catálogos.
+#[derive(Clone)]
+pub struct RowState {
+    pub item: InfraItem,
+    pub deleted: bool,
+}
+
+impl RowState {
+    /// Texto mostrado no diálogo de conflitos.
+    pub fn summary(&self) -> String {
+        if self.deleted {
+            return "Na lixeira".to_string();
+        }
+        let item = &self.item;
+        let mut parts = vec![
+            format!("R$ {}", format_money(item.price)),
+            item.updated_at.clone(),
+        ];
+        parts.extend(
+            [&item.category, &item.unit]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .cloned(),
+        );
+        parts.extend(item.tags.iter().map(|t| format!("#{}", t)));
+        parts.join(" · ")
+    }
+}
+
+/// Excluído ou na lixeira: os dois contam como "não está no catálogo".
+fn is_gone(state: Option<&RowState>) -> bool {
+    state.is_none_or(|s| s.deleted)
+}
+
+fn same_content(a: Option<&RowState>, b: Option<&RowState>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) if !a.deleted && !b.deleted => {
+            let (a, b) = (&a.item, &b.item);
+            let sorted = |tags: &[String]| tags.iter().cloned().collect::<BTreeSet<_>>();
+            (a.price - b.price).abs() <= f32::EPSILON
+                && a.updated_at == b.updated_at
+                && a.category == b.category
+                && a.unit == b.unit
+                && sorted(&a.tags) == sorted(&b.tags)
+        }
+        _ => is_gone(a) && is_gone(b),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Side {
+    Local,
+    Other,
+}
+
+impl Side {
+    pub fn label(self) -> &'static str {
+        match self {
+            Side::Local => "Este catálogo",
+            Side::Other => "Outro catálogo",
+        }
+    }
+}
+
+/// Alteração feita de um lado só, copiada para o outro.
+pub struct Change {
+    pub key: ItemKey,
+    /// Lado onde o item foi alterado.
+    pub from: Side,
+    pub state: Option<RowState>,
+}
+
+/// Item alterado de forma diferente nos dois catálogos.
+pub struct Conflict {
+    pub key: ItemKey,
+    pub local: Option<RowState>,
+    pub other: Option<RowState>,
+    /// Versão escolhida pelo usuário.
+    pub choice: Option<Side>,
+}
+
+#[derive(Default)]
+pub struct SyncPlan {
+    pub changes: Vec<Change>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl SyncPlan {
+    pub fn count_from(&self, side: Side) -> usize {
+        self.changes.iter().filter(|c| c.from == side).count()
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.conflicts.iter().all(|c| c.choice.is_some())
+    }
+}
+
+fn load_states(conn: &Connection) -> rusqlite::Result<BTreeMap<ItemKey, RowState>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS}, deleted_at IS NOT NULL AS deleted FROM infra_item"
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        let item = item_from_row(row)?;
+        let key = (
+            item.description.clone(),
+            item.brand.clone(),
+            item.vendor.clone(),
+        );
+        Ok((
+            key,
+            RowState {
+                item,
+                deleted: row.get("deleted")?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+type Base = BTreeMap<ItemKey, (Option<i64>, Option<i64>)>;
+
+fn load_base(conn: &Connection, peer: &str) -> rusqlite::Result<Base> {
+    let mut stmt = conn.prepare(
+        "SELECT description, brand, vendor, local_version, peer_version
+         FROM sync_base WHERE peer = ?1",
+    )?;
+    let rows = stmt.query_map([peer], |row| {
+        Ok((
+            (row.get(0)?, row.get(1)?, row.get(2)?),
+            (row.get(3)?, row.get(4)?),
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Os identificadores dos dois catálogos. Um arquivo copiado tem o mesmo
+/// identificador do original; nesse caso o outro catálogo recebe um novo.
+fn catalog_ids(local: &Connection, other: &Connection) -> rusqlite::Result<(String, String)> {
+    let local_id = catalog_id(local)?;
+    let mut other_id = catalog_id(other)?;
+    if other_id == local_id {
+        other_id = new_catalog_id();
+        settings::set(other, CATALOG_ID_KEY, &other_id)?;
+    }
+    Ok((local_id, other_id))
+}
+
+/// Compara os dois catálogos com o estado da última sincronização entre
+/// eles. O item alterado de um lado só é copiado para o outro; alterado dos
+/// dois lados (ou presente nos dois, diferente, sem sincronização anterior)
+/// vira conflito.
+pub fn plan(local: &Connection, other: &Connection) -> rusqlite::Result<SyncPlan> {
+    let (_, other_id) = catalog_ids(local, other)?;
+    let base = load_base(local, &other_id)?;
+    let mut local_states = load_states(local)?;
+    let mut other_states = load_states(other)?;
+    let keys: BTreeSet<ItemKey> = local_states
+        .keys()
+        .chain(other_states.keys())
+        .cloned()
+        .collect();
+
+    let mut plan = SyncPlan::default();
+    for key in keys {
+        let l = local_states.remove(&key);
+        let o = other_states.remove(&key);
+        if same_content(l.as_ref(), o.as_ref()) {
+            continue;
+        }
+        let (local_changed, other_changed) = match base.get(&key) {
+            Some((local_version, other_version)) => (
+                l.as_ref().map(|s| s.item.version) != *local_version,
+                o.as_ref().map(|s| s.item.version) != *other_version,
+            ),
+            // Sem sincronização anterior, só quem tem o item o alterou
+            None => (l.is_some(), o.is_some()),
+        };
+        match (local_changed, other_changed) {
+            (true, false) => plan.changes.push(Change {
+                key,
+                from: Side::Local,
+                state: l,
+            }),
+            (false, true) => plan.changes.push(Change {
+                key,
+                from: Side::Other,
+                state: o,
+            }),
+            _ => plan.conflicts.push(Conflict {
+                key,
+                local: l,
+                other: o,
+                choice: None,
+            }),
+        }
+    }
+    Ok(plan)
+}
+
+/// Grava `state` (vindo de `source`) em `target`. Um item excluído vai para
+/// a lixeira de `target`; um item presente é incluído ou atualizado, com as
+/// tags e o histórico de preços que faltam.
+fn copy_state(
+    source: &Connection,
+    target: &Connection,
+    key: &ItemKey,
+    state: Option<&RowState>,
+    user: &str,
+) -> rusqlite::Result<()> {
+    let (description, brand, vendor) = key;
+    let Some(state) = state.filter(|s| !s.deleted) else {
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        target.execute(
+            "UPDATE infra_item SET deleted_at = ?1, deleted_by = ?2
+             WHERE description = ?3 AND brand = ?4 AND vendor = ?5 AND deleted_at IS NULL",
+            (&now, user, description, brand, vendor),
+        )?;
+        return Ok(());
+    };
+
+    let item = &state.item;
+    target.execute(
+        "INSERT INTO infra_item (description, brand, vendor, price, updated_at, category, unit)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT(description, brand, vendor) DO UPDATE SET
+            price = excluded.price,
+            updated_at = excluded.updated_at,
+            category = excluded.category,
+            unit = excluded.unit,
+            deleted_at = NULL,
+            deleted_by = NULL",
+        (
+            description,
+            brand,
+            vendor,
+            item.price,
+            &item.updated_at,
+            &item.category,
+            &item.unit,
+        ),
+    )?;
+    let target_id = find_item_id(target, description, brand, vendor)?
+        .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    if tags_of(target, target_id)? != item.tags.iter().cloned().collect() {
+        tags::set_item_tags(target, target_id, &item.tags)?;
+    }
+
+    for record in history::load_history(source, item.id)? {
+        target.execute(
+            "INSERT INTO price_history (item_id, price, recorded_at, source)
+             SELECT ?1, ?2, ?3, ?4 WHERE NOT EXISTS (
+                SELECT 1 FROM price_history
+                WHERE item_id = ?1 AND price = ?2 AND recorded_at = ?3 AND source = ?4)",
+            (target_id, record.price, &record.recorded_at, &record.source),
+        )?;
+    }
+    Ok(())
+}
+
+fn tags_of(conn: &Connection, id: i32) -> rusqlite::Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name FROM item_tag it JOIN tag t ON t.id = it.tag_id WHERE it.item_id = ?1",
+    )?;
+    let names = stmt.query_map([id], |row| row.get(0))?;
+    names.collect()
+}
+
+/// Grava a versão atual de cada item dos dois lados como ponto de partida
+/// da próxima sincronização.
+fn save_base(
+    conn: &Connection,
+    peer: &str,
+    local: &BTreeMap<ItemKey, RowState>,
+    other: &BTreeMap<ItemKey, RowState>,
+) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM sync_base WHERE peer = ?1", [peer])?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO sync_base (peer, description, brand, vendor, local_version, peer_version)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for key in local.keys().chain(other.keys()).collect::<BTreeSet<_>>() {
+        let (description, brand, vendor) = key;
+        stmt.execute((
+            peer,
+            description,
+            brand,
+            vendor,
+            local.get(key).map(|s| s.item.version),
+            other.get(key).map(|s| s.item.version),
+        ))?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct SyncSummary {
+    pub sent: usize,
+    pub received: usize,
+}
+
+/// Aplica o plano nos dois catálogos. Todos os conflitos precisam ter uma
+/// versão escolhida.
+pub fn apply(
+    local: &mut Connection,
+    other: &mut Connection,
+    plan: &SyncPlan,
+    user: &str,
+) -> rusqlite::Result<SyncSummary> {
+    let (local_id, other_id) = catalog_ids(local, other)?;
+    let resolved = plan.conflicts.iter().map(|conflict| {
+        let from = conflict.choice.unwrap_or(Side::Local);
+        let state = match from {
+            Side::Local => conflict.local.as_ref(),
+            Side::Other => conflict.other.as_ref(),
+        };
+        (&conflict.key, from, state)
+    });
+    let all = plan
+        .changes
+        .iter()
+        .map(|change| (&change.key, change.from, change.state.as_ref()))
+        .chain(resolved);
+
+    // Savepoint: o aplicativo grava a auditoria local na mesma transação
+    let local_tx = local.savepoint()?;
+    let other_tx = other.transaction()?;
+    let mut summary = SyncSummary::default();
+    for (key, from, state) in all {
+        match from {
+            Side::Local => {
+                copy_state(&local_tx, &other_tx, key, state, user)?;
+                summary.sent += 1;
+            }
+            Side::Other => {
+                copy_state(&other_tx, &local_tx, key, state, user)?;
+                summary.received += 1;
+            }
+        }
+    }
+
+    let local_states = load_states(&local_tx)?;
+    let other_states = load_states(&other_tx)?;
+    save_base(&local_tx, &other_id, &local_states, &other_states)?;
+    save_base(&other_tx, &local_id, &other_states, &local_states)?;
+    other_tx.commit()?;
+    local_tx.commit()?;
+    Ok(summary)
+}
+
+/// Estado da janela de sincronização.
+#[derive(Default)]
+pub struct SyncView {
+    pub open: bool,
+    pub other_path: Option<PathBuf>,
+    pub other: Option<Connection>,
+    pub plan: Option<SyncPlan>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{Side, apply, plan};
+
+    fn catalog() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, description: &str, price: f32) {
+        conn.execute(
+            "INSERT INTO infra_item (description, brand, vendor, price, updated_at)
+            VALUES (?1, '', 'Fornecedor', ?2, '2024-01-10')",
+            (description, price),
+        )
+        .unwrap();
+    }
+
+    fn set_price(conn: &Connection, description: &str, price: f32) {
+        conn.execute(
+            "UPDATE infra_item SET price = ?1 WHERE description = ?2",
+            (price, description),
+        )
+        .unwrap();
+    }
+
+    fn price(conn: &Connection, description: &str) -> f32 {
+        conn.query_row(
+            "SELECT price FROM infra_item WHERE description = ?1 AND deleted_at IS NULL",
+            [description],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    /// Dois catálogos já sincronizados uma vez, com os mesmos dois itens.
+    fn synced_pair() -> (Connection, Connection) {
+        let (mut office, mut laptop) = (catalog(), catalog());
+        insert(&office, "Disjuntor DIN 20A", 10.0);
+        insert(&laptop, "Cabo 2,5mm", 3.0);
+        let first = plan(&office, &laptop).unwrap();
+        assert_eq!(first.count_from(Side::Local), 1);
+        assert_eq!(first.count_from(Side::Other), 1);
+        assert!(first.conflicts.is_empty());
+        apply(&mut office, &mut laptop, &first, "teste").unwrap();
+        (office, laptop)
+    }
+
+    #[test]
+    fn first_sync_copies_new_items_both_ways() {
+        let (office, laptop) = synced_pair();
+        for conn in [&office, &laptop] {
+            assert_eq!(price(conn, "Disjuntor DIN 20A"), 10.0);
+            assert_eq!(price(conn, "Cabo 2,5mm"), 3.0);
+        }
+        let again = plan(&office, &laptop).unwrap();
+        assert!(again.changes.is_empty() && again.conflicts.is_empty());
+        // O plano visto do outro lado também está vazio
+        let reverse = plan(&laptop, &office).unwrap();
+        assert!(reverse.changes.is_empty() && reverse.conflicts.is_empty());
+    }
+
+    #[test]
+    fn one_sided_changes_and_deletions_are_applied() {
+        let (mut office, mut laptop) = synced_pair();
+        set_price(&laptop, "Cabo 2,5mm", 3.5);
+        office
+            .execute(
+                "UPDATE infra_item SET deleted_at = '2024-02-01 10:00:00'
+                 WHERE description = 'Disjuntor DIN 20A'",
+                [],
+            )
+            .unwrap();
+
+        let plan = plan(&office, &laptop).unwrap();
+        assert!(plan.conflicts.is_empty());
+        assert_eq!(plan.changes.len(), 2);
+        apply(&mut office, &mut laptop, &plan, "teste").unwrap();
+
+        assert_eq!(price(&office, "Cabo 2,5mm"), 3.5);
+        let in_trash: bool = laptop
+            .query_row(
+                "SELECT deleted_at IS NOT NULL FROM infra_item WHERE description = 'Disjuntor DIN 20A'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(in_trash);
+    }
+
+    #[test]
+    fn changes_on_both_sides_become_conflicts() {
+        let (mut office, mut laptop) = synced_pair();
+        set_price(&office, "Cabo 2,5mm", 4.0);
+        set_price(&laptop, "Cabo 2,5mm", 3.5);
+        // Alteração igual dos dois lados não é conflito
+        set_price(&office, "Disjuntor DIN 20A", 11.0);
+        set_price(&laptop, "Disjuntor DIN 20A", 11.0);
+
+        let mut plan = plan(&office, &laptop).unwrap();
+        assert!(plan.changes.is_empty());
+        assert_eq!(plan.conflicts.len(), 1);
+        assert!(!plan.is_resolved());
+        plan.conflicts[0].choice = Some(Side::Other);
+        apply(&mut office, &mut laptop, &plan, "teste").unwrap();
+        assert_eq!(price(&office, "Cabo 2,5mm"), 3.5);
+        assert_eq!(price(&laptop, "Cabo 2,5mm"), 3.5);
+
+        let again = super::plan(&office, &laptop).unwrap();
+        assert!(again.changes.is_empty() && again.conflicts.is_empty());
+    }
+
+    #[test]
+    fn copied_file_gets_its_own_id() {
+        let office = catalog();
+        let id = super::catalog_id(&office).unwrap();
+        let copy = catalog();
+        crate::settings::set(&copy, super::CATALOG_ID_KEY, &id).unwrap();
+        let (local_id, other_id) = super::catalog_ids(&office, &copy).unwrap();
+        assert_eq!(local_id, id);
+        assert_ne!(other_id, id);
+    }
+}
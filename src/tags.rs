@@ -0,0 +1,197 @@
+use rusqlite::Connection;
+
+use crate::filters::ItemFilter;
+
+pub fn init_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tag (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+        CREATE TABLE IF NOT EXISTS item_tag (
+            item_id INTEGER NOT NULL REFERENCES infra_item(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+            PRIMARY KEY (item_id, tag_id)
+        );
+        CREATE TABLE IF NOT EXISTS saved_filter (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            filter TEXT NOT NULL
+        );",
+    )
+}
+
+/// Separa o texto digitado ("obra-escola, NBR 5410") em tags, sem repetições.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+pub fn set_item_tags(conn: &Connection, item_id: i32, tags: &[String]) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM item_tag WHERE item_id = ?1", [item_id])?;
+    for tag in tags {
+        conn.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", [tag])?;
+        conn.execute(
+            "INSERT OR IGNORE INTO item_tag (item_id, tag_id)
+             SELECT ?1, id FROM tag WHERE name = ?2",
+            (item_id, tag),
+        )?;
+    }
+    // Remove tags que não estão mais em uso
+    conn.execute(
+        "DELETE FROM tag WHERE id NOT IN (SELECT tag_id FROM item_tag)",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn load_tag_names(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM tag ORDER BY name COLLATE NOCASE")?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
+#[derive(Clone)]
+pub struct SavedFilter {
+    pub name: String,
+    pub filter: ItemFilter,
+}
+
+pub fn load_saved_filters(conn: &Connection) -> rusqlite::Result<Vec<SavedFilter>> {
+    let mut stmt = conn.prepare("SELECT name, filter FROM saved_filter ORDER BY name")?;
+    let filters = stmt.query_map([], |row| {
+        let json: String = row.get(1)?;
+        Ok(SavedFilter {
+            name: row.get(0)?,
+            filter: serde_json::from_str(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?,
+        })
+    })?;
+    filters.collect()
+}
+
+pub fn save_filter(
+    conn: &Connection,
+    saved: &SavedFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    conn.execute(
+        "INSERT INTO saved_filter (name, filter) VALUES (?1, ?2)
+        ON CONFLICT(name) DO UPDATE SET filter = excluded.filter",
+        (&saved.name, serde_json::to_string(&saved.filter)?),
+    )?;
+    Ok(())
+}
+
+pub fn delete_saved_filter(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    let affected = conn.execute("DELETE FROM saved_filter WHERE name = ?1", [name])?;
+    Ok(affected > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{
+        SavedFilter, delete_saved_filter, load_saved_filters, load_tag_names, parse_tags,
+        save_filter, set_item_tags,
+    };
+    use crate::filters::{AgeBucket, ItemFilter};
+
+    #[test]
+    fn parses_tags_without_blanks_or_repeats() {
+        assert_eq!(
+            parse_tags(" obra-escola, NBR 5410,,OBRA-ESCOLA , "),
+            ["obra-escola", "NBR 5410"]
+        );
+        assert_eq!(
+            parse_tags("Elétrica,  elétrica  ,ELÉTRICA"),
+            ["Elétrica", "ELÉTRICA"]
+        );
+        assert_eq!(parse_tags("NBR 5410"), ["NBR 5410"]);
+        assert!(parse_tags(" , ").is_empty());
+        assert!(parse_tags("").is_empty());
+    }
+
+    #[test]
+    fn item_tags_round_trip_and_unused_tags_are_removed() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Cabo', '', 'Eletro', 3.5, '2024-01-01'),
+                   (2, 'Fita', '', 'Eletro', 10, '2024-01-01');",
+        )
+        .unwrap();
+        set_item_tags(&conn, 1, &parse_tags(" obra ,NBR 5410,, Obra")).unwrap();
+        set_item_tags(&conn, 2, &parse_tags("Obra")).unwrap();
+        assert_eq!(load_tag_names(&conn).unwrap(), ["NBR 5410", "obra"]);
+        let mut item_tags = crate::items::load_item(&conn, 1).unwrap().unwrap().tags;
+        item_tags.sort();
+        assert_eq!(item_tags, ["NBR 5410", "obra"]);
+        // "Obra" reaproveita a tag "obra" já cadastrada
+        let item_tags = crate::items::load_item(&conn, 2).unwrap().unwrap().tags;
+        assert_eq!(item_tags, ["obra"]);
+
+        set_item_tags(&conn, 1, &[]).unwrap();
+        assert_eq!(load_tag_names(&conn).unwrap(), ["obra"]);
+    }
+
+    #[test]
+    fn saved_filters_are_replaced_and_deleted_by_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let saved = |query: &str| SavedFilter {
+            name: "Obra".to_string(),
+            filter: ItemFilter {
+                query: query.to_string(),
+                ..Default::default()
+            },
+        };
+        save_filter(&conn, &saved("cabo")).unwrap();
+        save_filter(&conn, &saved("fita")).unwrap();
+        let filters = load_saved_filters(&conn).unwrap();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].filter.query, "fita");
+
+        assert!(delete_saved_filter(&conn, "Obra").unwrap());
+        assert!(!delete_saved_filter(&conn, "Obra").unwrap());
+        assert!(load_saved_filters(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn saved_filter_keeps_every_field() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let filter = ItemFilter {
+            query: "cabo".to_string(),
+            tags: vec!["obra-escola".to_string()],
+            vendors: vec!["Elétrica Central".to_string(), "Outro".to_string()],
+            brands: vec!["Marca".to_string()],
+            min_price: "10,00".to_string(),
+            max_price: "1.234,56".to_string(),
+            updated_from: "01/01/2024".to_string(),
+            updated_to: "31/12/2024".to_string(),
+            age: Some(AgeBucket::UpToQuarter),
+            outdated_only: true,
+        };
+        save_filter(
+            &conn,
+            &SavedFilter {
+                name: "Obra".to_string(),
+                filter: filter.clone(),
+            },
+        )
+        .unwrap();
+
+        let saved = load_saved_filters(&conn).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].name, "Obra");
+        assert_eq!(saved[0].filter, filter);
+    }
+}
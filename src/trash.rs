@@ -0,0 +1,214 @@
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::{ITEM_COLUMNS, InfraItem, audit, item_from_row, settings, undo};
+
+const RETENTION_KEY: &str = "trash_retention_days";
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// Adiciona as colunas de exclusão lógica em catálogos criados antes da lixeira.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    crate::add_column_if_missing(conn, "infra_item", "deleted_at", "TEXT")?;
+    crate::add_column_if_missing(conn, "infra_item", "deleted_by", "TEXT")?;
+    Ok(())
+}
+
+pub struct TrashedItem {
+    pub item: InfraItem,
+    pub deleted_at: String,
+    pub deleted_by: String,
+}
+
+pub fn load_trash(conn: &Connection) -> rusqlite::Result<Vec<TrashedItem>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS}, deleted_at, COALESCE(deleted_by, '') AS deleted_by
+         FROM infra_item WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+    ))?;
+    let items = stmt.query_map([], |row| {
+        Ok(TrashedItem {
+            item: item_from_row(row)?,
+            deleted_at: row.get("deleted_at")?,
+            deleted_by: row.get("deleted_by")?,
+        })
+    })?;
+    items.collect()
+}
+
+/// Move os itens para a lixeira. Devolve quantos foram movidos.
+pub fn soft_delete(conn: &mut Connection, ids: &[i32], user: &str) -> rusqlite::Result<usize> {
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let tx = conn.savepoint()?;
+    let mut affected = 0;
+    for id in ids {
+        affected += tx.execute(
+            "UPDATE infra_item SET deleted_at = ?1, deleted_by = ?2
+             WHERE id = ?3 AND deleted_at IS NULL",
+            (&now, user, id),
+        )?;
+    }
+    tx.commit()?;
+    Ok(affected)
+}
+
+pub fn restore(conn: &mut Connection, ids: &[i32]) -> rusqlite::Result<usize> {
+    let tx = conn.savepoint()?;
+    let mut affected = 0;
+    for id in ids {
+        affected += tx.execute(
+            "UPDATE infra_item SET deleted_at = NULL, deleted_by = NULL WHERE id = ?1",
+            [id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(affected)
+}
+
+/// Itens no orçamento não são excluídos definitivamente: a exclusão levaria
+/// junto a linha do orçamento.
+const NOT_IN_BUDGET: &str = "id NOT IN (SELECT item_id FROM budget_item)";
+
+/// Exclui definitivamente itens que estão na lixeira (com tags e histórico),
+/// menos os que estão no orçamento. Devolve quantos foram excluídos.
+pub fn purge(conn: &mut Connection, ids: &[i32]) -> rusqlite::Result<usize> {
+    let tx = conn.savepoint()?;
+    let mut affected = 0;
+    for id in ids {
+        affected += tx.execute(
+            &format!(
+                "DELETE FROM infra_item WHERE id = ?1 AND deleted_at IS NOT NULL AND {NOT_IN_BUDGET}"
+            ),
+            [id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(affected)
+}
+
+/// Exclui definitivamente o que está na lixeira há mais de `days` dias, menos
+/// os itens que estão no orçamento, e registra a exclusão na auditoria.
+pub fn purge_expired(conn: &mut Connection, days: i64) -> rusqlite::Result<usize> {
+    let tx = conn.savepoint()?;
+    let ids: Vec<i32> = tx
+        .prepare(&format!(
+            "SELECT id FROM infra_item
+             WHERE deleted_at IS NOT NULL AND julianday('now') - julianday(deleted_at) > ?1
+               AND {NOT_IN_BUDGET}"
+        ))?
+        .query_map([days], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let before = undo::capture(&tx, &ids)?;
+    for id in &ids {
+        tx.execute("DELETE FROM infra_item WHERE id = ?1", [id])?;
+    }
+    let after = undo::capture(&tx, &ids)?;
+    let origin = format!("Esvaziar a lixeira (mais de {} dias)", days);
+    audit::record_changes(&tx, &origin, &before, &after)?;
+    tx.commit()?;
+    Ok(ids.len())
+}
+
+pub fn retention_days(conn: &Connection) -> i64 {
+    settings::get_i64(conn, RETENTION_KEY, DEFAULT_RETENTION_DAYS)
+}
+
+pub fn set_retention_days(conn: &Connection, days: i64) -> rusqlite::Result<()> {
+    settings::set(conn, RETENTION_KEY, &days.to_string())
+}
+
+/// Estado da janela da lixeira.
+#[derive(Default)]
+pub struct TrashView {
+    pub open: bool,
+    pub items: Vec<TrashedItem>,
+    pub retention_days: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{load_trash, purge, purge_expired, restore, soft_delete};
+    use crate::{audit, items};
+
+    fn catalog() -> (Connection, i32, i32) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let item = |description: &str| items::ItemFields {
+            description: description.to_string(),
+            brand: String::new(),
+            vendor: "Eletro".to_string(),
+            price: 10.0,
+            tags: vec!["obra".to_string()],
+            category: String::new(),
+            unit: String::new(),
+        };
+        let cable = items::upsert_item(&conn, &item("Cabo 2,5mm"), "2024-01-01").unwrap();
+        let socket = items::upsert_item(&conn, &item("Tomada 10A"), "2024-01-01").unwrap();
+        (conn, cable, socket)
+    }
+
+    fn trashed_ids(conn: &Connection) -> Vec<i32> {
+        load_trash(conn)
+            .unwrap()
+            .into_iter()
+            .map(|trashed| trashed.item.id)
+            .collect()
+    }
+
+    #[test]
+    fn deleted_items_go_to_the_trash_and_come_back() {
+        let (mut conn, cable, socket) = catalog();
+        assert_eq!(soft_delete(&mut conn, &[cable], "maria").unwrap(), 1);
+        // Já na lixeira: não conta de novo
+        assert_eq!(soft_delete(&mut conn, &[cable], "joao").unwrap(), 0);
+
+        let trash = load_trash(&conn).unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].deleted_by, "maria");
+        assert_eq!(trash[0].item.tags, ["obra"]);
+        assert!(items::load_item(&conn, cable).unwrap().is_none());
+        assert!(items::load_item(&conn, socket).unwrap().is_some());
+
+        assert_eq!(restore(&mut conn, &[cable]).unwrap(), 1);
+        assert!(trashed_ids(&conn).is_empty());
+        assert!(items::load_item(&conn, cable).unwrap().is_some());
+    }
+
+    #[test]
+    fn purge_only_removes_trashed_items() {
+        let (mut conn, cable, socket) = catalog();
+        soft_delete(&mut conn, &[cable], "maria").unwrap();
+
+        assert_eq!(purge(&mut conn, &[cable, socket]).unwrap(), 1);
+        assert!(trashed_ids(&conn).is_empty());
+        assert!(items::load_item(&conn, socket).unwrap().is_some());
+        let history: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM price_history WHERE item_id = ?1",
+                [cable],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(history, 0);
+    }
+
+    #[test]
+    fn expired_items_are_purged_and_audited() {
+        let (mut conn, cable, socket) = catalog();
+        soft_delete(&mut conn, &[cable, socket], "maria").unwrap();
+        conn.execute(
+            "UPDATE infra_item SET deleted_at = datetime('now', '-31 days') WHERE id = ?1",
+            [cable],
+        )
+        .unwrap();
+
+        assert_eq!(purge_expired(&mut conn, 30).unwrap(), 1);
+        assert_eq!(trashed_ids(&conn), [socket]);
+
+        let entries = audit::load_entries(&conn, "", None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].item_id, cable);
+        assert_eq!(entries[0].action, "exclusão definitiva");
+        assert_eq!(entries[0].origin, "Esvaziar a lixeira (mais de 30 dias)");
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{ITEM_COLUMNS, InfraItem, item_from_row, tags};
+
+const MAX_ENTRIES: usize = 100;
+
+/// Estado completo de um item (com tags, histórico de preços e linha do
+/// orçamento) num instante.
+#[derive(Clone, PartialEq)]
+pub struct ItemState {
+    item: InfraItem,
+    /// `deleted_at` e `deleted_by`, quando o item está na lixeira.
+    deleted: Option<(String, String)>,
+    history: Vec<(i64, f32, String, String)>,
+    /// Quantidade e data de inclusão no orçamento.
+    budget: Option<(f64, String)>,
+}
+
+impl ItemState {
+    pub fn item(&self) -> &InfraItem {
+        &self.item
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+}
+
+/// Estado de cada item afetado; `None` quando o item não existia.
+pub type Snapshot = Vec<(i32, Option<ItemState>)>;
+
+struct UndoEntry {
+    label: String,
+    before: Snapshot,
+    after: Snapshot,
+}
+
+/// Alteração aplicada por desfazer/refazer.
+pub struct Applied {
+    pub label: String,
+}
+
+pub enum Outcome {
+    Applied(Applied),
+    /// Outra pessoa alterou os itens depois do registro: nada foi gravado e a
+    /// entrada saiu da pilha. Contém as descrições dos itens em conflito.
+    Conflict {
+        label: String,
+        items: Vec<String>,
+    },
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<UndoEntry>,
+    redo: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    /// Registra uma alteração. Entradas sem diferença entre antes e depois são ignoradas.
+    pub fn push(&mut self, label: impl Into<String>, before: Snapshot, after: Snapshot) {
+        if before == after {
+            return;
+        }
+        self.undo.push(UndoEntry {
+            label: label.into(),
+            before,
+            after,
+        });
+        if self.undo.len() > MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|e| e.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|e| e.label.as_str())
+    }
+
+    /// Desfaz a última alteração e devolve sua descrição. `audit` recebe a
+    /// descrição e os retratos (de, para) aplicados dentro da mesma
+    /// transação; se falhar, nada é desfeito.
+    pub fn undo(
+        &mut self,
+        conn: &mut Connection,
+        audit: impl FnOnce(&Connection, &str, &Snapshot, &Snapshot) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<Option<Outcome>> {
+        let Some(mut entry) = self.undo.pop() else {
+            return Ok(None);
+        };
+        let recorded = restore(conn, &entry.after, &entry.before, |tx, restored| {
+            audit(tx, &entry.label, &entry.after, restored)
+        });
+        entry.before = match recorded {
+            Ok(Ok(restored)) => {
+                self.renumber(&entry.before, &restored);
+                restored
+            }
+            Ok(Err(items)) => {
+                return Ok(Some(Outcome::Conflict {
+                    label: entry.label,
+                    items,
+                }));
+            }
+            Err(e) => {
+                self.undo.push(entry);
+                return Err(e);
+            }
+        };
+        let applied = Applied {
+            label: entry.label.clone(),
+        };
+        self.redo.push(entry);
+        Ok(Some(Outcome::Applied(applied)))
+    }
+
+    /// Refaz a última alteração desfeita, como `undo`.
+    pub fn redo(
+        &mut self,
+        conn: &mut Connection,
+        audit: impl FnOnce(&Connection, &str, &Snapshot, &Snapshot) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<Option<Outcome>> {
+        let Some(mut entry) = self.redo.pop() else {
+            return Ok(None);
+        };
+        let recorded = restore(conn, &entry.before, &entry.after, |tx, restored| {
+            audit(tx, &entry.label, &entry.before, restored)
+        });
+        entry.after = match recorded {
+            Ok(Ok(restored)) => {
+                self.renumber(&entry.after, &restored);
+                restored
+            }
+            Ok(Err(items)) => {
+                return Ok(Some(Outcome::Conflict {
+                    label: entry.label,
+                    items,
+                }));
+            }
+            Err(e) => {
+                self.redo.push(entry);
+                return Err(e);
+            }
+        };
+        let applied = Applied {
+            label: entry.label.clone(),
+        };
+        self.undo.push(entry);
+        Ok(Some(Outcome::Applied(applied)))
+    }
+
+    /// Gravar `target` dá novas versões aos itens: atualiza as outras entradas
+    /// que registram esse mesmo estado, para que não pareçam alteradas por
+    /// outra pessoa.
+    fn renumber(&mut self, target: &Snapshot, restored: &Snapshot) {
+        let versions: HashMap<i32, (i64, i64)> = target
+            .iter()
+            .zip(restored)
+            .filter_map(|((id, old), (_, new))| {
+                Some((
+                    *id,
+                    (old.as_ref()?.item.version, new.as_ref()?.item.version),
+                ))
+            })
+            .collect();
+        for entry in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for (id, state) in entry.before.iter_mut().chain(entry.after.iter_mut()) {
+                if let Some(state) = state
+                    && let Some((old, new)) = versions.get(id)
+                    && state.item.version == *old
+                {
+                    state.item.version = *new;
+                }
+            }
+        }
+    }
+}
+
+fn load_state(conn: &Connection, id: i32) -> rusqlite::Result<Option<ItemState>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS}, deleted_at, COALESCE(deleted_by, '') AS deleted_by
+         FROM infra_item WHERE id = ?1"
+    ))?;
+    let mut rows = stmt.query_map([id], |row| {
+        let deleted_at: Option<String> = row.get("deleted_at")?;
+        let deleted = deleted_at
+            .map(|at| row.get("deleted_by").map(|by| (at, by)))
+            .transpose()?;
+        Ok((item_from_row(row)?, deleted))
+    })?;
+    let Some((item, deleted)) = rows.next().transpose()? else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, price, recorded_at, source FROM price_history WHERE item_id = ?1 ORDER BY id",
+    )?;
+    let history = stmt
+        .query_map([id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let budget = conn
+        .query_row(
+            "SELECT quantity, added_at FROM budget_item WHERE item_id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(Some(ItemState {
+        item,
+        deleted,
+        history,
+        budget,
+    }))
+}
+
+/// Captura o estado atual dos itens indicados.
+pub fn capture(conn: &Connection, ids: &[i32]) -> rusqlite::Result<Snapshot> {
+    ids.iter()
+        .map(|id| Ok((*id, load_state(conn, *id)?)))
+        .collect()
+}
+
+/// Captura o catálogo inteiro; usado em operações que podem tocar qualquer item
+/// (importações).
+pub fn capture_all(conn: &Connection) -> rusqlite::Result<HashMap<i32, ItemState>> {
+    let mut stmt = conn.prepare("SELECT id FROM infra_item")?;
+    let ids: Vec<i32> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut states = HashMap::with_capacity(ids.len());
+    for id in ids {
+        if let Some(state) = load_state(conn, id)? {
+            states.insert(id, state);
+        }
+    }
+    Ok(states)
+}
+
+/// Compara dois retratos completos e devolve (antes, depois) só dos itens alterados.
+pub fn diff(
+    mut before: HashMap<i32, ItemState>,
+    mut after: HashMap<i32, ItemState>,
+) -> (Snapshot, Snapshot) {
+    let mut ids: Vec<i32> = before.keys().chain(after.keys()).copied().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut snapshot_before = vec![];
+    let mut snapshot_after = vec![];
+    for id in ids {
+        let b = before.remove(&id);
+        let a = after.remove(&id);
+        if b != a {
+            snapshot_before.push((id, b));
+            snapshot_after.push((id, a));
+        }
+    }
+    (snapshot_before, snapshot_after)
+}
+
+/// Descrições dos itens cuja versão atual não é mais a do retrato `expected`
+/// (incluindo os criados ou apagados desde então).
+fn conflicts(conn: &Connection, expected: &Snapshot) -> rusqlite::Result<Vec<String>> {
+    let mut items = vec![];
+    for (id, state) in expected {
+        let current = load_state(conn, *id)?;
+        let version = |state: &Option<ItemState>| state.as_ref().map(|s| s.item.version);
+        if version(&current) != version(state) {
+            let description = current.as_ref().or(state.as_ref()).map_or_else(
+                || format!("item {}", id),
+                |state| state.item.description.clone(),
+            );
+            items.push(description);
+        }
+    }
+    Ok(items)
+}
+
+/// Leva os itens do retrato `from` ao retrato `to`, numa única transação,
+/// que também recebe o que `record` gravar sobre o estado restaurado.
+/// Se algum item não estiver mais como em `from`, nada é alterado e as
+/// descrições dos itens em conflito são devolvidas. Senão, devolve o estado
+/// gravado, com as novas versões dos itens.
+fn restore(
+    conn: &mut Connection,
+    from: &Snapshot,
+    snapshot: &Snapshot,
+    record: impl FnOnce(&Connection, &Snapshot) -> rusqlite::Result<()>,
+) -> rusqlite::Result<Result<Snapshot, Vec<String>>> {
+    let tx = conn.savepoint()?;
+    let items = conflicts(&tx, from)?;
+    if !items.is_empty() {
+        return Ok(Err(items));
+    }
+    // Remove primeiro os itens que não deveriam existir, para liberar a chave
+    // única, e o histórico de todos: uma mesclagem move linhas de histórico
+    // de um item para outro, e os ids voltariam repetidos
+    for (id, state) in snapshot {
+        if state.is_none() {
+            tx.execute("DELETE FROM infra_item WHERE id = ?1", [id])?;
+        }
+        tx.execute("DELETE FROM price_history WHERE item_id = ?1", [id])?;
+    }
+    let budget = |snapshot: &Snapshot, id: i32| {
+        snapshot
+            .iter()
+            .find(|(other, _)| *other == id)
+            .and_then(|(_, state)| state.as_ref()?.budget.clone())
+    };
+    for (id, state) in snapshot {
+        let Some(state) = state else {
+            continue;
+        };
+        let item = &state.item;
+        let values = (
+            id,
+            &item.description,
+            &item.brand,
+            &item.vendor,
+            item.price,
+            &item.updated_at,
+            &item.category,
+            &item.unit,
+        );
+        let updated = tx.execute(
+            "UPDATE infra_item SET description = ?2, brand = ?3, vendor = ?4, price = ?5,
+                updated_at = ?6, category = ?7, unit = ?8
+            WHERE id = ?1",
+            values,
+        )?;
+        if updated == 0 {
+            tx.execute(
+                "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at, category, unit)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                values,
+            )?;
+        }
+        let (deleted_at, deleted_by) = state.deleted.clone().unzip();
+        tx.execute(
+            "UPDATE infra_item SET deleted_at = ?1, deleted_by = ?2 WHERE id = ?3",
+            (deleted_at, deleted_by, id),
+        )?;
+        tags::set_item_tags(&tx, *id, &item.tags)?;
+
+        // A linha do orçamento só volta quando a alteração mexeu nela: as
+        // quantidades editadas depois no orçamento ficam como estão
+        if budget(from, *id) != state.budget {
+            tx.execute("DELETE FROM budget_item WHERE item_id = ?1", [id])?;
+            if let Some((quantity, added_at)) = &state.budget {
+                tx.execute(
+                    "INSERT INTO budget_item (item_id, quantity, added_at) VALUES (?1, ?2, ?3)",
+                    (id, quantity, added_at),
+                )?;
+            }
+        }
+        for (history_id, price, recorded_at, source) in &state.history {
+            tx.execute(
+                "INSERT INTO price_history (id, item_id, price, recorded_at, source)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                (history_id, id, price, recorded_at, source),
+            )?;
+        }
+    }
+    let ids: Vec<i32> = snapshot.iter().map(|(id, _)| *id).collect();
+    let restored = capture(&tx, &ids)?;
+    record(&tx, &restored)?;
+    tx.commit()?;
+    Ok(Ok(restored))
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{Outcome, UndoStack, capture};
+    use crate::{audit, budget, duplicates, history};
+
+    fn label(result: rusqlite::Result<Option<Outcome>>) -> Option<String> {
+        match result.unwrap() {
+            Some(Outcome::Applied(applied)) => Some(applied.label),
+            Some(Outcome::Conflict { .. }) => panic!("conflito inesperado"),
+            None => None,
+        }
+    }
+
+    fn set_price(conn: &Connection, id: i32, price: f32) {
+        conn.execute(
+            "UPDATE infra_item SET price = ?1 WHERE id = ?2",
+            (price, id),
+        )
+        .unwrap();
+    }
+
+    fn price(conn: &Connection, id: i32) -> Option<f32> {
+        conn.query_row("SELECT price FROM infra_item WHERE id = ?1", [id], |row| {
+            row.get(0)
+        })
+        .ok()
+    }
+
+    /// Catálogo com um item incluído e depois editado, com as duas
+    /// alterações no desfazer.
+    fn edited_catalog() -> (Connection, UndoStack, i32) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        let mut stack = UndoStack::default();
+
+        conn.execute(
+            "INSERT INTO infra_item (description, brand, vendor, price, updated_at, category, unit)
+            VALUES ('Disjuntor DIN 20A', '', 'Fornecedor', 10.0, '2024-01-10', 'Proteção', 'pç')",
+            [],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid() as i32;
+        crate::tags::set_item_tags(&conn, id, &["proteção".to_string()]).unwrap();
+        let after = capture(&conn, &[id]).unwrap();
+        stack.push("Incluir", vec![(id, None)], after.clone());
+
+        set_price(&conn, id, 12.5);
+        stack.push("Editar", after, capture(&conn, &[id]).unwrap());
+        (conn, stack, id)
+    }
+
+    #[test]
+    fn push_ignores_unchanged_snapshots() {
+        let (conn, mut stack, id) = edited_catalog();
+        let snapshot = capture(&conn, &[id]).unwrap();
+        stack.push("Nada", snapshot.clone(), snapshot);
+        assert_eq!(stack.undo_label(), Some("Editar"));
+        assert_eq!(stack.redo_label(), None);
+    }
+
+    #[test]
+    fn undo_and_redo_restore_snapshots() {
+        let (mut conn, mut stack, id) = edited_catalog();
+
+        assert_eq!(
+            label(stack.undo(&mut conn, audit::record_changes)),
+            Some("Editar".to_string())
+        );
+        assert_eq!(price(&conn, id), Some(10.0));
+        assert_eq!(
+            label(stack.undo(&mut conn, audit::record_changes)),
+            Some("Incluir".to_string())
+        );
+        assert_eq!(price(&conn, id), None);
+        assert!(
+            stack
+                .undo(&mut conn, audit::record_changes)
+                .unwrap()
+                .is_none()
+        );
+
+        // Refazer depois de desfazer: as versões gravadas pelo desfazer valem
+        assert_eq!(
+            label(stack.redo(&mut conn, audit::record_changes)),
+            Some("Incluir".to_string())
+        );
+        assert_eq!(
+            label(stack.redo(&mut conn, audit::record_changes)),
+            Some("Editar".to_string())
+        );
+        let snapshot = capture(&conn, &[id]).unwrap();
+        let state = snapshot[0].1.as_ref().unwrap();
+        assert_eq!(state.item.price, 12.5);
+        assert_eq!(state.item.category, "Proteção");
+        assert_eq!(state.item.unit, "pç");
+        assert_eq!(state.item.tags, ["proteção"]);
+        assert_eq!(stack.undo_label(), Some("Editar"));
+
+        // Cada desfazer e refazer aplicado ficou na auditoria
+        let origins: Vec<String> = audit::load_entries(&conn, "", None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.origin)
+            .collect();
+        assert_eq!(origins, ["Editar", "Incluir", "Incluir", "Editar"]);
+    }
+
+    #[test]
+    fn undo_refuses_items_changed_by_someone_else() {
+        let (mut conn, mut stack, id) = edited_catalog();
+        // Edição feita por outra instância, fora desta pilha
+        set_price(&conn, id, 15.0);
+
+        match stack.undo(&mut conn, audit::record_changes) {
+            Ok(Some(Outcome::Conflict { label, items })) => {
+                assert_eq!(label, "Editar");
+                assert_eq!(items, ["Disjuntor DIN 20A"]);
+            }
+            _ => panic!("esperava conflito"),
+        }
+        assert_eq!(price(&conn, id), Some(15.0));
+        assert_eq!(stack.undo_label(), Some("Incluir"));
+        assert!(audit::load_entries(&conn, "", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn undo_merge_restores_history_and_budget() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO infra_item (id, description, brand, vendor, price, updated_at)
+            VALUES (1, 'Disjuntor DIN 20A bipolar', '', 'Eletro', 30, '2024-01-01'),
+                   (2, 'Disjuntor bipolar 20 A DIN', '', 'Eletro', 32, '2024-02-01');",
+        )
+        .unwrap();
+        history::record_price(&conn, 1, 30.0, "2024-01-01", "cadastro").unwrap();
+        history::record_price(&conn, 2, 32.0, "2024-02-01", "cadastro").unwrap();
+        budget::add_items(&mut conn, &[1, 2]).unwrap();
+
+        // Na ordem da lista de duplicados: o item mesclado antes do mantido
+        let ids = [2, 1];
+        let before = capture(&conn, &ids).unwrap();
+        let options = duplicates::MergeOptions {
+            combine_history: true,
+            repoint_budget: true,
+        };
+        duplicates::merge(&mut conn, 1, &[2], options, "teste").unwrap();
+        let mut stack = UndoStack::default();
+        stack.push("Mesclar", before.clone(), capture(&conn, &ids).unwrap());
+
+        assert_eq!(
+            label(stack.undo(&mut conn, audit::record_changes)),
+            Some("Mesclar".to_string())
+        );
+        assert_eq!(history::load_history(&conn, 1).unwrap().len(), 1);
+        assert_eq!(history::load_history(&conn, 2).unwrap().len(), 1);
+        let lines = budget::load_lines(&conn).unwrap();
+        let quantities: Vec<(i32, f64)> = lines
+            .iter()
+            .map(|line| (line.item.id, line.quantity))
+            .collect();
+        assert_eq!(quantities, [(1, 1.0), (2, 1.0)]);
+        assert!(crate::items::load_item(&conn, 2).unwrap().is_some());
+
+        assert_eq!(
+            label(stack.redo(&mut conn, audit::record_changes)),
+            Some("Mesclar".to_string())
+        );
+        assert_eq!(history::load_history(&conn, 1).unwrap().len(), 2);
+        assert_eq!(budget::load_lines(&conn).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use eframe::egui::Ui;
+
+use crate::{InfraItem, items::ItemFields, money};
+
+/// Erros do formulário de cadastro, mostrados ao lado de cada campo.
+#[derive(Default)]
+pub struct FormErrors {
+    pub description: Option<String>,
+    pub vendor: Option<String>,
+    pub price: Option<String>,
+}
+
+impl FormErrors {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.vendor.is_none() && self.price.is_none()
+    }
+
+    /// Primeiro campo com erro, na ordem do formulário, para receber o foco.
+    pub fn first_field(&self) -> Option<&'static str> {
+        [
+            ("description", &self.description),
+            ("vendor", &self.vendor),
+            ("price", &self.price),
+        ]
+        .into_iter()
+        .find_map(|(field, error)| error.is_some().then_some(field))
+    }
+}
+
+/// Confere os campos obrigatórios e o preço do formulário; devolve o preço
+/// lido ou os erros de cada campo.
+pub fn validate(description: &str, vendor: &str, price: &str) -> Result<f32, FormErrors> {
+    let mut errors = FormErrors::default();
+    if description.trim().is_empty() {
+        errors.description = Some("Informe a descrição".to_string());
+    }
+    if vendor.trim().is_empty() {
+        errors.vendor = Some("Informe o fornecedor".to_string());
+    }
+    let price = money::parse_price(price).map_err(|e| e.to_string());
+    match price {
+        Ok(price) if errors.is_empty() => Ok(price),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.price = Some(e);
+            Err(errors)
+        }
+    }
+}
+
+/// Outro item ativo com a mesma descrição, marca e fornecedor, a chave que o
+/// catálogo não deixa repetir.
+pub fn key_conflict<'a>(
+    items: &'a [InfraItem],
+    id: i32,
+    fields: &ItemFields,
+) -> Option<&'a InfraItem> {
+    items.iter().find(|item| {
+        item.id != id
+            && item.description == fields.description
+            && item.brand == fields.brand
+            && item.vendor == fields.vendor
+    })
+}
+
+/// Edição que deixaria o item igual a outro já cadastrado: o usuário escolhe
+/// entre mesclar no existente ou continuar editando.
+pub struct KeyConflict {
+    /// Item em edição.
+    pub editing: i32,
+    pub existing: InfraItem,
+    pub fields: ItemFields,
+}
+
+/// Mensagem de erro ao lado do campo, se houver.
+pub fn show_error(ui: &mut Ui, error: &Option<String>) {
+    if let Some(error) = error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key_conflict, validate};
+    use crate::{InfraItem, items::ItemFields};
+
+    #[test]
+    fn each_field_gets_its_own_message() {
+        let errors = validate(" ", "", "-3").unwrap_err();
+        assert_eq!(errors.description.as_deref(), Some("Informe a descrição"));
+        assert_eq!(errors.vendor.as_deref(), Some("Informe o fornecedor"));
+        assert_eq!(
+            errors.price.as_deref(),
+            Some("O preço não pode ser negativo")
+        );
+        assert_eq!(errors.first_field(), Some("description"));
+
+        let errors = validate("Cabo 2,5mm", "Eletro", "").unwrap_err();
+        assert_eq!(errors.first_field(), Some("price"));
+        assert_eq!(
+            validate("Cabo 2,5mm", "Eletro", "R$ 1.234,50").ok(),
+            Some(1234.5)
+        );
+    }
+
+    #[test]
+    fn conflict_ignores_the_item_being_edited() {
+        let item = |id: i32, brand: &str| InfraItem {
+            id,
+            description: "Cabo 2,5mm".to_string(),
+            brand: brand.to_string(),
+            vendor: "Eletro".to_string(),
+            price: 3.5,
+            updated_at: "2024-01-01".to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: String::new(),
+        };
+        let items = [item(1, "Sil"), item(2, "Cobrecom")];
+        let fields = |brand: &str| ItemFields {
+            description: "Cabo 2,5mm".to_string(),
+            brand: brand.to_string(),
+            vendor: "Eletro".to_string(),
+            price: 3.5,
+            tags: vec![],
+            category: String::new(),
+            unit: String::new(),
+        };
+        assert_eq!(
+            key_conflict(&items, 1, &fields("Cobrecom")).map(|i| i.id),
+            Some(2)
+        );
+        assert!(key_conflict(&items, 1, &fields("Sil")).is_none());
+        assert!(key_conflict(&items, 1, &fields("Outra")).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::InfraItem;
+
+/// O mesmo material em outros fornecedores: itens com a mesma descrição
+/// (sem diferenciar maiúsculas e espaços nas pontas), do mais barato ao mais caro.
+pub fn other_vendors(items: &[InfraItem], item: &InfraItem) -> Vec<InfraItem> {
+    let description = item.description.trim().to_lowercase();
+    let mut others: Vec<InfraItem> = items
+        .iter()
+        .filter(|other| {
+            other.id != item.id && other.description.trim().to_lowercase() == description
+        })
+        .cloned()
+        .collect();
+    others.sort_by(|a, b| a.price.total_cmp(&b.price));
+    others
+}
+
+/// Janela "Outros fornecedores" do menu da lista.
+#[derive(Default)]
+pub struct OtherVendorsView {
+    pub item: Option<InfraItem>,
+    pub others: Vec<InfraItem>,
+}
+
+/// Janela com os dados de um fornecedor, aberta pelo menu da lista.
+#[derive(Default)]
+pub struct VendorView {
+    pub vendor: Option<String>,
+    pub contact: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::other_vendors;
+    use crate::InfraItem;
+
+    fn item(id: i32, description: &str, vendor: &str, price: f32) -> InfraItem {
+        InfraItem {
+            id,
+            description: description.to_string(),
+            brand: String::new(),
+            vendor: vendor.to_string(),
+            price,
+            updated_at: "2024-01-01".to_string(),
+            tags: vec![],
+            version: 0,
+            category: String::new(),
+            unit: String::new(),
+        }
+    }
+
+    #[test]
+    fn finds_same_description_sorted_by_price() {
+        let items = [
+            item(1, "Disjuntor 20A", "A", 15.0),
+            item(2, "disjuntor 20A ", "B", 12.0),
+            item(3, "Disjuntor 25A", "C", 9.0),
+            item(4, "Disjuntor 20A", "D", 13.5),
+        ];
+        let others = other_vendors(&items, &items[0]);
+        let vendors: Vec<&str> = others.iter().map(|o| o.vendor.as_str()).collect();
+        assert_eq!(vendors, ["B", "D"]);
+    }
+}